@@ -2,6 +2,7 @@
 #define LIBWING_H
 
 #include <stdint.h>
+#include <stddef.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -9,10 +10,34 @@ extern "C" {
 
 typedef struct WingDiscoveryInfo WingDiscoveryInfo;
 typedef struct WingConsole WingConsole;
-typedef struct WingMeter WingMeter;
 typedef struct Response Response;
+typedef struct WingDefinitions WingDefinitions;
+
+// Version of this header. wing_api_version() returns the version of the library, which works with
+// this header if its major version is the same and its minor version is not lower.
+#define WING_API_VERSION_MAJOR 2
+#define WING_API_VERSION_MINOR 1
+#define WING_API_VERSION ((WING_API_VERSION_MAJOR << 16) | WING_API_VERSION_MINOR)
 
 // Enums
+// Errors. Functions returning int return WING_OK, or 1/0 for yes/no, or a count, unless there
+// was an error, which is always negative. Functions returning a pointer return NULL on an error,
+// and the others return 0. wing_last_error() and wing_last_error_message() then tell what went
+// wrong. Every function catches errors inside the library, so they never abort the program.
+typedef enum {
+    WING_OK = 0,
+    WING_ERROR_INVALID_ARGUMENT = -1, // a NULL handle or pointer, a string that isn't UTF-8, or an index or code out of range
+    WING_ERROR_IO = -2,
+    WING_ERROR_CONNECTION = -3,
+    WING_ERROR_DISCOVERY = -4,
+    WING_ERROR_INVALID_DATA = -5,
+    WING_ERROR_WRONG_RESPONSE_TYPE = -6, // e.g. wing_node_data_get_float() on a node definition
+    WING_ERROR_METER = -7,
+    WING_ERROR_TIMEOUT = -8,
+    WING_ERROR_INTERNAL = -9, // a bug in the library
+    WING_ERROR_BUFFER_TOO_SMALL = -10 // the sizes needed were returned, see wing_console_read_meter_frame()
+} WingErrorCode;
+
 typedef enum {
     WING_RESPONSE_END = 0,
     WING_RESPONSE_NODE_DEFINITION = 1,
@@ -60,30 +85,94 @@ typedef enum {
     MATRIX2 = 0xAF
 } MeterType;
 #define METER_ID(type, index) (((type << 8) | (index & 0xFF)) & 0xFFFF)
+#define METER_TYPE(id) ((MeterType)(((id) >> 8) & 0xFF))
+#define METER_INDEX(id) ((id) & 0xFF)
+
+// One meter of a frame from wing_console_read_meter_frame(). Its values are
+// values[offset] to values[offset + count - 1]: levels in dB, in the order the Wing sends them, or
+// for CHANNEL2 to MATRIX2 whether the gate and the dynamics are active, as 0 or 1.
+typedef struct {
+    uint16_t meter;  // METER_ID() of the meter
+    uint16_t count;  // 8 for strips, 4 for DCA, FX and MONITOR, 1 for SOURCE and OUTPUT, 120 for RTA, 2 for the states
+    uint32_t offset;
+} WingMeterSource;
+
+// Callbacks for wing_console_set_response_callback() and wing_console_set_meter_callback().
+// The response, sources and values are only valid during the call; don't destroy or keep them.
+// A meter frame is laid out like the ones from wing_console_read_meter_frame().
+typedef void (*WingResponseCallback)(void* user_data, const Response* response);
+typedef void (*WingMeterCallback)(void* user_data, uint16_t id, const WingMeterSource* sources, size_t source_count,
+                                  const float* values, size_t value_count);
+
+uint32_t           wing_api_version                               (void); // see WING_API_VERSION
+WingErrorCode      wing_last_error                                (void); // the error of the last call that failed on this thread
+const char*        wing_last_error_message                        (void); // Don't free; valid until the next call that fails on this thread
 
 WingDiscoveryInfo* wing_discover_scan                             (int stop_on_first); // Return value must be freed by wing_discover_destroy()
+// Sends the probe only to ip and port, without broadcasting, and waits up to timeout_ms for the answer
+WingDiscoveryInfo* wing_discover_scan_target                      (const char* ip, uint16_t port, int timeout_ms); // Return value must be freed by wing_discover_destroy()
 int                wing_discover_count                            (const WingDiscoveryInfo* handle);
 const char*        wing_discover_get_ip                           (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
 const char*        wing_discover_get_name                         (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
 const char*        wing_discover_get_model                        (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
 const char*        wing_discover_get_serial                       (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
 const char*        wing_discover_get_firmware                     (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
+const char*        wing_discover_get_interface                    (const WingDiscoveryInfo* handle, int index); // Return value must be free by wing_string_destroy()
 void               wing_discover_destroy                          (WingDiscoveryInfo* handle);
 
 WingConsole*       wing_console_connect                           (const char* ip); // Return value must be freed by wing_console_destroy()
+WingConsole*       wing_console_connect_port                      (const char* ip, uint16_t port); // Return value must be freed by wing_console_destroy()
+WingConsole*       wing_console_connect_to                        (const char* name_or_serial, int timeout_ms); // Return value must be freed by wing_console_destroy()
 Response*          wing_console_read                              (WingConsole* handle); // Return value must be freed by wing_response_destroy()
 int                wing_console_set_string                        (WingConsole* handle, int32_t id, const char* value);
 int                wing_console_set_float                         (WingConsole* handle, int32_t id, float value);
 int                wing_console_set_int                           (WingConsole* handle, int32_t id, int value);
 int                wing_console_request_node_definition           (WingConsole* handle, int32_t id);
 int                wing_console_request_node_data                 (WingConsole* handle, int32_t id);
-uint16_t           wing_console_request_meter                     (WingConsole* handle, uint16_t *meter_ids, size_t len); // see above about meter ids
-int                wing_console_read_meter                        (WingConsole* handle, WingMeter* meter, uint16_t *out_id, int16_t *out_data);
+uint16_t           wing_console_request_meter                     (WingConsole* handle, const uint16_t* meter_ids, size_t len); // see above about meter ids; returns 0 on error
+int                wing_console_cancel_meter                      (WingConsole* handle, uint16_t id);
+int                wing_meter_value_count                         (uint16_t meter); // number of values of a METER_ID()
+// The sizes of the frames of a meter subscription: the number of WingMeterSources and of values
+int                wing_console_meter_size                        (WingConsole* handle, uint16_t id, size_t* out_sources, size_t* out_values);
+// Waits up to timeout_ms (-1 waits forever) for the next meter frame, and writes its subscription
+// id, its sources and its values. out_sources and out_values are set to the number written.
+// If the buffers are too small, returns WING_ERROR_BUFFER_TOO_SMALL with out_sources and
+// out_values set to the sizes needed, and the same frame is returned by the next call.
+// Don't use this and a meter callback at the same time.
+int                wing_console_read_meter_frame                  (WingConsole* handle, int timeout_ms, uint16_t* out_id,
+                                                                   WingMeterSource* sources, size_t sources_capacity, size_t* out_sources,
+                                                                   float* values, size_t values_capacity, size_t* out_values);
+
+// Events without blocking: the first call to wing_console_poll() or
+// wing_console_set_response_callback() starts a thread in the library that reads the console's
+// responses. From then on, use these instead of wing_console_read(). The first call to
+// wing_console_set_meter_callback() starts another thread, which only reads meters.
+//
+// - wing_console_poll() returns the next response, waiting up to timeout_ms (-1 waits forever),
+//   or NULL with WING_ERROR_TIMEOUT if there was none. Responses are queued for it while no
+//   response callback is set. The queue keeps the latest 1024 responses;
+//   wing_console_dropped_responses() returns how many older ones were dropped since it was last
+//   called.
+// - The response callback is called on the first thread for node data, node definitions and
+//   request ends. The meter callback is called on the second thread for each meter frame of a
+//   subscription. Each callback is never called concurrently with itself, but the two can run at
+//   the same time.
+// - Pass NULL as the callback to remove it. Once a set function returns, the previous callback
+//   is no longer running and won't be called again, so don't call the set functions or
+//   wing_console_destroy() from inside a callback.
+// - The other wing_console_* functions, like setting values or requesting data, can be called
+//   from a callback.
+// - wing_console_destroy() stops the threads and waits for them.
+Response*          wing_console_poll                              (WingConsole* handle, int timeout_ms); // Return value must be freed by wing_response_destroy()
+size_t             wing_console_dropped_responses                 (WingConsole* handle);
+int                wing_console_set_response_callback             (WingConsole* handle, WingResponseCallback callback, void* user_data);
+int                wing_console_set_meter_callback                (WingConsole* handle, WingMeterCallback callback, void* user_data);
 void               wing_console_destroy                           (WingConsole* handle);
 
 WingResponseType   wing_response_get_type                         (const Response* handle);
 void               wing_response_destroy                          (Response* handle);
 
+int32_t            wing_node_data_get_id                          (const Response* handle);
 const char*        wing_node_data_get_string                      (const Response* handle); // Return value must be free by wing_string_destroy()
 float              wing_node_data_get_float                       (const Response* handle);
 int                wing_node_data_get_int                         (const Response* handle);
@@ -99,6 +188,8 @@ WingNodeUnit       wing_node_definition_get_unit                  (const Respons
 const char*        wing_node_definition_get_name                  (const Response* handle); // Return value must be free by wing_string_destroy()
 const char*        wing_node_definition_get_long_name             (const Response* handle); // Return value must be free by wing_string_destroy()
 int                wing_node_definition_is_read_only              (const Response* handle);
+// These return 1 and set *ret if the definition has the value, or 0 if it doesn't. A string in
+// *ret must be freed by wing_string_destroy().
 int                wing_node_definition_get_min_float             (const Response* handle, float* ret);
 int                wing_node_definition_get_max_float             (const Response* handle, float* ret);
 int                wing_node_definition_get_steps                 (const Response* handle, int* ret);
@@ -112,7 +203,21 @@ int                wing_node_definition_get_float_enum_long_item  (const Respons
 int                wing_node_definition_get_string_enum_item      (const Response* handle, int index, const char** ret);
 int                wing_node_definition_get_string_enum_long_item (const Response* handle, int index, const char** ret);
 
-int                wing_name_to_id                                (const char* name, int32_t* out_id);
+int                wing_name_to_id                                (const char* name, int32_t* out_id); // 1 if found, 0 if not
+
+// Lookups in the property map built into the library, without asking a console. The definitions
+// are Responses, so the wing_node_definition_* functions work on them.
+int                wing_name_to_def                               (const char* name, Response** ret); // 1 if found, 0 if not; *ret must be freed by wing_response_destroy()
+WingDefinitions*   wing_id_to_defs                                (int32_t id); // every name of the id, or none if unknown; Return value must be freed by wing_definitions_destroy()
+WingDefinitions*   wing_find_names                                (const char* pattern); // e.g. "/ch/*/fdr" or "/bus/{1..8}/mute", sorted by name; Return value must be freed by wing_definitions_destroy()
+int                wing_definitions_count                         (const WingDefinitions* handle);
+const char*        wing_definitions_get_name                      (const WingDefinitions* handle, int index); // Return value must be free by wing_string_destroy()
+Response*          wing_definitions_get_definition                (const WingDefinitions* handle, int index); // Return value must be freed by wing_response_destroy()
+const char*        wing_definitions_to_json                       (const WingDefinitions* handle); // an array like wingprop -j prints; Return value must be free by wing_string_destroy()
+void               wing_definitions_destroy                       (WingDefinitions* handle);
+
+const char*        wing_node_definition_to_json                   (const Response* handle); // Return value must be free by wing_string_destroy()
+const char*        wing_node_definition_to_description            (const Response* handle); // like wingprop prints; Return value must be free by wing_string_destroy()
 
 // you must call this to free the memory of any string returned by the library
 void               wing_string_destroy                            (const char* handle);
@@ -0,0 +1,459 @@
+//! Generates the typed parameter API from a schema.
+//!
+//! Property paths are turned into chains of method calls. Numbered path segments become an
+//! index argument, so `/ch/1/fdr` is `wing.ch(1).fdr()` and `/ch/1/eq/2/f` is
+//! `wing.ch(1).eq().at(2).f()`. Each property's Rust type comes from its `NodeType`, with an
+//! enum generated for every distinct list of `string_enum`/`float_enum` items. The property ids
+//! for every valid combination of indexes are built into the generated code, so it does not need
+//! the property map at runtime. A property that also has children of its own keeps its method,
+//! and its children are reached through a second method with `_node` appended. See the `typed`
+//! module for how the generated code is used.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::node::{WingNodeDef, NodeType};
+use crate::schema::Schema;
+
+/// Returns the source of a Rust module with the typed API for every property in the schema
+pub fn generate(schema: &Schema) -> String {
+    let mut root = Shape::default();
+    for (fullname, def) in schema.iter() {
+        root.add(fullname, def);
+    }
+
+    let mut g = Generator::default();
+    let mut out = String::new();
+    writeln!(out, "// Generated by libwing::codegen from a schema with {} entries. Do not edit.", schema.len()).unwrap();
+    writeln!(out, "#![allow(dead_code, clippy::all)]").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use libwing::{{WingConsole, WingNodeData}};").unwrap();
+    writeln!(out, "use libwing::typed::{{Param, ReadOnlyParam, ParamValue, lookup}};").unwrap();
+    writeln!(out).unwrap();
+
+    let root_methods = g.methods(&root, &[], &Receiver::Console);
+    writeln!(out, "/// Typed access to the Wing's properties").unwrap();
+    writeln!(out, "pub trait Params {{").unwrap();
+    for m in &root_methods {
+        writeln!(out, "{}    {};", m.doc, m.signature).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "impl Params for WingConsole {{").unwrap();
+    for m in &root_methods {
+        writeln!(out, "    {} {{\n{}    }}", m.signature, m.body).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out.push_str(&g.structs);
+    out.push_str(&g.enums);
+    out
+}
+
+/// The merged shape of all paths that only differ in their numbered segments
+#[derive(Default)]
+struct Shape {
+    /// The path segments, `None` for numbered ones
+    path: Vec<Option<String>>,
+    named: BTreeMap<String, Shape>,
+    indexed: Option<Box<Shape>>,
+    indexes: BTreeSet<u16>,
+    leaf: Option<WingNodeDef>,
+    ids: BTreeMap<Vec<u16>, i32>,
+}
+
+impl Shape {
+    fn add(&mut self, fullname: &str, def: &WingNodeDef) {
+        let mut shape = self;
+        let mut idx = Vec::new();
+        for seg in fullname.trim_start_matches('/').split('/') {
+            let mut path = shape.path.clone();
+            if let Some(n) = seg.parse::<u16>().ok().filter(|_| seg.bytes().all(|b| b.is_ascii_digit())) {
+                shape.indexes.insert(n);
+                idx.push(n);
+                path.push(None);
+                shape = shape.indexed.get_or_insert_with(|| Box::new(Shape { path, ..Default::default() }));
+            } else {
+                path.push(Some(seg.to_string()));
+                shape = shape.named.entry(seg.to_string()).or_insert_with(|| Shape { path, ..Default::default() });
+            }
+        }
+        if def.node_type != NodeType::Node {
+            if shape.leaf.is_none() {
+                shape.leaf = Some(def.clone());
+            }
+            shape.ids.insert(idx, def.id);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.leaf.is_none() && !self.has_children()
+    }
+
+    fn has_children(&self) -> bool {
+        self.named.values().any(|s| !s.is_empty())
+            || self.indexed.as_ref().is_some_and(|s| !s.is_empty())
+    }
+
+    fn index_range(&self) -> (u16, u16) {
+        (*self.indexes.first().unwrap_or(&0), *self.indexes.last().unwrap_or(&0))
+    }
+
+    fn format_string(&self) -> String {
+        let segs: Vec<&str> = self.path.iter().map(|s| s.as_deref().unwrap_or("{}")).collect();
+        String::from("/") + &segs.join("/")
+    }
+
+    fn camel_name(&self) -> String {
+        self.path.iter().flatten().map(|s| camel(s)).collect()
+    }
+}
+
+/// Where the generated methods live: on `WingConsole` through the `Params` trait, or on one of
+/// the generated structs
+enum Receiver {
+    Console,
+    Struct,
+}
+
+impl Receiver {
+    fn recv(&self) -> &'static str {
+        match self { Receiver::Console => "&mut self", Receiver::Struct => "self" }
+    }
+    fn lifetime(&self) -> &'static str {
+        match self { Receiver::Console => "'_", Receiver::Struct => "'a" }
+    }
+    fn wing(&self) -> &'static str {
+        match self { Receiver::Console => "self", Receiver::Struct => "self.wing" }
+    }
+}
+
+struct Method {
+    doc: String,
+    signature: String,
+    body: String,
+}
+
+#[derive(Default)]
+struct Generator {
+    structs: String,
+    enums: String,
+    struct_names: HashSet<String>,
+    enum_names: HashSet<String>,
+    /// enum item lists that already have a type, and the name of that type
+    enum_types: HashMap<String, String>,
+}
+
+impl Generator {
+    /// The methods for the children of `shape`. `dims` has the index range of each numbered
+    /// segment in `shape`'s path.
+    fn methods(&mut self, shape: &Shape, dims: &[(u16, u16)], recv: &Receiver) -> Vec<Method> {
+        let mut methods = Vec::new();
+        let mut used = HashSet::new();
+        let idx: Vec<String> = (0..dims.len()).map(|i| format!("self.idx[{}]", i)).collect();
+        let lt = recv.lifetime();
+
+        let mut children: Vec<(String, &Shape)> = shape.named.iter()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(n, s)| (n.clone(), s))
+            .collect();
+        // numbered children next to named ones are reached through `at(n)`
+        if let Some(s) = shape.indexed.as_ref().filter(|s| !s.is_empty()) {
+            children.push((String::new(), s));
+        }
+
+        for (name, child) in children {
+            let method = unique(&mut used, if name.is_empty() { "at".to_string() } else { snake(&name) });
+
+            // a named child with nothing but numbered children takes the index directly
+            let folded = !name.is_empty() && child.leaf.is_none()
+                && child.named.values().all(|s| s.is_empty()) && child.indexed.is_some();
+            let (target, arg, child_dims) = if folded || name.is_empty() {
+                let (target, owner) = if name.is_empty() { (child, shape) } else { (child.indexed.as_deref().unwrap(), child) };
+                let mut d = dims.to_vec();
+                d.push(owner.index_range());
+                (target, true, d)
+            } else {
+                (child, false, dims.to_vec())
+            };
+            let mut call_idx = idx.clone();
+            if arg { call_idx.push("n".to_string()); }
+            let args = if arg { format!("{}, n: u16", recv.recv()) } else { recv.recv().to_string() };
+
+            if let Some(def) = &target.leaf {
+                let (ty, read_only) = self.leaf_type(target, def);
+                let param = if read_only { "ReadOnlyParam" } else { "Param" };
+                let table = id_table(target, &child_dims);
+                let dims_src = child_dims.iter().map(|(a, b)| format!("({}, {})", a, b)).collect::<Vec<_>>().join(", ");
+                let name_src = if call_idx.is_empty() {
+                    format!("{:?}.to_string()", target.format_string())
+                } else {
+                    format!("format!({:?}, {})", target.format_string(), call_idx.join(", "))
+                };
+                methods.push(Method {
+                    doc: doc(target, def),
+                    signature: format!("fn {}({}) -> {}<{}, {}>", method, args, param, lt, ty),
+                    body: format!(
+                        "        static IDS: [i32; {}] = [{}];\n        {}::new({}, lookup(&IDS, &[{}], &[{}]), {})\n",
+                        table.len(),
+                        table.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", "),
+                        param, recv.wing(), dims_src, call_idx.join(", "), name_src),
+                });
+                if !target.has_children() {
+                    continue;
+                }
+            }
+
+            // a property that also has children reaches them through a second method
+            let method = match target.leaf {
+                Some(_) => unique(&mut used, format!("{}_node", method)),
+                None => method,
+            };
+            let struct_name = self.struct_name(target, name.is_empty());
+            let (lo, hi) = child_dims.last().copied().unwrap_or((0, 0));
+            methods.push(Method {
+                doc: if arg {
+                    format!("    /// {} ({}..={})\n", target.format_string(), lo, hi)
+                } else {
+                    format!("    /// {}\n", target.format_string())
+                },
+                signature: format!("fn {}({}) -> {}<{}>", method, args, struct_name, lt),
+                body: format!("        {} {{ wing: {}, idx: [{}] }}\n", struct_name, recv.wing(), call_idx.join(", ")),
+            });
+            self.gen_struct(target, &struct_name, &child_dims);
+        }
+        methods
+    }
+
+    fn gen_struct(&mut self, shape: &Shape, name: &str, dims: &[(u16, u16)]) {
+        let methods = self.methods(shape, dims, &Receiver::Struct);
+        let mut s = String::new();
+        writeln!(s).unwrap();
+        writeln!(s, "/// {}", shape.format_string()).unwrap();
+        writeln!(s, "pub struct {}<'a> {{", name).unwrap();
+        writeln!(s, "    wing: &'a mut WingConsole,").unwrap();
+        writeln!(s, "    idx: [u16; {}],", dims.len()).unwrap();
+        writeln!(s, "}}").unwrap();
+        writeln!(s).unwrap();
+        writeln!(s, "impl<'a> {}<'a> {{", name).unwrap();
+        for (i, m) in methods.iter().enumerate() {
+            if i > 0 { writeln!(s).unwrap(); }
+            writeln!(s, "{}    pub {} {{\n{}    }}", m.doc, m.signature, m.body).unwrap();
+        }
+        writeln!(s, "}}").unwrap();
+        self.structs.push_str(&s);
+    }
+
+    fn struct_name(&mut self, shape: &Shape, item: bool) -> String {
+        let mut base = shape.camel_name();
+        if item || base.is_empty() { base.push_str("Item"); }
+        unique(&mut self.struct_names, base)
+    }
+
+    /// The Rust type of a property, and whether it is read-only
+    fn leaf_type(&mut self, shape: &Shape, def: &WingNodeDef) -> (String, bool) {
+        let ty = match def.node_type {
+            NodeType::LinearFloat | NodeType::LogarithmicFloat | NodeType::FaderLevel => "f32".to_string(),
+            NodeType::Integer => "i32".to_string(),
+            NodeType::String | NodeType::Node => "String".to_string(),
+            NodeType::StringEnum => match def.string_enum.as_deref() {
+                Some(items) if !items.is_empty() => self.string_enum(shape, def, items),
+                _ => "String".to_string(),
+            },
+            NodeType::FloatEnum => match def.float_enum.as_deref() {
+                Some(items) if !items.is_empty() => self.float_enum(shape, def, items),
+                _ => "f32".to_string(),
+            },
+        };
+        (ty, def.read_only)
+    }
+
+    fn string_enum(&mut self, shape: &Shape, def: &WingNodeDef, items: &[crate::node::StringEnumItem]) -> String {
+        let key = format!("s:{}", items.iter().map(|i| i.item.as_str()).collect::<Vec<_>>().join("\u{1}"));
+        if let Some(name) = self.enum_types.get(&key) {
+            return name.clone();
+        }
+        let name = unique(&mut self.enum_names, shape.camel_name() + "Value");
+        self.enum_types.insert(key, name.clone());
+
+        let mut used = HashSet::new();
+        let variants: Vec<String> = items.iter().enumerate()
+            .map(|(i, item)| unique(&mut used, variant(&item.item, i)))
+            .collect();
+
+        let mut s = String::new();
+        writeln!(s).unwrap();
+        writeln!(s, "/// Values of {} ({})", shape.format_string(), def.node_type.as_str()).unwrap();
+        writeln!(s, "#[derive(Copy, Clone, Debug, PartialEq, Eq)]").unwrap();
+        writeln!(s, "pub enum {} {{", name).unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            if !item.long_item.is_empty() {
+                writeln!(s, "    /// {}", item.long_item).unwrap();
+            }
+            writeln!(s, "    {},", v).unwrap();
+        }
+        writeln!(s, "}}").unwrap();
+        writeln!(s).unwrap();
+        writeln!(s, "impl ParamValue for {} {{", name).unwrap();
+        writeln!(s, "    fn from_data(data: &WingNodeData) -> Option<Self> {{").unwrap();
+        writeln!(s, "        match data.get_string().as_str() {{").unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            writeln!(s, "            {:?} => Some(Self::{}),", item.item, v).unwrap();
+        }
+        writeln!(s, "            _ => None,").unwrap();
+        writeln!(s, "        }}").unwrap();
+        writeln!(s, "    }}").unwrap();
+        writeln!(s, "    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<(), libwing::Error> {{").unwrap();
+        writeln!(s, "        wing.set_string(id, match self {{").unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            writeln!(s, "            Self::{} => {:?},", v, item.item).unwrap();
+        }
+        writeln!(s, "        }})").unwrap();
+        writeln!(s, "    }}").unwrap();
+        writeln!(s, "}}").unwrap();
+        self.enums.push_str(&s);
+        name
+    }
+
+    fn float_enum(&mut self, shape: &Shape, def: &WingNodeDef, items: &[crate::node::FloatEnumItem]) -> String {
+        let key = format!("f:{}", items.iter().map(|i| i.item.to_bits().to_string()).collect::<Vec<_>>().join(","));
+        if let Some(name) = self.enum_types.get(&key) {
+            return name.clone();
+        }
+        let name = unique(&mut self.enum_names, shape.camel_name() + "Value");
+        self.enum_types.insert(key, name.clone());
+
+        let mut used = HashSet::new();
+        let variants: Vec<String> = items.iter().enumerate()
+            .map(|(i, item)| {
+                let label = if item.long_item.is_empty() { item.item.to_string() } else { item.long_item.clone() };
+                unique(&mut used, variant(&label, i))
+            })
+            .collect();
+
+        let mut s = String::new();
+        writeln!(s).unwrap();
+        writeln!(s, "/// Values of {} ({})", shape.format_string(), def.node_type.as_str()).unwrap();
+        writeln!(s, "#[derive(Copy, Clone, Debug, PartialEq, Eq)]").unwrap();
+        writeln!(s, "pub enum {} {{", name).unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            writeln!(s, "    /// {}", item.item).unwrap();
+            writeln!(s, "    {},", v).unwrap();
+        }
+        writeln!(s, "}}").unwrap();
+        writeln!(s).unwrap();
+        writeln!(s, "impl ParamValue for {} {{", name).unwrap();
+        writeln!(s, "    fn from_data(data: &WingNodeData) -> Option<Self> {{").unwrap();
+        writeln!(s, "        let v = data.to_float()?;").unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            writeln!(s, "        if v == f32::from_bits({:#010x}) {{ return Some(Self::{}); }}", item.item.to_bits(), v).unwrap();
+        }
+        writeln!(s, "        None").unwrap();
+        writeln!(s, "    }}").unwrap();
+        writeln!(s, "    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<(), libwing::Error> {{").unwrap();
+        writeln!(s, "        wing.set_float(id, match self {{").unwrap();
+        for (item, v) in items.iter().zip(&variants) {
+            writeln!(s, "            Self::{} => f32::from_bits({:#010x}),", v, item.item.to_bits()).unwrap();
+        }
+        writeln!(s, "        }})").unwrap();
+        writeln!(s, "    }}").unwrap();
+        writeln!(s, "}}").unwrap();
+        self.enums.push_str(&s);
+        name
+    }
+}
+
+/// The ids of a property for every combination of indexes in `dims`, last index fastest, 0 where
+/// the combination does not exist
+fn id_table(shape: &Shape, dims: &[(u16, u16)]) -> Vec<i32> {
+    let mut table = vec![0];
+    let mut keys: Vec<Vec<u16>> = vec![Vec::new()];
+    for &(lo, hi) in dims {
+        keys = keys.iter().flat_map(|k| (lo..=hi).map(move |i| { let mut k = k.clone(); k.push(i); k })).collect();
+    }
+    if !dims.is_empty() {
+        table = keys.iter().map(|k| *shape.ids.get(k).unwrap_or(&0)).collect();
+    } else if let Some(id) = shape.ids.get(&Vec::new()) {
+        table[0] = *id;
+    }
+    table
+}
+
+fn doc(shape: &Shape, def: &WingNodeDef) -> String {
+    let mut d = format!("    /// {}", shape.format_string());
+    if !def.long_name.is_empty() {
+        d.push_str(&format!(": {}", def.long_name));
+    }
+    d.push_str(&format!(" ({}", def.node_type.as_str()));
+    if !def.unit.as_str().is_empty() {
+        d.push_str(&format!(", {}", def.unit.as_str()));
+    }
+    if let (Some(min), Some(max)) = (def.min_float, def.max_float) {
+        d.push_str(&format!(", {}..{}", min, max));
+    }
+    if let (Some(min), Some(max)) = (def.min_int, def.max_int) {
+        d.push_str(&format!(", {}..{}", min, max));
+    }
+    if def.read_only {
+        d.push_str(", read-only");
+    }
+    d.push_str(")\n");
+    d
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "gen",
+];
+
+/// A snake_case method name for a path segment
+fn snake(s: &str) -> String {
+    let mut r: String = s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if r.is_empty() || r.starts_with(|c: char| c.is_ascii_digit()) {
+        r.insert(0, 'n');
+    }
+    if KEYWORDS.contains(&r.as_str()) {
+        r.push('_');
+    }
+    r
+}
+
+/// A CamelCase type name for a path segment
+pub(crate) fn camel(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let mut c = p.chars();
+            let first = c.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(c.map(|c| c.to_ascii_lowercase())).collect::<String>()
+        })
+        .collect()
+}
+
+/// A CamelCase enum variant name for an item, falling back to its position
+fn variant(item: &str, i: usize) -> String {
+    let c = camel(item);
+    if c.is_empty() {
+        format!("V{}", i)
+    } else if c.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("V{}", c)
+    } else {
+        c
+    }
+}
+
+/// Makes `name` unique within `used` by appending a number
+pub(crate) fn unique(used: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    candidate
+}
@@ -0,0 +1,179 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+
+use crate::{Result, Error};
+use crate::console::WingConsole;
+use crate::discovery::{Discovery, DiscoveryInfo};
+
+/// TCP port of the Native protocol
+const NATIVE_PORT: u16 = 2222;
+
+#[derive(Debug, Clone)]
+enum Target {
+    /// The first console that answers discovery
+    First,
+    Host(String),
+    /// The console with this name or serial number
+    Console(String),
+}
+
+/// Options for connecting to a console, for when `WingConsole::connect()` is not enough.
+///
+/// ```ignore
+/// let wing = ConnectOptions::new()
+///     .console("FOH")
+///     .discovery(Discovery::new().timeout(Duration::from_secs(5)))
+///     .interface("eth1")
+///     .connect_timeout(Duration::from_secs(2))
+///     .keepalive(Duration::from_secs(10), Duration::from_secs(2))
+///     .connect()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    target: Target,
+    port: u16,
+    discovery: Discovery,
+    connect_timeout: Option<Duration>,
+    bind: Option<IpAddr>,
+    interface: Option<String>,
+    keepalive: Option<(Duration, Duration)>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions {
+            target: Target::First,
+            port: NATIVE_PORT,
+            discovery: Discovery::new(),
+            connect_timeout: None,
+            bind: None,
+            interface: None,
+            keepalive: None,
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// Connects to the first console that answers discovery, unless `host()` or `console()` is
+    /// used
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Connects to this IP address or hostname, without discovery
+    pub fn host(mut self, host_or_ip: &str) -> Self {
+        self.target = Target::Host(host_or_ip.to_string());
+        self
+    }
+
+    /// Discovers the console with this name or serial number and connects to it. Other consoles
+    /// that answer are ignored, so the wrong console is never used.
+    pub fn console(mut self, name_or_serial: &str) -> Self {
+        self.target = Target::Console(name_or_serial.to_string());
+        self
+    }
+
+    /// The TCP port to connect to. Default is 2222, the port of the Native protocol.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// How to discover the console, for example how long to search for it. Default is
+    /// `Discovery::new()`.
+    pub fn discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+
+    /// How long to wait for the TCP connection. Default is the system's timeout.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects from this local address, to choose the network the console is reached on
+    pub fn bind(mut self, ip: IpAddr) -> Self {
+        self.bind = Some(ip);
+        self
+    }
+
+    /// Connects from the IPv4 address of this network interface, for example `eth1`
+    pub fn interface(mut self, name: &str) -> Self {
+        self.interface = Some(name.to_string());
+        self
+    }
+
+    /// Turns on TCP keepalive, so a console that was switched off or unplugged is noticed. Probes
+    /// start after the connection has been idle for `time` and are repeated every `interval`.
+    pub fn keepalive(mut self, time: Duration, interval: Duration) -> Self {
+        self.keepalive = Some((time, interval));
+        self
+    }
+
+    /// Finds the console if needed, and connects to it
+    pub fn connect(&self) -> Result<WingConsole> {
+        let host = match &self.target {
+            Target::Host(host) => host.clone(),
+            Target::First => self.discovery.clone().stop_on_first(true).scan()?
+                .into_iter().next().ok_or(Error::DiscoveryError)?.ip,
+            Target::Console(name) => self.discovery.find(|info| matches(info, name))?
+                .ok_or(Error::DiscoveryError)?.ip,
+        };
+
+        let bind = match (&self.interface, self.bind) {
+            (Some(name), _) => Some(interface_ip(name)?),
+            (None, bind) => bind,
+        };
+
+        let mut last_error = None;
+        for addr in (host.as_str(), self.port).to_socket_addrs()? {
+            if bind.is_some_and(|b| b.is_ipv4() != addr.is_ipv4()) {
+                continue;
+            }
+            match self.open(addr, bind) {
+                Ok(stream) => return WingConsole::from_stream(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or(Error::ConnectionError))
+    }
+
+    fn open(&self, addr: SocketAddr, bind: Option<IpAddr>) -> Result<TcpStream> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        if let Some(ip) = bind {
+            socket.bind(&SocketAddr::new(ip, 0).into())?;
+        }
+        if let Some((time, interval)) = self.keepalive {
+            let keepalive = TcpKeepalive::new().with_time(time);
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+                      target_os = "freebsd", target_os = "netbsd", windows))]
+            let keepalive = keepalive.with_interval(interval);
+            #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+                          target_os = "freebsd", target_os = "netbsd", windows)))]
+            let _ = interval;
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+        match self.connect_timeout {
+            Some(timeout) => socket.connect_timeout(&addr.into(), timeout)?,
+            None => socket.connect(&addr.into())?,
+        }
+        socket.set_nodelay(true)?;
+        Ok(socket.into())
+    }
+}
+
+/// Whether a discovered console is the one asked for by name or serial number
+fn matches(info: &DiscoveryInfo, name_or_serial: &str) -> bool {
+    info.name == name_or_serial || info.serial == name_or_serial
+}
+
+fn interface_ip(name: &str) -> Result<IpAddr> {
+    if_addrs::get_if_addrs()?.into_iter()
+        .find(|i| i.name == name && i.ip().is_ipv4())
+        .map(|i| i.ip())
+        .ok_or_else(|| Error::Io(std::io::Error::new(ErrorKind::NotFound, format!("no IPv4 address on interface {}", name))))
+}
@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::{TcpStream, UdpSocket};
 use std::io::{Read, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::{Mutex, Arc};
+use std::sync::mpsc;
 
 use crate::{Result, Error, WingResponse};
-use crate::node::{WingNodeDef, WingNodeData};
+use crate::node::{WingNodeDef, WingNodeData, NodeType};
 use crate::propmap::NAME_TO_DEF;
+use crate::schema::PathPattern;
+use crate::meter::{MeterFrame, MeterSource};
+use crate::discovery::{Discovery, DiscoveryInfo};
+use crate::connect::ConnectOptions;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Meter {
     Channel(u8),
     Aux(u8),
@@ -40,22 +45,21 @@ lazy_static::lazy_static! {
         }
         id2name
     };
+
+    /// ids of the `mdl` properties, which select the FX/EQ/dynamics model that decides which
+    /// properties exist below their parent
+    static ref MODEL_IDS: HashSet<i32> = NAME_TO_DEF.iter()
+        .filter(|(name, def)| name.ends_with("/mdl") && def.node_type == NodeType::StringEnum)
+        .map(|(_, def)| def.id)
+        .collect();
 }
 
 const RX_BUFFER_SIZE: usize = 2048;
 const DATA_KEEP_ALIVE_SECONDS: u64 = 7;
 const METERS_KEEP_ALIVE_SECONDS: u64 = 3;
 
-pub struct DiscoveryInfo {
-    pub ip:       String,
-    pub name:     String,
-    pub model:    String,
-    pub serial:   String,
-    pub firmware: String,
-}
-
 pub struct Meters {
-    pub socket: UdpSocket,
+    pub socket: Arc<UdpSocket>,
     pub port: u16,
 }
 
@@ -70,10 +74,16 @@ struct _WingConsoleMain {
     current_node_id:         i32,
 }
 
+struct MeterSubscription {
+    meters: Vec<Meter>,
+    sender: Option<mpsc::Sender<MeterFrame>>,
+}
+
 struct _WingConsoleMeters {
     meters:                  Option<Meters>,
     next_meter_id:           u16,
     keep_alive_meters_timer: std::time::Instant,
+    subscriptions:           HashMap<u16, MeterSubscription>,
 }
 
 #[derive(Clone)]
@@ -82,64 +92,36 @@ pub struct WingConsole {
     wsock: Arc<Mutex<TcpStream>>,
     main: Arc<Mutex<_WingConsoleMain>>,
     mtrs: Arc<Mutex<_WingConsoleMeters>>,
-
+    models: Arc<Mutex<HashMap<i32, String>>>,
 }
 
 impl WingConsole {
+    /// Broadcasts a discovery probe on every network interface and returns the Wing consoles
+    /// that answer. See `Discovery` for more options.
     pub fn scan(stop_on_first: bool) -> Result<Vec<DiscoveryInfo>> {
-        let dsock = UdpSocket::bind("0.0.0.0:0")?;
-        dsock.set_broadcast(true)?;
-        dsock.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
-
-        let mut results = Vec::new();
-        let mut attempts = 0;
-
-        dsock.send_to(b"WING?", "255.255.255.255:2222")?;
-        while attempts < 10 {
-            let mut buf = [0u8; 1024];
-            match dsock.recv_from(&mut buf) {
-                Ok((received, _)) => {
-                    if let Ok(response) = String::from_utf8(buf[..received].to_vec()) {
-                        let tokens: Vec<&str> = response.split(',').collect();
-                        if tokens.len() >= 6 && tokens[0] == "WING" {
-                            results.push(DiscoveryInfo {
-                                ip:       tokens[1].to_string(),
-                                name:     tokens[2].to_string(),
-                                model:    tokens[3].to_string(),
-                                serial:   tokens[4].to_string(),
-                                firmware: tokens[5].to_string(),
-                            });
-                            if stop_on_first {
-                                break;
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    attempts += 1;
-                }
-            }
-        }
-
-        Ok(results)
+        Discovery::new().stop_on_first(stop_on_first).scan()
     }
 
+    /// Connects to the console at `host_or_ip`, or to the first console that answers discovery.
+    /// See `ConnectOptions` for more options.
     pub fn connect(host_or_ip: Option<&str>) -> Result<Self> {
-        let ip =
-            if let Some(i) = host_or_ip {
-                i.to_string()
-            } else {
-                let devices = WingConsole::scan(true)?;
-                if !devices.is_empty() {
-                    devices[0].ip.clone()
-                } else {
-                    return Err(Error::DiscoveryError);
-                }
-            };
+        match host_or_ip {
+            Some(host) => ConnectOptions::new().host(host),
+            None => ConnectOptions::new(),
+        }.connect()
+    }
 
-        let mut stream = TcpStream::connect((ip, 2222))?;
-        // stream.set_nonblocking(true)?;
-        stream.set_nodelay(true)?;
+    /// Discovers the console whose name or serial number is `name_or_serial` and connects to it.
+    /// Returns `Error::DiscoveryError` if it doesn't answer within `timeout`.
+    pub fn connect_to(name_or_serial: &str, timeout: Duration) -> Result<Self> {
+        ConnectOptions::new()
+            .console(name_or_serial)
+            .discovery(Discovery::new().timeout(timeout))
+            .connect()
+    }
+
+    /// Starts the Native protocol on a connected stream
+    pub(crate) fn from_stream(mut stream: TcpStream) -> Result<Self> {
         stream.write_all(&[0xdf, 0xd1])?;
 
         Ok(Self {
@@ -159,11 +141,63 @@ impl WingConsole {
                 keep_alive_meters_timer: std::time::Instant::now() + std::time::Duration::from_secs(METERS_KEEP_ALIVE_SECONDS),
                 meters: None,
                 next_meter_id: 0,
+                subscriptions: HashMap::new(),
             })),
+            models: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
     pub fn read(&mut self) -> Result<WingResponse> {
+        let response = self.read_response()?;
+        if let WingResponse::NodeData(id, data) = &response {
+            if MODEL_IDS.contains(id) {
+                self.models.lock().unwrap().insert(*id, data.get_string());
+            }
+        }
+        Ok(response)
+    }
+
+    /// Like `read()`, but returns `None` if nothing has started to arrive by the deadline. A
+    /// response that has started to arrive is read to its end.
+    fn read_until(&mut self, deadline: Instant) -> Result<Option<WingResponse>> {
+        if !self.wait_for_data(deadline)? {
+            return Ok(None);
+        }
+        self.read().map(Some)
+    }
+
+    /// Waits until there is received data to decode, or the deadline passes
+    fn wait_for_data(&mut self, deadline: Instant) -> Result<bool> {
+        let mainptr = self.main.clone();
+        let mut r = mainptr.lock().unwrap();
+        loop {
+            if r.rx_buf_size > 0 || r.rx_has_in_pipe.is_some() {
+                return Ok(true);
+            }
+            self._keep_alive(&mut r)?;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            let wait = r.keep_alive_timer.saturating_duration_since(now).min(deadline - now);
+            let rsock = self.rsock.clone();
+            let mut sock = rsock.lock().unwrap();
+            // a zero timeout would block forever
+            sock.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+            match sock.read(&mut r.rx_buf) {
+                Ok(n) if n > 0 => {
+                    r.rx_buf_size = n;
+                    r.rx_buf_tail = 0;
+                }
+                Err(ref e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Ok(_) => return Err(Error::ConnectionError),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn read_response(&mut self) -> Result<WingResponse> {
         loop {
             let mainptr = self.main.clone();
             let mut main = mainptr.lock().unwrap();
@@ -314,12 +348,10 @@ impl WingConsole {
                 (meters.port & 0xff) as u8,
                 0xdf, 0xd1
             ];
-            let mut i = m.next_meter_id as i32;
-            while i > 0 {
-                keepalive[3] = ((i >> 8) & 0xff) as u8;
-                keepalive[4] = (i & 0xff) as u8;
+            for id in m.subscriptions.keys() {
+                keepalive[3] = ((id >> 8) & 0xff) as u8;
+                keepalive[4] = (id & 0xff) as u8;
                 self.wsock.clone().lock().unwrap().write_all(&keepalive)?;
-                i -= 1;
             }
             m.keep_alive_meters_timer = std::time::Instant::now() + std::time::Duration::from_secs(METERS_KEEP_ALIVE_SECONDS);
         }
@@ -350,6 +382,8 @@ impl WingConsole {
                         std::thread::sleep(Duration::from_millis(10));
                         continue;
                     }
+                    // a signal arrived, for example Ctrl-C handled by the application
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
                     Ok(_) => return Err(Error::ConnectionError),
                     Err(e) => return Err(e.into()),
                 }
@@ -436,18 +470,48 @@ impl WingConsole {
 
 
     /// Subscribes to meters from the Wing mixer and returns a meter ID that can be used to
-    /// associate the values that come back when you call read_meter()
-    pub fn request_meter(&mut self, meters: &[Meter]) -> Result<u16>
+    /// associate the values that come back when you call read_meter(). Each call adds a
+    /// subscription with its own ID, until it is cancelled with cancel_meter().
+    pub fn request_meter(&mut self, meters: &[Meter]) -> Result<u16> {
+        self.add_meter_subscription(meters, None)
+    }
+
+    /// Subscribes to meters like request_meter(), but the frames of this subscription are sent to
+    /// the returned receiver instead of being returned by read_meters(). Something still has to
+    /// read the meter packets for them to be received, for example a thread that reads the other
+    /// subscriptions with read_meters(), or calls read_meters_timeout() if all subscriptions have
+    /// a receiver. The subscription is cancelled when the receiver is dropped.
+    pub fn subscribe_meter(&mut self, meters: &[Meter]) -> Result<(u16, mpsc::Receiver<MeterFrame>)> {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.add_meter_subscription(meters, Some(sender))?;
+        Ok((id, receiver))
+    }
+
+    /// Stops renewing a meter subscription. The Wing stops sending its meters a few seconds
+    /// later, and any packets that still arrive for it are ignored by read_meters().
+    pub fn cancel_meter(&mut self, id: u16) -> Result<()> {
+        match self.mtrs.lock().unwrap().subscriptions.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(Error::MeterError(format!("no meter subscription with id {}", id))),
+        }
+    }
+
+    fn add_meter_subscription(&mut self, meters: &[Meter], sender: Option<mpsc::Sender<MeterFrame>>) -> Result<u16>
     {
         let mtrsptr = self.mtrs.clone();
         let mut mtrs = mtrsptr.lock().unwrap();
-        mtrs.next_meter_id = 1;
+        loop {
+            mtrs.next_meter_id = mtrs.next_meter_id.wrapping_add(1).max(1);
+            if !mtrs.subscriptions.contains_key(&mtrs.next_meter_id) {
+                break;
+            }
+        }
 
         if mtrs.meters.is_none() {
             let socket = UdpSocket::bind("0.0.0.0:0")?;
             let port = socket.local_addr()?.port();
             socket.set_read_timeout(Some(Duration::from_millis(1000))).unwrap();
-            mtrs.meters = Some(Meters { socket, port });
+            mtrs.meters = Some(Meters { socket: Arc::new(socket), port });
         } else {
             self._keep_alive_meters(&mut mtrs)?;
         }
@@ -539,39 +603,119 @@ impl WingConsole {
 
         self.wsock.clone().lock().unwrap().write_all(&buf)?;
 
-        Ok(mtrs.next_meter_id)
+        let id = mtrs.next_meter_id;
+        mtrs.subscriptions.insert(id, MeterSubscription { meters: meters.to_vec(), sender });
+        Ok(id)
+    }
+
+    /// reads any meter values that have been requested with request_meter() and returns them
+    /// decoded according to the meters that were requested, in dB. Frames of subscriptions made
+    /// with subscribe_meter() are sent to their receivers instead, and packets of cancelled
+    /// subscriptions are skipped. Returns `Error::MeterPacketError` for a packet that is
+    /// truncated or doesn't match the meters requested, and `Error::MeterError` when there is no
+    /// subscription made with request_meter(), as there would be nothing to return.
+    pub fn read_meters(&mut self) -> Result<MeterFrame> {
+        loop {
+            if !self.mtrs.lock().unwrap().subscriptions.values().any(|sub| sub.sender.is_none()) {
+                return Err(Error::MeterError("no meters requested with request_meter()".to_string()));
+            }
+            if let Some(frame) = self.try_read_meters(None)? {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for a meter packet, and decodes it like `read_meters()`. Returns
+    /// `None` if nothing arrived, or the packet was sent to the receiver of a subscription made
+    /// with subscribe_meter(), or was for a cancelled subscription.
+    pub fn read_meters_timeout(&mut self, timeout: Duration) -> Result<Option<MeterFrame>> {
+        self.try_read_meters(Some(timeout))
+    }
+
+    /// Waits for one meter packet like `try_read_meters_raw()`, and decodes it like
+    /// `read_meters()`. Returns `None` if nothing arrived, or the packet was not for a
+    /// subscription made with request_meter().
+    pub(crate) fn try_read_meters(&mut self, timeout: Option<Duration>) -> Result<Option<MeterFrame>> {
+        let Some((id, values)) = self.try_read_meters_raw(timeout)? else {
+            return Ok(None);
+        };
+        let mut mtrs = self.mtrs.lock().unwrap();
+        let Some(sub) = mtrs.subscriptions.get(&id) else {
+            return Ok(None);
+        };
+        let frame = MeterFrame::decode(id, &sub.meters, &values)?;
+        match &sub.sender {
+            None => Ok(Some(frame)),
+            Some(sender) => {
+                if sender.send(frame).is_err() {
+                    mtrs.subscriptions.remove(&id);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// The meters a subscription was requested with, if it hasn't been cancelled
+    pub(crate) fn meter_subscription(&self, id: u16) -> Option<Vec<Meter>> {
+        self.mtrs.lock().unwrap().subscriptions.get(&id).map(|sub| sub.meters.clone())
     }
 
     /// reads any meter values that have been requested with request_meter() and returns the meter
-    /// ID along with the meters values
-    pub fn read_meters(&mut self) -> Result<(u16, Vec<i16>)> {
+    /// ID along with the raw meters values, in 1/256 dB
+    pub fn read_meters_raw(&mut self) -> Result<(u16, Vec<i16>)> {
         loop {
+            if let Some(packet) = self.try_read_meters_raw(None)? {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Whether any meters have been requested, so there is something for `read_meters_raw()` to
+    /// read
+    pub(crate) fn has_meters(&self) -> bool {
+        self.mtrs.lock().unwrap().meters.is_some()
+    }
+
+    /// Waits for one meter packet, up to `timeout` or until the next meter keepalive is due
+    pub(crate) fn try_read_meters_raw(&mut self, timeout: Option<Duration>) -> Result<Option<(u16, Vec<i16>)>> {
+        let (socket, wait) = {
             let mptr = self.mtrs.clone();
             let mut m = mptr.lock().unwrap();
 
             self._keep_alive_meters(&mut m)?;
-            let md = m.meters.as_ref().unwrap();
-            let mut buf = [0u8; 8192];
-            md.socket.set_read_timeout(Some(m.keep_alive_meters_timer.duration_since(std::time::Instant::now())))?;
-            match md.socket.recv_from(&mut buf) {
-                Ok((received, _addr)) => {
-                    return Ok((u16::from_be_bytes([buf[0], buf[1]]), buf[4..received]
-                            .chunks_exact(2) // Take 2 bytes at a time
-                            .map(|chunk| i16::from_be_bytes([chunk[0], chunk[1]]))
-                            .collect()));
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    std::thread::sleep(Duration::from_millis(10));
-                    continue;
-                }
-                Err(e) => {
-                    return Err(Error::Io(e.into()));
+            let md = m.meters.as_ref().ok_or_else(|| Error::MeterError("no meters requested".to_string()))?;
+            let mut wait = m.keep_alive_meters_timer.duration_since(std::time::Instant::now());
+            if let Some(timeout) = timeout {
+                wait = wait.min(timeout);
+            }
+            (md.socket.clone(), wait)
+        };
+        // without holding the lock, so other threads can subscribe and cancel meanwhile
+        let mut buf = [0u8; 8192];
+        // a zero timeout would block forever
+        socket.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+        match socket.recv_from(&mut buf) {
+            Ok((received, _addr)) => {
+                if received < 4 || received % 2 != 0 {
+                    return Err(Error::MeterPacketError(format!("truncated packet of {} bytes", received)));
                 }
+                Ok(Some((u16::from_be_bytes([buf[0], buf[1]]), buf[4..received]
+                        .chunks_exact(2) // Take 2 bytes at a time
+                        .map(|chunk| i16::from_be_bytes([chunk[0], chunk[1]]))
+                        .collect())))
             }
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
+                Ok(None)
+            }
+            Err(e) => Err(Error::Io(e)),
         }
     }
 
     pub fn set_string(&mut self, id: i32, value: &str) -> Result<()> {
+        if MODEL_IDS.contains(&id) {
+            self.models.lock().unwrap().insert(id, value.to_string());
+        }
+
         let mut buf = Vec::new();
         self.format_id(id, &mut buf, 0xd7, None);
 
@@ -655,11 +799,248 @@ impl WingConsole {
                 .map(|(n, v)| (n.clone(), v.clone())
                 ).collect())
     }
+
+    /// Requests the current value of every `mdl` property and waits for them, so that
+    /// `resolve_id()` knows which models are loaded. After this, `read()` keeps track of model
+    /// changes by itself. Any other data the Wing sends in the meantime is discarded.
+    pub fn sync_models(&mut self) -> Result<()> {
+        for id in MODEL_IDS.iter() {
+            self.request_node_data(*id)?;
+        }
+        let mut pending = MODEL_IDS.len();
+        while pending > 0 {
+            if let WingResponse::RequestEnd = self.read()? {
+                pending -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves an id to the one property name it currently stands for. The same id can appear
+    /// under several names, one for each FX/EQ/dynamics model (for example `/ch/1/gate/GATE/thr`
+    /// and `/ch/1/gate/EXP2/thr`). Each name's parent chain is checked against the model that is
+    /// currently selected in the `mdl` property above it. Returns `None` if the id is unknown or
+    /// the current models are not known well enough to pick a single name; see `sync_models()`.
+    pub fn resolve_id(&self, id: i32) -> Option<(String, WingNodeDef)> {
+        let mut defs = Self::id_to_defs(id)?;
+        if defs.len() > 1 {
+            let models = self.models.lock().unwrap();
+            defs.retain(|(name, _)| Self::matches_models(name, &models));
+        }
+        if defs.len() == 1 { defs.pop() } else { None }
+    }
+
+    /// Whether every model segment in the name is the currently selected model of its `mdl`
+    /// property. Names with a model segment whose `mdl` value is unknown do not match.
+    fn matches_models(fullname: &str, models: &HashMap<i32, String>) -> bool {
+        let segments: Vec<&str> = fullname.split('/').collect();
+        Self::model_segments(&segments).into_iter()
+            .all(|(k, mdl)| models.get(&mdl).map(|m| m.as_str()) == Some(segments[k]))
+    }
+
+    /// Whether the name belongs to the models currently selected, like `resolve_id()` checks
+    pub(crate) fn has_current_models(&self, fullname: &str) -> bool {
+        Self::matches_models(fullname, &self.models.lock().unwrap())
+    }
+
+    /// The segments of a full name that are a model of the `mdl` property next to them, by index,
+    /// with the id of that `mdl` property
+    fn model_segments(segments: &[&str]) -> Vec<(usize, i32)> {
+        (1..segments.len()).filter_map(|k| {
+            let mdl = NAME_TO_DEF.get(&(segments[..k].join("/") + "/mdl"))?;
+            let is_model = mdl.string_enum.as_ref()
+                .is_some_and(|items| items.iter().any(|i| i.item == segments[k]));
+            is_model.then_some((k, mdl.id))
+        }).collect()
+    }
+
+    /// A full name without its model segments, the way the console addresses the property over
+    /// OSC: `/fx/1/HALL/3` is `/fx/1/3`, whichever model is loaded
+    pub(crate) fn strip_models(fullname: &str) -> String {
+        let segments: Vec<&str> = fullname.split('/').collect();
+        let models: Vec<usize> = Self::model_segments(&segments).into_iter().map(|(k, _)| k).collect();
+        segments.iter().enumerate()
+            .filter(|(k, _)| !models.contains(k))
+            .map(|(_, s)| *s)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Returns the name and definition of every property in the built-in property map whose full
+    /// name matches the pattern, sorted by name. See `schema::PathPattern` for the syntax.
+    pub fn find_names(pattern: &str) -> Result<Vec<(String, WingNodeDef)>> {
+        let pattern = PathPattern::parse(pattern)?;
+        let mut found: Vec<(String, WingNodeDef)> = NAME_TO_DEF.iter()
+            .filter(|(n, _)| pattern.matches(n))
+            .map(|(n, d)| (n.clone(), d.clone()))
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(found)
+    }
+
+    /// Requests the value of a single property and waits for it to arrive. Any other data the Wing
+    /// sends in the meantime is discarded. Returns `Error::InvalidData` if the Wing ends the
+    /// request without sending a value, for example because the id is not a property.
+    pub fn get_data(&mut self, id: i32) -> Result<WingNodeData> {
+        self.request_node_data(id)?;
+        let mut value = None;
+        loop {
+            match self.read()? {
+                WingResponse::NodeData(i, data) if i == id => value = Some(data),
+                WingResponse::RequestEnd => return value.ok_or(Error::InvalidData),
+                _ => {}
+            }
+        }
+    }
+
+    /// Requests the values of all properties matching the pattern and waits up to `timeout` for
+    /// them to arrive. Nodes are skipped. Any other data the Wing sends in the meantime is
+    /// discarded. Returns `Error::Timeout` with the ids still unanswered when the time is up;
+    /// their answers may still arrive later and are then returned by `read()`.
+    pub fn get_many(&mut self, pattern: &str, timeout: Duration) -> Result<Vec<(String, WingNodeData)>> {
+        let deadline = Instant::now() + timeout;
+        let props: Vec<_> = Self::find_names(pattern)?
+            .into_iter()
+            .filter(|(_, def)| def.node_type != NodeType::Node)
+            .collect();
+
+        let mut ids: Vec<i32> = props.iter().map(|(_, def)| def.id).collect();
+        ids.sort();
+        ids.dedup();
+        for id in &ids {
+            self.request_node_data(*id)?;
+        }
+
+        // the Wing answers in the order of the requests, so the first `answered` ids are done
+        let mut values = HashMap::new();
+        let mut answered = 0;
+        while answered < ids.len() {
+            let Some(response) = self.read_until(deadline)? else {
+                return Err(Error::Timeout(ids[answered..].to_vec()));
+            };
+            match response {
+                WingResponse::NodeData(id, data) => {
+                    if ids.binary_search(&id).is_ok() {
+                        values.insert(id, data);
+                    }
+                }
+                WingResponse::RequestEnd => answered += 1,
+                WingResponse::NodeDef(_) => {}
+            }
+        }
+
+        Ok(props
+            .into_iter()
+            .filter_map(|(name, def)| values.get(&def.id).map(|v| (name, v.clone())))
+            .collect())
+    }
+
+    /// Sets all properties matching the pattern to the value, converted to each property's type.
+    /// Nodes and read-only properties are skipped. Returns the names of the properties set. The
+    /// value is checked against every property first, and if any of them can't take it, nothing
+    /// is sent and `Error::ValueError` names them.
+    pub fn set_many(&mut self, pattern: &str, value: &WingNodeData) -> Result<Vec<String>> {
+        let mut done = HashSet::new();
+        let mut names = Vec::new();
+        let mut sets = Vec::new();
+        let mut invalid = Vec::new();
+        for (name, def) in Self::find_names(pattern)? {
+            if def.node_type == NodeType::Node || def.read_only {
+                continue;
+            }
+            if done.insert(def.id) {
+                match Self::value_for(&def, value) {
+                    Ok(Some(data)) => sets.push((def.id, data)),
+                    Ok(None) => {}
+                    Err(_) => invalid.push(name.clone()),
+                }
+            }
+            names.push(name);
+        }
+        if !invalid.is_empty() {
+            return Err(Error::ValueError(format!("{:?} is not a value of {}", value.get_string(), invalid.join(", "))));
+        }
+        for (id, data) in sets {
+            self.set_node_data(id, &data)?;
+        }
+        Ok(names)
+    }
+
+    /// Sets a property to a value as it was read from the console, in the type it has, without
+    /// converting or parsing it. Used to put back saved values.
+    pub fn set_node_data(&mut self, id: i32, value: &WingNodeData) -> Result<()> {
+        if value.has_float() {
+            self.set_float(id, value.get_float())
+        } else if value.has_int() {
+            self.set_int(id, value.get_int())
+        } else {
+            self.set_string(id, &value.get_string())
+        }
+    }
+
+    /// Sets a property to the value, converted to the property's type. Nodes are ignored. Text
+    /// values are read with `WingNodeDef::parse_value()`, so "-6 dB", "1.2 kHz" or an enum's
+    /// long name work too, except for string properties, which get the text exactly as it is.
+    /// Returns `Error::ValueError` for a value the property can't take, such as a text that is
+    /// not one of an enum's items.
+    pub fn set_data(&mut self, def: &WingNodeDef, value: &WingNodeData) -> Result<()> {
+        match Self::value_for(def, value)? {
+            Some(data) => self.set_node_data(def.id, &data),
+            None => Ok(()),
+        }
+    }
+
+    /// The value to send for a property, converted to its type as `set_data()` describes, or
+    /// `None` for a node
+    fn value_for(def: &WingNodeDef, value: &WingNodeData) -> Result<Option<WingNodeData>> {
+        let invalid = || Error::ValueError(format!("{:?} is not a value of property {}", value.get_string(), def.id));
+        let numeric = value.has_float() || value.has_int();
+        let data = match def.node_type {
+            NodeType::Node => return Ok(None),
+            NodeType::String => WingNodeData::with_string(value.get_string()),
+            NodeType::Integer if numeric => WingNodeData::with_i32(value.to_int().ok_or_else(invalid)?),
+            NodeType::FaderLevel |
+            NodeType::LogarithmicFloat |
+            NodeType::LinearFloat if numeric => WingNodeData::with_float(value.to_float().ok_or_else(invalid)?),
+            // enums are checked against their items, also for numbers
+            _ => def.parse_value(&value.get_string()).ok_or_else(invalid)?,
+        };
+        Ok(Some(data))
+    }
+
+    /// Sets a property to the value at a position from 0.0 to 1.0 on its scale, as a UI slider
+    /// would, for example `set_normalized(id, 0.5)` for a fader at -10 dB. The value is quantized
+    /// to the property's steps. See `WingNodeDef::to_normalized()`. Returns `Error::InvalidData`
+    /// if the id is unknown or ambiguous (see `resolve_id()`), or the property has no scale.
+    pub fn set_normalized(&mut self, id: i32, position: f32) -> Result<()> {
+        let (_, def) = self.resolve_id(id).ok_or(Error::InvalidData)?;
+        let value = def.normalized_to_data(position).ok_or(Error::InvalidData)?;
+        self.set_data(&def, &value)
+    }
+
+    /// Shuts the connection down, which ends a `read()` that is blocked on another thread
+    pub(crate) fn shutdown(&self) {
+        // wsock first: a blocked read holds the lock of rsock, and both are the same socket
+        let _ = self.wsock.lock().unwrap().shutdown(std::net::Shutdown::Both);
+        let _ = self.rsock.lock().unwrap().shutdown(std::net::Shutdown::Both);
+    }
+
+    /// Requests the value of a property and returns its position from 0.0 to 1.0 on its scale
+    pub fn get_normalized(&mut self, id: i32) -> Result<f32> {
+        let (_, def) = self.resolve_id(id).ok_or(Error::InvalidData)?;
+        let value = self.get_data(id)?;
+        def.data_to_normalized(&value).ok_or(Error::InvalidData)
+    }
+}
+
+impl MeterSource for WingConsole {
+    fn read_meters(&mut self) -> Result<MeterFrame> {
+        WingConsole::read_meters(self)
+    }
 }
 
 impl Drop for WingConsole {
     fn drop(&mut self) {
-        let _ = self.wsock.clone().lock().unwrap().shutdown(std::net::Shutdown::Both);
-        let _ = self.rsock.clone().lock().unwrap().shutdown(std::net::Shutdown::Both);
+        self.shutdown();
     }
 }
@@ -0,0 +1,709 @@
+//! Crawls the complete property schema of a Wing.
+//!
+//! The crawler requests the children of every node in the tree, keeping many definition requests
+//! outstanding on each connection instead of waiting for every `RequestEnd`. More connections to
+//! the same mixer can be added to spread the requests out further.
+//!
+//! Some nodes have an `mdl` property (FX slots, EQ and dynamics slots and so on) and their other
+//! children depend on the model selected in it. The crawler loads every model in turn and crawls
+//! the children of each. The current model and all values below the node are saved first and
+//! restored afterwards, also when the crawl fails or is stopped.
+//!
+//! With a checkpoint file, every answered request is appended to the file as it comes in, and a
+//! crawl that was interrupted continues where it left off the next time it is run with the same
+//! file. Model slots are checkpointed once all of their models are done. The saved models and
+//! values go into the file as well, so if the crawler was killed before it could restore them, it
+//! restores them first when it resumes.
+//!
+//! ```ignore
+//! let mut wing = WingConsole::connect(None)?;
+//! let result = Crawler::new(&mut wing)
+//!     .checkpoint("propmap.checkpoint")
+//!     .on_progress(|p| print!("\rReceived {} nodes", p.nodes))
+//!     .run()?;
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::{Result, Error, WingConsole, WingResponse};
+use crate::node::{NodeType, WingNodeData, WingNodeDef};
+
+const DEFAULT_PIPELINE_DEPTH: usize = 32;
+
+/// Sort key offset for the children of a model, so that they come after the node's own children
+const MODEL_ORDER: u32 = 1 << 16;
+
+/// A node or property found by the crawler
+pub struct CrawlEntry {
+    pub fullname: String,
+    pub def: WingNodeDef,
+    /// For a model-specific property, such as `/fx/1/HALL/3`, its index below the model. 0 for
+    /// everything else.
+    pub fake: u8,
+    order: Vec<u32>,
+}
+
+/// A node with an `mdl` property. Its other children depend on the model selected in it.
+#[derive(Clone)]
+pub struct ModelSlot {
+    /// The full name of the node, for example `/fx/1`
+    pub fullname: String,
+    pub mdl: WingNodeDef,
+    order: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CrawlProgress {
+    /// Nodes and properties found so far
+    pub nodes: usize,
+    /// Nodes whose children have not been received yet
+    pub pending: usize,
+    /// Model slots crawled so far
+    pub models_done: usize,
+    /// Model slots found so far
+    pub models_total: usize,
+}
+
+pub struct CrawlResult {
+    /// Every node and property, in tree order
+    pub entries: Vec<CrawlEntry>,
+    /// Every node with an `mdl` property, in tree order
+    pub models: Vec<ModelSlot>,
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&CrawlProgress) + Send + 'a>;
+
+pub struct Crawler<'a> {
+    wing: &'a mut WingConsole,
+    extra: Vec<WingConsole>,
+    depth: usize,
+    checkpoint: Option<PathBuf>,
+    log: Option<File>,
+    on_progress: Option<ProgressFn<'a>>,
+    stop: Option<Arc<AtomicBool>>,
+    cycle_models: bool,
+    progress: CrawlProgress,
+    saved: Vec<Saved>,
+}
+
+/// The model and values below a model slot, from before the crawl changed them
+struct Saved {
+    mdl: WingNodeDef,
+    model: String,
+    values: Vec<(WingNodeDef, WingNodeData)>,
+}
+
+struct Task {
+    id: i32,
+    fullname: String,
+    order: Vec<u32>,
+    /// The node is a model slot with a model loaded, and only its model-specific children are
+    /// wanted
+    model_root: bool,
+}
+
+impl Task {
+    fn root() -> Self {
+        Task { id: 0, fullname: String::new(), order: Vec::new(), model_root: false }
+    }
+}
+
+/// State shared by the connections while a pipeline runs
+struct Shared<'s, 'a> {
+    queue: VecDeque<Task>,
+    /// Requests sent but not answered yet, over all connections
+    busy: usize,
+    entries: Vec<CrawlEntry>,
+    models: Vec<ModelSlot>,
+    log: Option<&'s mut File>,
+    progress: &'s mut CrawlProgress,
+    on_progress: Option<&'s mut ProgressFn<'a>>,
+}
+
+impl<'a> Crawler<'a> {
+    pub fn new(wing: &'a mut WingConsole) -> Self {
+        Crawler {
+            wing,
+            extra: Vec::new(),
+            depth: DEFAULT_PIPELINE_DEPTH,
+            checkpoint: None,
+            log: None,
+            on_progress: None,
+            stop: None,
+            cycle_models: true,
+            progress: CrawlProgress::default(),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Adds another connection to the same mixer to send definition requests on. Models are
+    /// always changed through the first connection.
+    pub fn add_connection(mut self, wing: WingConsole) -> Self {
+        self.extra.push(wing);
+        self
+    }
+
+    /// How many definition requests to keep outstanding on each connection. Default is 32.
+    pub fn pipeline_depth(mut self, depth: usize) -> Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    /// Saves progress to the file, and resumes from it if it already exists. The file is removed
+    /// when the crawl completes.
+    pub fn checkpoint<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.checkpoint = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Called whenever a request is answered and whenever a model slot is done
+    pub fn on_progress<F: FnMut(&CrawlProgress) + Send + 'a>(mut self, f: F) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    /// When the flag is set, for example from a Ctrl-C handler, the crawl stops sending requests
+    /// and `run()` returns an `Interrupted` IO error once the outstanding ones are answered
+    pub fn stop_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.stop = Some(flag);
+        self
+    }
+
+    /// Whether to load every model of the model slots to crawl their children. When disabled,
+    /// the Wing is not changed at all, and `CrawlResult::models` tells which slots would be.
+    /// Default is enabled.
+    pub fn cycle_models(mut self, cycle: bool) -> Self {
+        self.cycle_models = cycle;
+        self
+    }
+
+    pub fn run(&mut self) -> Result<CrawlResult> {
+        let mut result = match self.crawl() {
+            Ok(result) => result,
+            Err(e) => {
+                // if this fails too, needs_restore() tells the caller
+                let _ = self.restore_all();
+                return Err(e);
+            }
+        };
+        result.entries.sort_by(|a, b| a.order.cmp(&b.order));
+        result.models.sort_by(|a, b| a.order.cmp(&b.order));
+        if let Some(path) = &self.checkpoint {
+            self.log = None;
+            std::fs::remove_file(path)?;
+        }
+        Ok(result)
+    }
+
+    /// Whether some model slots still have to be restored, because restoring them after an error
+    /// failed too. Call `restore_all()` to try again.
+    pub fn needs_restore(&self) -> bool {
+        !self.saved.is_empty()
+    }
+
+    /// Restores the models and values of all model slots that are changed by the crawl
+    pub fn restore_all(&mut self) -> Result<()> {
+        while !self.saved.is_empty() {
+            self.restore()?;
+        }
+        Ok(())
+    }
+
+    fn crawl(&mut self) -> Result<CrawlResult> {
+        let (tasks, mut entries, mut models, done) = match &self.checkpoint {
+            Some(path) => {
+                let checkpoint = load_checkpoint(path)?;
+                let mut log = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+                if !checkpoint.complete {
+                    // the last line was cut off, so start on a fresh one
+                    writeln!(log)?;
+                }
+                self.log = Some(log);
+                // the last run didn't get to restore these, and the slots are crawled again anyway
+                self.saved = checkpoint.saved;
+                self.restore_all()?;
+                (checkpoint.tasks, checkpoint.entries, checkpoint.models, checkpoint.done)
+            }
+            None => (vec![Task::root()], Vec::new(), Vec::new(), HashSet::new()),
+        };
+        self.progress.nodes = entries.len();
+        self.progress.models_total = models.len();
+        self.progress.models_done = models.iter().filter(|m| done.contains(&m.fullname)).count();
+
+        let (found, found_models) = self.pipeline(tasks, true)?;
+        entries.extend(found);
+        models.extend(found_models);
+
+        if self.cycle_models {
+            models.sort_by(|a, b| a.order.cmp(&b.order));
+            for slot in &models {
+                if done.contains(&slot.fullname) {
+                    continue;
+                }
+                let found = self.crawl_model(slot)?;
+                if let Some(log) = self.log.as_mut() {
+                    let mut line = jzon::object!{ "model": slot.fullname.clone() };
+                    line.insert("entries", entries_to_json(&found)).unwrap();
+                    writeln!(log, "{}", jzon::stringify(line))?;
+                }
+                entries.extend(found);
+                self.progress.models_done += 1;
+                self.report();
+            }
+        }
+
+        Ok(CrawlResult { entries, models })
+    }
+
+    /// Loads every model of the slot in turn and crawls its children, including any model slots
+    /// below them, then puts the slot back the way it was
+    fn crawl_model(&mut self, slot: &ModelSlot) -> Result<Vec<CrawlEntry>> {
+        self.save(&slot.mdl)?;
+        let items: Vec<String> = slot.mdl.string_enum.iter().flatten().map(|x| x.item.clone()).collect();
+        let mut entries = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            self.check_stop()?;
+            self.wing.set_string(slot.mdl.id, item)?;
+            // wait for the model change to be processed before asking for the new children
+            self.wing.get_data(slot.mdl.id)?;
+            let mut order = slot.order.clone();
+            order.push(MODEL_ORDER + i as u32);
+            let task = Task {
+                id: slot.mdl.parent_id,
+                fullname: format!("{}/{}", slot.fullname, item),
+                order,
+                model_root: true,
+            };
+            let (found, nested) = self.pipeline(vec![task], false)?;
+            entries.extend(found);
+            for nested in nested {
+                entries.extend(self.crawl_model(&nested)?);
+            }
+        }
+        self.restore()?;
+        Ok(entries)
+    }
+
+    fn check_stop(&self) -> Result<()> {
+        if self.stop.as_ref().is_some_and(|f| f.load(Ordering::Relaxed)) {
+            Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "interrupted").into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn report(&mut self) {
+        if let Some(f) = self.on_progress.as_mut() {
+            f(&self.progress);
+        }
+    }
+
+    /// Crawls the trees below the tasks with all connections. Returns what was found, and the
+    /// model slots found but not crawled.
+    fn pipeline(&mut self, tasks: Vec<Task>, checkpoint: bool) -> Result<(Vec<CrawlEntry>, Vec<ModelSlot>)> {
+        let depth = self.depth;
+        let stop = self.stop.as_deref();
+        let shared = Mutex::new(Shared {
+            busy: 0,
+            queue: tasks.into(),
+            entries: Vec::new(),
+            models: Vec::new(),
+            log: if checkpoint { self.log.as_mut() } else { None },
+            progress: &mut self.progress,
+            on_progress: self.on_progress.as_mut(),
+        });
+        let wake = Condvar::new();
+
+        let wing = &mut *self.wing;
+        let extra = &mut self.extra;
+        let (first, others) = std::thread::scope(|scope| {
+            let (shared, wake) = (&shared, &wake);
+            let handles: Vec<_> = extra.iter_mut()
+                .map(|w| scope.spawn(move || worker(w, depth, shared, wake, stop)))
+                .collect();
+            let first = worker(wing, depth, shared, wake, stop);
+            let others: Vec<bool> = handles.into_iter().map(|h| h.join().unwrap().is_ok()).collect();
+            (first, others)
+        });
+        // connections that failed are not used again
+        let mut others = others.into_iter();
+        self.extra.retain(|_| others.next().unwrap_or(false));
+        let Shared { entries, models, .. } = shared.into_inner().unwrap();
+        first?;
+        self.check_stop()?;
+        Ok((entries, models))
+    }
+
+    fn get_children(&mut self, id: i32) -> Result<Vec<WingNodeDef>> {
+        self.wing.request_node_definition(id)?;
+        let mut children = Vec::new();
+        loop {
+            match self.wing.read()? {
+                WingResponse::NodeDef(def) => if def.parent_id == id { children.push(def) },
+                WingResponse::RequestEnd => return Ok(children),
+                WingResponse::NodeData(_, _) => {}
+            }
+        }
+    }
+
+    /// Saves the current model of `mdl` and every writable value below its parent
+    fn save(&mut self, mdl: &WingNodeDef) -> Result<()> {
+        let model = self.wing.get_data(mdl.id)?.get_string();
+        let mut defs = Vec::new();
+        let mut parents = vec![mdl.parent_id];
+        while let Some(parent) = parents.pop() {
+            for def in self.get_children(parent)? {
+                if def.node_type == NodeType::Node {
+                    parents.push(def.id);
+                } else if !def.read_only && def.id != mdl.id {
+                    defs.push(def);
+                }
+            }
+        }
+
+        for def in &defs {
+            self.wing.request_node_data(def.id)?;
+        }
+        let mut data = HashMap::new();
+        let mut pending = defs.len();
+        while pending > 0 {
+            match self.wing.read()? {
+                WingResponse::NodeData(id, value) => { data.insert(id, value); }
+                WingResponse::RequestEnd => pending -= 1,
+                WingResponse::NodeDef(_) => {}
+            }
+        }
+        let values = defs.into_iter()
+            .filter_map(|def| data.remove(&def.id).map(|value| (def, value)))
+            .collect();
+
+        let saved = Saved { mdl: mdl.clone(), model, values };
+        if let Some(log) = self.log.as_mut() {
+            writeln!(log, "{}", jzon::stringify(saved.to_json()))?;
+        }
+        self.saved.push(saved);
+        Ok(())
+    }
+
+    /// Puts back the most recently saved model and values
+    fn restore(&mut self) -> Result<()> {
+        if let Some(saved) = self.saved.last() {
+            self.wing.set_string(saved.mdl.id, &saved.model)?;
+            // wait for the model change to be processed before setting the values below it
+            self.wing.get_data(saved.mdl.id)?;
+            // exactly as they were read, set_data() would parse and trim them
+            for (def, value) in &saved.values {
+                self.wing.set_node_data(def.id, value)?;
+            }
+            if let Some(log) = self.log.as_mut() {
+                writeln!(log, "{}", jzon::stringify(jzon::object!{ "restored": saved.mdl.id }))?;
+            }
+            self.saved.pop();
+        }
+        Ok(())
+    }
+}
+
+impl Saved {
+    fn to_json(&self) -> jzon::JsonValue {
+        let mut values = jzon::array![];
+        for (def, value) in &self.values {
+            values.push(jzon::object!{ "raw": to_hex(&def.raw), "value": data_to_json(value) }).unwrap();
+        }
+        jzon::object!{
+            "saved": to_hex(&self.mdl.raw),
+            "model": self.model.clone(),
+            "values": values,
+        }
+    }
+
+    fn from_json(json: &jzon::JsonValue) -> Option<Self> {
+        let values = json["values"].members()
+            .map(|v| Some((WingNodeDef::from_bytes(&from_hex(v["raw"].as_str()?)?), data_from_json(&v["value"])?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Saved {
+            mdl: WingNodeDef::from_bytes(&from_hex(json["saved"].as_str()?)?),
+            model: json["model"].as_str()?.to_string(),
+            values,
+        })
+    }
+}
+
+/// Sends requests from the shared queue on one connection and processes the answers, until the
+/// queue is empty and no connection has outstanding requests, or the crawl is stopped. If the
+/// connection fails, its outstanding requests are put back in the queue for the others.
+fn worker(wing: &mut WingConsole, depth: usize, shared: &Mutex<Shared>, wake: &Condvar, stop: Option<&AtomicBool>) -> Result<()> {
+    let mut inflight = VecDeque::new();
+    let result = work(wing, depth, shared, wake, stop, &mut inflight);
+    if result.is_err() {
+        let mut s = shared.lock().unwrap();
+        s.busy -= inflight.len();
+        while let Some(task) = inflight.pop_back() {
+            s.queue.push_front(task);
+        }
+    }
+    wake.notify_all();
+    result
+}
+
+fn work(wing: &mut WingConsole, depth: usize, shared: &Mutex<Shared>, wake: &Condvar, stop: Option<&AtomicBool>, inflight: &mut VecDeque<Task>) -> Result<()> {
+    loop {
+        let mut send = Vec::new();
+        {
+            let mut s = shared.lock().unwrap();
+            loop {
+                let stopped = stop.is_some_and(|f| f.load(Ordering::Relaxed));
+                if !stopped {
+                    while inflight.len() < depth {
+                        let Some(task) = s.queue.pop_front() else { break };
+                        send.push(task.id);
+                        inflight.push_back(task);
+                        s.busy += 1;
+                    }
+                }
+                if !inflight.is_empty() {
+                    break;
+                }
+                if stopped || (s.queue.is_empty() && s.busy == 0) {
+                    return Ok(());
+                }
+                s = wake.wait_timeout(s, Duration::from_millis(100)).unwrap().0;
+            }
+        }
+
+        for id in send {
+            wing.request_node_definition(id)?;
+        }
+
+        // The Wing answers the requests of a connection in the order they were sent, and the
+        // RequestEnd that ends an answer has no id, so this is taken to be the answer to the
+        // oldest request. A child of another request in flight means that didn't hold.
+        let id = inflight.front().unwrap().id;
+        let mut children = Vec::new();
+        loop {
+            match wing.read()? {
+                WingResponse::NodeDef(def) if def.parent_id == id => children.push(def),
+                WingResponse::NodeDef(def) if inflight.iter().any(|t| t.id == def.parent_id) => return Err(Error::InvalidData),
+                WingResponse::NodeDef(_) => {}
+                WingResponse::RequestEnd => break,
+                WingResponse::NodeData(_, _) => {}
+            }
+        }
+
+        shared.lock().unwrap().done(inflight.front().unwrap(), children)?;
+        inflight.pop_front();
+        wake.notify_all();
+    }
+}
+
+impl Shared<'_, '_> {
+    fn done(&mut self, task: &Task, children: Vec<WingNodeDef>) -> Result<()> {
+        let slot = if task.model_root {
+            None
+        } else {
+            children.iter().find(|d| d.name == "mdl" && d.node_type == NodeType::StringEnum).cloned()
+        };
+
+        let mut found = Vec::new();
+        for (pos, def) in children.into_iter().enumerate() {
+            // a model slot's numbered children belong to the model, and are crawled per model
+            if (slot.is_some() && def.index != 0) || (task.model_root && def.index == 0) {
+                continue;
+            }
+            let fullname = if def.name.is_empty() {
+                format!("{}/{}", task.fullname, def.index)
+            } else {
+                format!("{}/{}", task.fullname, def.name)
+            };
+            let mut order = task.order.clone();
+            order.push(pos as u32);
+            let fake = if task.model_root { def.index as u8 } else { 0 };
+            found.push(CrawlEntry { fullname, def, fake, order });
+        }
+        let slot = slot.map(|mdl| ModelSlot { fullname: task.fullname.clone(), mdl, order: task.order.clone() });
+
+        if let Some(log) = self.log.as_mut() {
+            let mut line = jzon::object!{ "done": task.fullname.clone() };
+            line.insert("entries", entries_to_json(&found)).unwrap();
+            if let Some(slot) = &slot {
+                line.insert("slot", jzon::object!{
+                    "fullname": slot.fullname.clone(),
+                    "order": slot.order.clone(),
+                    "mdl": to_hex(&slot.mdl.raw),
+                }).unwrap();
+            }
+            writeln!(log, "{}", jzon::stringify(line))?;
+        }
+
+        self.busy -= 1;
+        for entry in &found {
+            if entry.def.node_type == NodeType::Node {
+                self.queue.push_back(Task {
+                    id: entry.def.id,
+                    fullname: entry.fullname.clone(),
+                    order: entry.order.clone(),
+                    model_root: false,
+                });
+            }
+        }
+        self.progress.nodes += found.len();
+        self.progress.pending = self.queue.len() + self.busy;
+        if let Some(slot) = slot {
+            self.progress.models_total += 1;
+            self.models.push(slot);
+        }
+        self.entries.extend(found);
+        if let Some(f) = self.on_progress.as_mut() {
+            f(self.progress);
+        }
+        Ok(())
+    }
+}
+
+fn entries_to_json(entries: &[CrawlEntry]) -> jzon::JsonValue {
+    let mut ret = jzon::array![];
+    for e in entries {
+        ret.push(jzon::object!{
+            "fullname": e.fullname.clone(),
+            "fake": e.fake,
+            "order": e.order.clone(),
+            "raw": to_hex(&e.def.raw),
+        }).unwrap();
+    }
+    ret
+}
+
+fn entry_from_json(json: &jzon::JsonValue) -> Option<CrawlEntry> {
+    Some(CrawlEntry {
+        fullname: json["fullname"].as_str()?.to_string(),
+        def: WingNodeDef::from_bytes(&from_hex(json["raw"].as_str()?)?),
+        fake: json["fake"].as_u8()?,
+        order: order_from_json(&json["order"])?,
+    })
+}
+
+fn order_from_json(json: &jzon::JsonValue) -> Option<Vec<u32>> {
+    json.members().map(|x| x.as_u32()).collect()
+}
+
+/// A value as it was read, with its type
+fn data_to_json(data: &WingNodeData) -> jzon::JsonValue {
+    if data.has_string() {
+        jzon::object!{ "string": data.get_string() }
+    } else if data.has_float() {
+        jzon::object!{ "float": data.get_float() }
+    } else if data.has_int() {
+        jzon::object!{ "int": data.get_int() }
+    } else {
+        jzon::object!{}
+    }
+}
+
+fn data_from_json(json: &jzon::JsonValue) -> Option<WingNodeData> {
+    if json.has_key("string") {
+        Some(WingNodeData::with_string(json["string"].as_str()?.to_string()))
+    } else if json.has_key("float") {
+        Some(WingNodeData::with_float(json["float"].as_f32()?))
+    } else if json.has_key("int") {
+        Some(WingNodeData::with_i32(json["int"].as_i32()?))
+    } else {
+        Some(WingNodeData::new())
+    }
+}
+
+/// What a checkpoint file says about an interrupted crawl
+struct Checkpoint {
+    /// The nodes whose children are still to be requested
+    tasks: Vec<Task>,
+    entries: Vec<CrawlEntry>,
+    models: Vec<ModelSlot>,
+    /// The model slots that are done
+    done: HashSet<String>,
+    /// The models and values that were saved but not restored, innermost last
+    saved: Vec<Saved>,
+    /// Whether the file ended with a complete line
+    complete: bool,
+}
+
+/// Replays a checkpoint file
+fn load_checkpoint(path: &Path) -> Result<Checkpoint> {
+    let mut pending = HashMap::new();
+    pending.insert(String::new(), Task::root());
+    let mut entries = Vec::new();
+    let mut models = Vec::new();
+    let mut done = HashSet::new();
+    let mut saved = Vec::new();
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Checkpoint { tasks: vec![Task::root()], entries, models, done, saved, complete: true });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        // a line cut off by a crash is skipped, and its request is simply sent again
+        let Ok(json) = jzon::parse(&line) else { continue };
+        if json.has_key("saved") {
+            saved.push(Saved::from_json(&json).ok_or(Error::InvalidData)?);
+            continue;
+        }
+        if let Some(id) = json["restored"].as_i32() {
+            // restores happen innermost first
+            if saved.last().map(|s: &Saved| s.mdl.id) == Some(id) {
+                saved.pop();
+            }
+            continue;
+        }
+        let Some(found) = json["entries"].members().map(entry_from_json).collect::<Option<Vec<_>>>() else {
+            return Err(Error::InvalidData);
+        };
+        if let Some(name) = json["model"].as_str() {
+            done.insert(name.to_string());
+        } else if let Some(name) = json["done"].as_str() {
+            pending.remove(name);
+            for entry in &found {
+                if entry.def.node_type == NodeType::Node {
+                    pending.insert(entry.fullname.clone(), Task {
+                        id: entry.def.id,
+                        fullname: entry.fullname.clone(),
+                        order: entry.order.clone(),
+                        model_root: false,
+                    });
+                }
+            }
+            let slot = &json["slot"];
+            if slot.is_object() {
+                models.push(ModelSlot {
+                    fullname: slot["fullname"].as_str().ok_or(Error::InvalidData)?.to_string(),
+                    mdl: WingNodeDef::from_bytes(&from_hex(slot["mdl"].as_str().unwrap_or_default()).ok_or(Error::InvalidData)?),
+                    order: order_from_json(&slot["order"]).ok_or(Error::InvalidData)?,
+                });
+            }
+        }
+        entries.extend(found);
+    }
+    let complete = std::fs::read(path)?.last().map_or(true, |b| *b == b'\n');
+
+    let mut tasks: Vec<Task> = pending.into_values().collect();
+    tasks.sort_by(|a, b| a.order.cmp(&b.order));
+    Ok(Checkpoint { tasks, entries, models, done, saved, complete })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
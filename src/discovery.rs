@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+use crate::model::{WingModel, FirmwareVersion};
+
+const DISCOVERY_PORT: u16 = 2222;
+/// As long as `WingConsole::scan()` has always waited, ten quiet periods of 500 ms
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the probe is sent again while waiting for answers, in case a packet was lost
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryInfo {
+    pub ip:        String,
+    pub name:      String,
+    pub model:     String,
+    pub serial:    String,
+    pub firmware:  String,
+    /// Name of the local network interface the console answered on, or empty if it is not on
+    /// the subnet of any of them
+    pub interface: String,
+}
+
+impl DiscoveryInfo {
+    /// Parses an answer to the discovery probe, `WING,ip,name,model,serial,firmware`
+    fn parse(response: &[u8], interface: String) -> Option<Self> {
+        let response = std::str::from_utf8(response).ok()?;
+        let tokens: Vec<&str> = response.split(',').collect();
+        if tokens.len() < 6 || tokens[0] != "WING" {
+            return None;
+        }
+        Some(DiscoveryInfo {
+            ip:       tokens[1].to_string(),
+            name:     tokens[2].to_string(),
+            model:    tokens[3].to_string(),
+            serial:   tokens[4].to_string(),
+            firmware: tokens[5].to_string(),
+            interface,
+        })
+    }
+
+    /// The model, parsed from `model`
+    pub fn wing_model(&self) -> WingModel {
+        WingModel::parse(&self.model)
+    }
+
+    /// The firmware version, parsed from `firmware`, or `None` if it isn't in the usual format
+    pub fn firmware_version(&self) -> Option<FirmwareVersion> {
+        self.firmware.parse().ok()
+    }
+
+    /// Whether the console's details are the same, leaving out `interface`: a console on the
+    /// subnets of several interfaces can answer on any of them
+    fn same_details(&self, other: &DiscoveryInfo) -> bool {
+        self.ip == other.ip && self.name == other.name && self.model == other.model
+            && self.serial == other.serial && self.firmware == other.firmware
+    }
+}
+
+/// An IPv4 network interface that discovery probes are broadcast on
+#[derive(Debug, Clone)]
+struct Interface {
+    name: String,
+    ip: Ipv4Addr,
+    netmask: Ipv4Addr,
+    broadcast: Ipv4Addr,
+}
+
+impl Interface {
+    fn list() -> Vec<Interface> {
+        if_addrs::get_if_addrs().unwrap_or_default().into_iter().filter_map(|i| match i.addr {
+            if_addrs::IfAddr::V4(ref v4) if !v4.ip.is_loopback() => Some(Interface {
+                name: i.name.clone(),
+                ip: v4.ip,
+                netmask: v4.netmask,
+                // broadcast = ip | ~netmask, see Discovery.md
+                broadcast: v4.broadcast.unwrap_or_else(|| Ipv4Addr::from(u32::from(v4.ip) | !u32::from(v4.netmask))),
+            }),
+            _ => None,
+        }).collect()
+    }
+
+    fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.netmask) == u32::from(self.ip) & u32::from(self.netmask)
+    }
+}
+
+/// Finds Wing consoles on the network, with more control than `WingConsole::scan()`.
+///
+/// The probe is sent to the broadcast address of the subnet of every network interface, so
+/// consoles are found on hosts with several networks, for example a Dante network and an office
+/// network. Consoles that can't be reached by broadcast, for example on another subnet behind a
+/// router, can be added as targets to probe directly. Consoles that answer more than once are
+/// only listed once.
+///
+/// ```ignore
+/// let consoles = Discovery::new()
+///     .target("10.0.20.5".parse()?)
+///     .timeout(Duration::from_secs(1))
+///     .scan()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    targets: Vec<IpAddr>,
+    port: u16,
+    broadcast: bool,
+    timeout: Duration,
+    stop_on_first: bool,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Discovery { targets: Vec::new(), port: DISCOVERY_PORT, broadcast: true, timeout: DEFAULT_TIMEOUT, stop_on_first: false }
+    }
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Also sends the probe directly to this address
+    pub fn target(mut self, ip: IpAddr) -> Self {
+        self.targets.push(ip);
+        self
+    }
+
+    /// The UDP port the probe is sent to. Default is 2222, the port the Wing answers on.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Whether to broadcast the probe on every network interface. Default is true.
+    pub fn broadcast(mut self, broadcast: bool) -> Self {
+        self.broadcast = broadcast;
+        self
+    }
+
+    /// How long to wait for answers. Default is 5 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Return as soon as one console has answered
+    pub fn stop_on_first(mut self, stop: bool) -> Self {
+        self.stop_on_first = stop;
+        self
+    }
+
+    /// Sends the probes and returns the consoles that answered within the timeout, in the order
+    /// they answered
+    pub fn scan(&self) -> Result<Vec<DiscoveryInfo>> {
+        self.scan_until(|_| self.stop_on_first)
+    }
+
+    /// Sends the probes until a console answers that `matches` accepts, or the timeout passes
+    pub fn find<F: Fn(&DiscoveryInfo) -> bool>(&self, matches: F) -> Result<Option<DiscoveryInfo>> {
+        Ok(self.scan_until(&matches)?.into_iter().find(matches))
+    }
+
+    /// Returns the consoles that answered within the timeout, or up to the first one `done`
+    /// accepts
+    fn scan_until<F: Fn(&DiscoveryInfo) -> bool>(&self, done: F) -> Result<Vec<DiscoveryInfo>> {
+        let interfaces = Interface::list();
+
+        // one socket per interface, so the probe leaves on each of them, plus one for the limited
+        // broadcast and the direct targets
+        let mut sockets: Vec<(UdpSocket, Vec<SocketAddr>)> = Vec::new();
+        for i in interfaces.iter().filter(|_| self.broadcast) {
+            if let Ok(socket) = UdpSocket::bind((i.ip, 0)) {
+                socket.set_broadcast(true)?;
+                socket.set_nonblocking(true)?;
+                sockets.push((socket, vec![SocketAddr::from((i.broadcast, self.port))]));
+            }
+        }
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        let mut addrs: Vec<SocketAddr> = self.targets.iter().map(|ip| SocketAddr::new(*ip, self.port)).collect();
+        if self.broadcast {
+            addrs.push(SocketAddr::from((Ipv4Addr::BROADCAST, self.port)));
+        }
+        sockets.push((socket, addrs));
+
+        let mut results = Vec::new();
+        let mut seen = HashSet::new();
+        let start = Instant::now();
+        let mut next_probe = start;
+        while start.elapsed() < self.timeout {
+            if Instant::now() >= next_probe {
+                for (socket, addrs) in &sockets {
+                    for addr in addrs {
+                        // an interface that is down or has no route fails here, the others still work
+                        let _ = socket.send_to(b"WING?", addr);
+                    }
+                }
+                next_probe += PROBE_INTERVAL;
+            }
+
+            let mut received = false;
+            for (socket, _) in &sockets {
+                let mut buf = [0u8; 1024];
+                let Ok((len, from)) = socket.recv_from(&mut buf) else { continue };
+                received = true;
+                let interface = match from.ip() {
+                    IpAddr::V4(ip) => interfaces.iter().find(|i| i.contains(ip)).map(|i| i.name.clone()).unwrap_or_default(),
+                    IpAddr::V6(_) => String::new(),
+                };
+                if let Some(info) = DiscoveryInfo::parse(&buf[..len], interface) {
+                    if seen.insert(info.serial.clone()) {
+                        let stop = done(&info);
+                        results.push(info);
+                        if stop {
+                            return Ok(results);
+                        }
+                    }
+                }
+            }
+            if !received {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// A change seen by a `DiscoveryWatcher`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoveryEvent {
+    /// A console answered that had not answered before, or had been lost
+    Appeared(DiscoveryInfo),
+    /// A console answered with different details, for example a new name or firmware. Answering
+    /// on another network interface is not a change.
+    Changed { old: DiscoveryInfo, new: DiscoveryInfo },
+    /// A console has not answered for the `lost_after` time
+    Lost(DiscoveryInfo),
+}
+
+/// Probes for consoles in the background and reports them appearing, changing and going away.
+///
+/// ```ignore
+/// let watcher = DiscoveryWatcher::new(Discovery::new())
+///     .interval(Duration::from_secs(2))
+///     .start();
+/// loop {
+///     if let Some(event) = watcher.next_event(Duration::from_secs(1)) {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+pub struct DiscoveryWatcher {
+    discovery: Discovery,
+    interval: Duration,
+    lost_after: Option<Duration>,
+    consoles: Arc<Mutex<HashMap<String, (DiscoveryInfo, Instant)>>>,
+    sender: Option<mpsc::Sender<DiscoveryEvent>>,
+    events: mpsc::Receiver<DiscoveryEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DiscoveryWatcher {
+    pub fn new(discovery: Discovery) -> Self {
+        let (sender, events) = mpsc::channel();
+        DiscoveryWatcher {
+            discovery,
+            interval: DEFAULT_WATCH_INTERVAL,
+            lost_after: None,
+            consoles: Default::default(),
+            sender: Some(sender),
+            events,
+            stop: Default::default(),
+            thread: None,
+        }
+    }
+
+    /// How often to probe. Each probe waits for answers for the discovery's timeout, or the
+    /// interval if that is shorter. Default is 2 seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long a console can go without answering before it is reported lost. Default is three
+    /// intervals.
+    pub fn lost_after(mut self, timeout: Duration) -> Self {
+        self.lost_after = Some(timeout);
+        self
+    }
+
+    /// Starts probing on a background thread. It stops when the watcher is dropped.
+    pub fn start(mut self) -> Self {
+        let Some(sender) = self.sender.take() else { return self };
+        let discovery = self.discovery.clone().timeout(self.discovery.timeout.min(self.interval));
+        let interval = self.interval;
+        let lost_after = self.lost_after.unwrap_or(interval * 3);
+        let consoles = self.consoles.clone();
+        let stop = self.stop.clone();
+
+        self.thread = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                // a failed probe, for example with the network down, is the same as no answers
+                let found = discovery.scan().unwrap_or_default();
+                let now = Instant::now();
+
+                let mut events = Vec::new();
+                {
+                    let mut consoles = consoles.lock().unwrap();
+                    for info in found {
+                        match consoles.get_mut(&info.serial) {
+                            None => {
+                                consoles.insert(info.serial.clone(), (info.clone(), now));
+                                events.push(DiscoveryEvent::Appeared(info));
+                            }
+                            // keep the interface as first reported, so events stay consistent
+                            Some((old, seen)) if old.same_details(&info) => *seen = now,
+                            Some((old, seen)) => {
+                                let old = std::mem::replace(old, info.clone());
+                                *seen = now;
+                                events.push(DiscoveryEvent::Changed { old, new: info });
+                            }
+                        }
+                    }
+                    consoles.retain(|_, (info, seen)| {
+                        let keep = now.duration_since(*seen) < lost_after;
+                        if !keep {
+                            events.push(DiscoveryEvent::Lost(info.clone()));
+                        }
+                        keep
+                    });
+                }
+                for event in events {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+
+                // wait for the next probe, but notice a stop quickly
+                while !stop.load(Ordering::Relaxed) && started.elapsed() < interval {
+                    std::thread::sleep(interval.saturating_sub(started.elapsed()).min(Duration::from_millis(50)));
+                }
+            }
+        }));
+        self
+    }
+
+    /// The events that happened since the last call, oldest first, without waiting
+    pub fn events(&self) -> mpsc::TryIter<'_, DiscoveryEvent> {
+        self.events.try_iter()
+    }
+
+    /// Waits for the next event, up to `timeout`
+    pub fn next_event(&self, timeout: Duration) -> Option<DiscoveryEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// The consoles that are currently present
+    pub fn consoles(&self) -> Vec<DiscoveryInfo> {
+        self.consoles.lock().unwrap().values().map(|(info, _)| info.clone()).collect()
+    }
+}
+
+impl Drop for DiscoveryWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Exports a schema as a JSON Schema document or as TypeScript declarations.
+//!
+//! Both describe the console state as a JSON object nested by property path, so `/ch/1/fdr` is
+//! `{"ch": {"1": {"fdr": -6.0}}}`. Every property keeps its type, range, unit, enum items and
+//! read-only flag. Nodes with the same structure, such as the 40 channels, share one named type
+//! (a `$defs` entry or an `interface`). Nothing is required, since model-specific properties only
+//! exist while their model is loaded. A property that also has children of its own, such as
+//! `/ch/1/dly` with `/ch/1/dly/on`, keeps its key for its value, and its children are under a
+//! sibling key with `_node` appended (`dly_node`), like the `codegen` methods.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::node::{WingNodeDef, NodeType};
+use crate::schema::Schema;
+use crate::codegen::{camel, unique};
+
+/// Returns a JSON Schema (draft 2020-12) document for the console state
+pub fn json_schema(schema: &Schema) -> jzon::JsonValue {
+    let types = Types::new(schema);
+    let mut doc = jzon::object!{
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Behringer Wing properties",
+    };
+    for (k, v) in object_schema(&types, 0).entries() {
+        doc.insert(k, v.clone()).unwrap();
+    }
+    let mut defs = jzon::object!{};
+    for (i, obj) in types.objects.iter().enumerate().skip(1) {
+        if obj.count > 1 {
+            defs.insert(&obj.name, object_schema(&types, i)).unwrap();
+        }
+    }
+    if !defs.is_empty() {
+        doc.insert("$defs", defs).unwrap();
+    }
+    doc
+}
+
+/// Returns TypeScript declarations for the console state. The state itself is `Wing`.
+pub fn typescript(schema: &Schema) -> String {
+    let types = Types::new(schema);
+    let mut out = String::new();
+    writeln!(out, "// Generated by libwing::export from a schema with {} entries. Do not edit.", schema.len()).unwrap();
+    for (i, obj) in types.objects.iter().enumerate() {
+        if i == 0 || obj.count > 1 {
+            writeln!(out).unwrap();
+            if !obj.title.is_empty() {
+                writeln!(out, "/** {} */", obj.title).unwrap();
+            }
+            write!(out, "export interface {} ", obj.name).unwrap();
+            ts_object(&types, i, 0, &mut out);
+            writeln!(out).unwrap();
+        }
+    }
+    out
+}
+
+/// A path segment, ordered numerically if it is a number
+#[derive(PartialEq, Eq)]
+struct Segment(String);
+
+impl Ord for Segment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.0.parse::<u32>(), other.0.parse::<u32>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => self.0.cmp(&other.0),
+        }
+    }
+}
+
+impl PartialOrd for Segment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct Tree<'a> {
+    def: Option<&'a WingNodeDef>,
+    children: BTreeMap<Segment, Tree<'a>>,
+}
+
+enum Prop<'a> {
+    Leaf(&'a WingNodeDef),
+    Object(usize),
+}
+
+struct Object<'a> {
+    name: String,
+    title: String,
+    props: Vec<(String, Prop<'a>)>,
+    /// How many nodes have this structure
+    count: usize,
+}
+
+/// The distinct node structures of a schema. The first one is the root.
+struct Types<'a> {
+    objects: Vec<Object<'a>>,
+    index: HashMap<String, usize>,
+    names: HashSet<String>,
+}
+
+impl<'a> Types<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        let mut root = Tree::default();
+        for (fullname, def) in schema.iter() {
+            let mut t = &mut root;
+            for seg in fullname.split('/').filter(|s| !s.is_empty()) {
+                t = t.children.entry(Segment(seg.to_string())).or_default();
+            }
+            t.def = Some(def);
+        }
+
+        let mut types = Types { objects: Vec::new(), index: HashMap::new(), names: HashSet::new() };
+        // reserve the root's slot so that it is always first
+        types.objects.push(Object { name: unique(&mut types.names, "Wing".to_string()), title: String::new(), props: Vec::new(), count: 1 });
+        let props = types.props(&mut Vec::new(), &root);
+        types.objects[0].props = props;
+        types
+    }
+
+    fn props(&mut self, path: &mut Vec<String>, tree: &Tree<'a>) -> Vec<(String, Prop<'a>)> {
+        let mut props = Vec::new();
+        let mut keys: HashSet<String> = tree.children.keys().map(|s| s.0.clone()).collect();
+        for (seg, child) in &tree.children {
+            let leaf = child.def.filter(|def| def.node_type != NodeType::Node);
+            if let Some(def) = leaf {
+                props.push((seg.0.clone(), Prop::Leaf(def)));
+                if child.children.is_empty() {
+                    continue;
+                }
+            }
+            let key = match leaf {
+                Some(_) => unique(&mut keys, format!("{}_node", seg.0)),
+                None => seg.0.clone(),
+            };
+            path.push(seg.0.clone());
+            let i = self.intern(path, child);
+            path.pop();
+            props.push((key, Prop::Object(i)));
+        }
+        props
+    }
+
+    fn intern(&mut self, path: &mut Vec<String>, tree: &Tree<'a>) -> usize {
+        let props = self.props(path, tree);
+        let title = tree.def.map(|d| d.long_name.clone()).unwrap_or_default();
+
+        let mut key = title.clone();
+        for (name, prop) in &props {
+            match prop {
+                Prop::Leaf(def) => write!(key, "\n{}={}", name, jzon::stringify(leaf_schema(def))).unwrap(),
+                Prop::Object(i) => write!(key, "\n{}=#{}", name, i).unwrap(),
+            }
+        }
+        if let Some(&i) = self.index.get(&key) {
+            self.objects[i].count += 1;
+            return i;
+        }
+
+        let mut name: String = path.iter().filter(|s| s.parse::<u32>().is_err()).map(|s| camel(s)).collect();
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert_str(0, "Node");
+        }
+        let name = unique(&mut self.names, name);
+        self.objects.push(Object { name, title, props, count: 1 });
+        self.index.insert(key, self.objects.len() - 1);
+        self.objects.len() - 1
+    }
+}
+
+fn leaf_schema(def: &WingNodeDef) -> jzon::JsonValue {
+    let mut j = jzon::object!{};
+    if !def.long_name.is_empty() {
+        j.insert("title", def.long_name.clone()).unwrap();
+    }
+    match def.node_type {
+        NodeType::Node => {
+            j.insert("type", "object").unwrap();
+        }
+        NodeType::LinearFloat |
+        NodeType::LogarithmicFloat |
+        NodeType::FaderLevel => {
+            j.insert("type", "number").unwrap();
+            if let Some(min) = def.min_float { j.insert("minimum", min).unwrap(); }
+            if let Some(max) = def.max_float { j.insert("maximum", max).unwrap(); }
+            if let Some(steps) = def.steps { j.insert("x-steps", steps).unwrap(); }
+        }
+        NodeType::Integer => {
+            j.insert("type", "integer").unwrap();
+            if let Some(min) = def.min_int { j.insert("minimum", min).unwrap(); }
+            if let Some(max) = def.max_int { j.insert("maximum", max).unwrap(); }
+        }
+        NodeType::String => {
+            j.insert("type", "string").unwrap();
+            if let Some(len) = def.max_string_len { j.insert("maxLength", len).unwrap(); }
+        }
+        NodeType::StringEnum => {
+            j.insert("type", "string").unwrap();
+            let items = def.string_enum.iter().flatten()
+                .map(|i| enum_item(jzon::JsonValue::from(i.item.clone()), &i.long_item));
+            j.insert("oneOf", items.collect::<Vec<_>>()).unwrap();
+        }
+        NodeType::FloatEnum => {
+            j.insert("type", "number").unwrap();
+            let items = def.float_enum.iter().flatten()
+                .map(|i| enum_item(jzon::JsonValue::from(i.item), &i.long_item));
+            j.insert("oneOf", items.collect::<Vec<_>>()).unwrap();
+        }
+    }
+    j.insert("x-type", def.node_type.as_str()).unwrap();
+    if !def.unit.as_str().is_empty() {
+        j.insert("x-unit", def.unit.as_str()).unwrap();
+    }
+    if def.read_only {
+        j.insert("readOnly", true).unwrap();
+    }
+    j
+}
+
+fn enum_item(value: jzon::JsonValue, long_item: &str) -> jzon::JsonValue {
+    let mut j = jzon::object!{ "const": value };
+    if !long_item.is_empty() {
+        j.insert("title", long_item).unwrap();
+    }
+    j
+}
+
+fn object_schema(types: &Types, i: usize) -> jzon::JsonValue {
+    let obj = &types.objects[i];
+    let mut j = jzon::object!{ "type": "object" };
+    if !obj.title.is_empty() {
+        j.insert("title", obj.title.clone()).unwrap();
+    }
+    let mut props = jzon::object!{};
+    for (name, prop) in &obj.props {
+        let p = match prop {
+            Prop::Leaf(def) => leaf_schema(def),
+            Prop::Object(i) if types.objects[*i].count > 1 => {
+                jzon::object!{ "$ref": format!("#/$defs/{}", types.objects[*i].name) }
+            }
+            Prop::Object(i) => object_schema(types, *i),
+        };
+        props.insert(name, p).unwrap();
+    }
+    j.insert("properties", props).unwrap();
+    j.insert("additionalProperties", false).unwrap();
+    j
+}
+
+fn ts_object(types: &Types, i: usize, indent: usize, out: &mut String) {
+    let pad = "    ".repeat(indent + 1);
+    writeln!(out, "{{").unwrap();
+    for (name, prop) in &types.objects[i].props {
+        let key = ts_key(name);
+        match prop {
+            Prop::Leaf(def) => {
+                writeln!(out, "{}/** {} */", pad, describe(def)).unwrap();
+                let ro = if def.read_only { "readonly " } else { "" };
+                writeln!(out, "{}{}{}?: {};", pad, ro, key, ts_type(def)).unwrap();
+            }
+            Prop::Object(c) if types.objects[*c].count > 1 => {
+                writeln!(out, "{}{}?: {};", pad, key, types.objects[*c].name).unwrap();
+            }
+            Prop::Object(c) => {
+                if !types.objects[*c].title.is_empty() {
+                    writeln!(out, "{}/** {} */", pad, types.objects[*c].title).unwrap();
+                }
+                write!(out, "{}{}?: ", pad, key).unwrap();
+                ts_object(types, *c, indent + 1, out);
+                writeln!(out, ";").unwrap();
+            }
+        }
+    }
+    write!(out, "{}}}", "    ".repeat(indent)).unwrap();
+}
+
+fn ts_key(name: &str) -> String {
+    let ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if ident { name.to_string() } else { jzon::stringify(name) }
+}
+
+fn ts_type(def: &WingNodeDef) -> String {
+    match def.node_type {
+        NodeType::Node => "object".to_string(),
+        NodeType::String => "string".to_string(),
+        NodeType::Integer |
+        NodeType::LinearFloat |
+        NodeType::LogarithmicFloat |
+        NodeType::FaderLevel => "number".to_string(),
+        NodeType::StringEnum => {
+            let items: Vec<String> = def.string_enum.iter().flatten().map(|i| jzon::stringify(i.item.as_str())).collect();
+            if items.is_empty() { "string".to_string() } else { items.join(" | ") }
+        }
+        NodeType::FloatEnum => {
+            let items: Vec<String> = def.float_enum.iter().flatten().map(|i| jzon::stringify(i.item)).collect();
+            if items.is_empty() { "number".to_string() } else { items.join(" | ") }
+        }
+    }
+}
+
+/// A one-line description of a property for a doc comment
+fn describe(def: &WingNodeDef) -> String {
+    let mut d = String::new();
+    if !def.long_name.is_empty() {
+        d.push_str(&def.long_name);
+        d.push_str(": ");
+    }
+    d.push_str(def.node_type.as_str());
+    if !def.unit.as_str().is_empty() {
+        d.push_str(&format!(", {}", def.unit.as_str()));
+    }
+    if let (Some(min), Some(max)) = (def.min_float, def.max_float) {
+        d.push_str(&format!(", {}..{}", min, max));
+    }
+    if let (Some(min), Some(max)) = (def.min_int, def.max_int) {
+        d.push_str(&format!(", {}..{}", min, max));
+    }
+    if let Some(steps) = def.steps.filter(|s| *s > 0) {
+        d.push_str(&format!(", {} steps", steps));
+    }
+    if let Some(len) = def.max_string_len {
+        d.push_str(&format!(", up to {} characters", len));
+    }
+    let long_items: Vec<String> = def.string_enum.iter().flatten()
+        .filter(|i| !i.long_item.is_empty())
+        .map(|i| format!("{} = {}", i.item, i.long_item))
+        .chain(def.float_enum.iter().flatten()
+            .filter(|i| !i.long_item.is_empty())
+            .map(|i| format!("{} = {}", i.item, i.long_item)))
+        .collect();
+    if !long_items.is_empty() {
+        d.push_str(&format!(" ({})", long_items.join(", ")));
+    }
+    d.replace("*/", "*\\/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+{"fullname":"/ch/1/fdr","id":10,"type":"fader level","unit":"dB"}
+{"fullname":"/ch/1/dly","id":11,"type":"linear float","unit":"ms","minfloat":0,"maxfloat":500}
+{"fullname":"/ch/1/dly/on","id":12,"type":"integer","minint":0,"maxint":1}
+{"fullname":"/ch/1/dly/mode","id":13,"type":"string enum","items":[{"item":"MS"},{"item":"M"}]}
+{"fullname":"/ch/2/fdr","id":20,"type":"fader level","unit":"dB"}
+{"fullname":"/ch/2/dly","id":21,"type":"linear float","unit":"ms","minfloat":0,"maxfloat":500}
+{"fullname":"/ch/2/dly/on","id":22,"type":"integer","minint":0,"maxint":1}
+{"fullname":"/ch/2/dly/mode","id":23,"type":"string enum","items":[{"item":"MS"},{"item":"M"}]}
+"#;
+
+    #[test]
+    fn property_with_children_keeps_both() {
+        let schema = Schema::from_jsonl(SCHEMA).unwrap();
+        let doc = json_schema(&schema);
+        let ch = &doc["properties"]["ch"]["properties"]["1"];
+        // the two channels share a type
+        assert_eq!(ch["$ref"], "#/$defs/Ch");
+        let props = &doc["$defs"]["Ch"]["properties"];
+        assert_eq!(props["dly"]["type"], "number");
+        assert_eq!(props["dly_node"]["$ref"], "#/$defs/ChDly");
+        let dly = &doc["$defs"]["ChDly"]["properties"];
+        assert_eq!(dly["on"]["type"], "integer");
+        assert_eq!(dly["mode"]["oneOf"].len(), 2);
+
+        let ts = typescript(&schema);
+        assert!(ts.contains("dly?: number;"));
+        assert!(ts.contains("dly_node?: ChDly;"));
+        assert!(ts.contains("export interface ChDly {"));
+    }
+
+    #[test]
+    fn node_key_does_not_clash() {
+        let schema = Schema::from_jsonl(r#"
+{"fullname":"/a/x","id":1,"type":"integer"}
+{"fullname":"/a/x/y","id":2,"type":"integer"}
+{"fullname":"/a/x_node","id":3,"type":"string"}
+"#).unwrap();
+        let doc = json_schema(&schema);
+        let props = &doc["properties"]["a"]["properties"];
+        assert_eq!(props["x"]["type"], "integer");
+        assert_eq!(props["x_node"]["type"], "string");
+        assert_eq!(props["x_node2"]["properties"]["y"]["type"], "integer");
+    }
+}
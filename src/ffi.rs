@@ -1,7 +1,19 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int, c_float};
+use std::io::ErrorKind;
+use std::os::raw::{c_char, c_int, c_float, c_void};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
-use crate::{WingConsole, NodeType, NodeUnit, WingResponse, console::Meter};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::{WingConsole, ConnectOptions, Discovery, WingNodeDef, WingNodeData, NodeType, NodeUnit, WingResponse, DiscoveryInfo, Error, MeterFrame, MeterValues, console::Meter};
+
+/// Version of the C API, `(major << 16) | minor`. The major version changes when a function
+/// changes or is removed, the minor version when functions are added.
+pub const WING_API_VERSION: u32 = (2 << 16) | 1;
 
 // Opaque type wrappers
 #[repr(C)]
@@ -12,6 +24,8 @@ pub struct WingDiscoveryInfoHandle {
 #[repr(C)]
 pub struct WingConsoleHandle {
     pub console: WingConsole,
+    /// a frame that didn't fit the buffers given to `wing_console_read_meter_frame()`
+    meter_frame: Option<MeterFrame>,
 }
 
 #[repr(C)]
@@ -19,6 +33,12 @@ pub struct ResponseHandle {
     pub response: WingResponse
 }
 
+/// Property names and definitions from the built-in property map
+#[repr(C)]
+pub struct WingDefinitionsHandle {
+    defs: Vec<(String, WingNodeDef)>
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, PartialEq)]
 pub enum ResponseType {
@@ -27,614 +47,926 @@ pub enum ResponseType {
     NodeData = 2,
 }
 
+/// Returned by the functions of the C API that return an int, negative for errors
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorCode {
+    Ok = 0,
+    /// A NULL handle or pointer, a string that isn't UTF-8, or an index or code out of range
+    InvalidArgument = -1,
+    Io = -2,
+    Connection = -3,
+    Discovery = -4,
+    InvalidData = -5,
+    /// The response is not the type the function is for
+    WrongResponseType = -6,
+    Meter = -7,
+    Timeout = -8,
+    /// A bug in the library; the message has the details
+    Internal = -9,
+    /// The buffers passed in are too small; the sizes needed were returned
+    BufferTooSmall = -10,
+}
+
+/// An error on its way to C: the code returned, and the message for `wing_last_error_message()`
+struct FfiError {
+    code: ErrorCode,
+    message: String,
+}
+
+type FfiResult<T> = std::result::Result<T, FfiError>;
+
+impl FfiError {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        FfiError { code, message: message.into() }
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        FfiError::new(ErrorCode::InvalidArgument, message)
+    }
+}
+
+impl From<Error> for FfiError {
+    fn from(e: Error) -> Self {
+        let code = match &e {
+            Error::Io(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => ErrorCode::Timeout,
+            Error::Timeout(_) => ErrorCode::Timeout,
+            Error::Io(_) => ErrorCode::Io,
+            Error::ConnectionError => ErrorCode::Connection,
+            Error::DiscoveryError => ErrorCode::Discovery,
+            Error::InvalidData | Error::SchemaError(_) | Error::OscError(_) => ErrorCode::InvalidData,
+            Error::PatternError(_) | Error::IndexError(_) | Error::ValueError(_) => ErrorCode::InvalidArgument,
+            Error::MeterError(_) | Error::MeterPacketError(_) => ErrorCode::Meter,
+        };
+        FfiError::new(code, e.to_string())
+    }
+}
+
+thread_local! {
+    /// The error of the last call that failed on this thread
+    static LAST_ERROR: RefCell<(ErrorCode, CString)> = RefCell::new((ErrorCode::Ok, CString::default()));
+}
+
+fn set_last_error(e: FfiError) -> ErrorCode {
+    // a message can't have a NUL in the middle in C
+    let message = CString::new(e.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = (e.code, message));
+    e.code
+}
+
+/// Runs the body of an exported function. An error, or a panic, which must not unwind into C, is
+/// kept for `wing_last_error_message()` and `on_error` makes the return value from its code.
+fn guard_with<T>(on_error: impl FnOnce(ErrorCode) -> T, f: impl FnOnce() -> FfiResult<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => on_error(set_last_error(e)),
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            on_error(set_last_error(FfiError::new(ErrorCode::Internal, format!("panic in libwing: {}", message))))
+        }
+    }
+}
+
+/// `guard_with()` for functions that return the same value on every error
+fn guard<T>(on_error: T, f: impl FnOnce() -> FfiResult<T>) -> T {
+    guard_with(|_| on_error, f)
+}
+
+/// `guard_with()` for functions that return `WING_OK` or an error code
+fn status(f: impl FnOnce() -> FfiResult<()>) -> c_int {
+    guard_with(|code| code as c_int, || f().map(|_| ErrorCode::Ok as c_int))
+}
+
+/// `guard_with()` for functions that return 1 and set `*ret` if there is a value, 0 if there is
+/// none, or an error code
+fn optional<T>(ret: *mut T, f: impl FnOnce() -> FfiResult<Option<T>>) -> c_int {
+    guard_with(|code| code as c_int, || {
+        let ret = out(ret)?;
+        match f()? {
+            Some(value) => {
+                *ret = value;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    })
+}
+
+fn console_handle<'a>(handle: *mut WingConsoleHandle) -> FfiResult<&'a mut WingConsoleHandle> {
+    unsafe { handle.as_mut() }.ok_or_else(|| FfiError::invalid("console handle is NULL"))
+}
+
+fn console<'a>(handle: *mut WingConsoleHandle) -> FfiResult<&'a mut WingConsole> {
+    Ok(&mut console_handle(handle)?.console)
+}
+
+fn response<'a>(handle: *const ResponseHandle) -> FfiResult<&'a WingResponse> {
+    unsafe { handle.as_ref() }.map(|h| &h.response).ok_or_else(|| FfiError::invalid("response handle is NULL"))
+}
+
+fn node_def<'a>(handle: *const ResponseHandle) -> FfiResult<&'a WingNodeDef> {
+    match response(handle)? {
+        WingResponse::NodeDef(def) => Ok(def),
+        _ => Err(FfiError::new(ErrorCode::WrongResponseType, "response is not a node definition")),
+    }
+}
+
+fn node_data<'a>(handle: *const ResponseHandle) -> FfiResult<(i32, &'a WingNodeData)> {
+    match response(handle)? {
+        WingResponse::NodeData(id, data) => Ok((*id, data)),
+        _ => Err(FfiError::new(ErrorCode::WrongResponseType, "response is not node data")),
+    }
+}
+
+fn discovered<'a>(handle: *const WingDiscoveryInfoHandle, index: c_int) -> FfiResult<&'a DiscoveryInfo> {
+    let info = &unsafe { handle.as_ref() }.ok_or_else(|| FfiError::invalid("discovery handle is NULL"))?.info;
+    usize::try_from(index).ok().and_then(|i| info.get(i))
+        .ok_or_else(|| FfiError::invalid(format!("discovery index {} out of range, found {} consoles", index, info.len())))
+}
+
+fn definitions<'a>(handle: *const WingDefinitionsHandle) -> FfiResult<&'a [(String, WingNodeDef)]> {
+    unsafe { handle.as_ref() }.map(|h| &h.defs[..]).ok_or_else(|| FfiError::invalid("definitions handle is NULL"))
+}
+
+fn definition<'a>(handle: *const WingDefinitionsHandle, index: c_int) -> FfiResult<&'a (String, WingNodeDef)> {
+    let defs = definitions(handle)?;
+    usize::try_from(index).ok().and_then(|i| defs.get(i))
+        .ok_or_else(|| FfiError::invalid(format!("definition index {} out of range, there are {}", index, defs.len())))
+}
+
+fn out<'a, T>(ret: *mut T) -> FfiResult<&'a mut T> {
+    unsafe { ret.as_mut() }.ok_or_else(|| FfiError::invalid("output pointer is NULL"))
+}
+
+fn c_str<'a>(s: *const c_char, what: &str) -> FfiResult<&'a str> {
+    if s.is_null() {
+        return Err(FfiError::invalid(format!("{} is NULL", what)));
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().map_err(|_| FfiError::invalid(format!("{} is not UTF-8", what)))
+}
+
+/// A string for C, to be freed with `wing_string_destroy()`
+fn to_c_string(s: &str) -> FfiResult<*const c_char> {
+    CString::new(s).map(|s| s.into_raw() as *const c_char)
+        .map_err(|_| FfiError::new(ErrorCode::InvalidData, format!("string contains a NUL: {:?}", s)))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_api_version() -> u32 {
+    WING_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn wing_last_error() -> ErrorCode {
+    LAST_ERROR.with(|last| last.borrow().0)
+}
+
+#[no_mangle]
+pub extern "C" fn wing_last_error_message() -> *const c_char {
+    // stays valid until the next call that fails on this thread
+    LAST_ERROR.with(|last| last.borrow().1.as_ptr())
+}
+
 #[no_mangle]
 pub extern "C" fn wing_string_destroy(handle: *const c_char) {
-    unsafe {
-        if handle.is_null() { return; }
-        drop(CString::from_raw(handle as *mut c_char));
-    }
+    guard((), || {
+        if !handle.is_null() {
+            drop(unsafe { CString::from_raw(handle as *mut c_char) });
+        }
+        Ok(())
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_scan(stop_on_first: c_int) -> *mut WingDiscoveryInfoHandle {
-    let results = WingConsole::scan(stop_on_first != 0);
-    if let Ok(results) = results {
-        Box::into_raw(Box::new(WingDiscoveryInfoHandle { info: results }))
-    } else {
-        ptr::null_mut()
-    }
+    guard(ptr::null_mut(), || {
+        let results = WingConsole::scan(stop_on_first != 0)?;
+        Ok(Box::into_raw(Box::new(WingDiscoveryInfoHandle { info: results })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_discover_scan_target(ip: *const c_char, port: u16, timeout_ms: c_int) -> *mut WingDiscoveryInfoHandle {
+    guard(ptr::null_mut(), || {
+        let ip = c_str(ip, "ip")?.parse().map_err(|_| FfiError::invalid("ip is not an IP address"))?;
+        let results = Discovery::new()
+            .target(ip)
+            .port(port)
+            .broadcast(false)
+            .timeout(Duration::from_millis(timeout_ms.max(0) as u64))
+            .stop_on_first(true)
+            .scan()?;
+        Ok(Box::into_raw(Box::new(WingDiscoveryInfoHandle { info: results })))
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_destroy(handle: *mut WingDiscoveryInfoHandle) {
-    unsafe {
-        drop(Box::from_raw(handle));
-    }
+    guard((), || {
+        if !handle.is_null() {
+            drop(unsafe { Box::from_raw(handle) });
+        }
+        Ok(())
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_count(handle: *const WingDiscoveryInfoHandle) -> c_int {
-    unsafe {
-        (*handle).info.len() as c_int
-    }
+    guard_with(|code| code as c_int, || {
+        let handle = unsafe { handle.as_ref() }.ok_or_else(|| FfiError::invalid("discovery handle is NULL"))?;
+        Ok(handle.info.len() as c_int)
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_get_ip(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
-    unsafe {
-        let info = &(&(*handle)).info[index as usize];
-        CString::new(&info.ip[..]).unwrap().into_raw()
-    }
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.ip))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_get_name(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
-    unsafe {
-        let info = &(&(*handle)).info[index as usize];
-        CString::new(&info.name[..]).unwrap().into_raw()
-    }
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.name))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_get_model(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
-    unsafe {
-        let info = &(&(*handle)).info[index as usize];
-        CString::new(&info.model[..]).unwrap().into_raw()
-    }
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.model))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_get_serial(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
-    unsafe {
-        let info = &(&(*handle)).info[index as usize];
-        CString::new(&info.serial[..]).unwrap().into_raw()
-    }
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.serial))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_discover_get_firmware(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
-    unsafe {
-        let info = &(&(*handle)).info[index as usize];
-        CString::new(&info.firmware[..]).unwrap().into_raw()
-    }
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.firmware))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_discover_get_interface(handle: *const WingDiscoveryInfoHandle, index: c_int) -> *const c_char {
+    guard(ptr::null(), || to_c_string(&discovered(handle, index)?.interface))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_connect(ip: *const c_char) -> *mut WingConsoleHandle {
-    if ip.is_null() {
-        match WingConsole::connect(None) {
-            Ok(console) => Box::into_raw(Box::new(WingConsoleHandle { console })),
-            Err(_) => ptr::null_mut()
-        }
-    } else if let Ok(ip) = unsafe { CStr::from_ptr(ip).to_str() } {
-        match WingConsole::connect(Some(ip)) {
-            Ok(console) => Box::into_raw(Box::new(WingConsoleHandle { console })),
-            Err(_) => ptr::null_mut()
-        }
-    } else {
-        ptr::null_mut()
-    }
+    guard(ptr::null_mut(), || {
+        let ip = if ip.is_null() { None } else { Some(c_str(ip, "ip")?) };
+        let console = WingConsole::connect(ip)?;
+        Ok(Box::into_raw(Box::new(WingConsoleHandle { console, meter_frame: None })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_connect_port(ip: *const c_char, port: u16) -> *mut WingConsoleHandle {
+    guard(ptr::null_mut(), || {
+        let console = ConnectOptions::new().host(c_str(ip, "ip")?).port(port).connect()?;
+        Ok(Box::into_raw(Box::new(WingConsoleHandle { console, meter_frame: None })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_connect_to(name_or_serial: *const c_char, timeout_ms: c_int) -> *mut WingConsoleHandle {
+    guard(ptr::null_mut(), || {
+        let name = c_str(name_or_serial, "name_or_serial")?;
+        let console = WingConsole::connect_to(name, Duration::from_millis(timeout_ms.max(0) as u64))?;
+        Ok(Box::into_raw(Box::new(WingConsoleHandle { console, meter_frame: None })))
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_destroy(handle: *mut WingConsoleHandle) {
-    unsafe {
-        drop(Box::from_raw(handle));
-    }
+    guard((), || {
+        if handle.is_null() {
+            return Ok(());
+        }
+        let events = EVENTS.lock().unwrap().remove(&(handle as usize));
+        if let Some(events) = events {
+            events.stop.store(true, Ordering::Relaxed);
+            // ends a read on the event thread; callbacks may still use the handle until the
+            // threads have ended, so it is freed after them
+            console(handle)?.shutdown();
+            events.join();
+        }
+        drop(unsafe { Box::from_raw(handle) });
+        Ok(())
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_read(handle: *mut WingConsoleHandle) -> *mut ResponseHandle {
-    unsafe {
-        if let Ok(response) = (*handle).console.read() {
-            Box::into_raw(Box::new(ResponseHandle { response }))
-        } else {
-            ptr::null_mut()
+    guard(ptr::null_mut(), || {
+        let response = console(handle)?.read()?;
+        Ok(Box::into_raw(Box::new(ResponseHandle { response })))
+    })
+}
+
+/// Called with each response when set with `wing_console_set_response_callback()`. The response
+/// is only valid during the call, and must not be destroyed.
+pub type WingResponseCallback = extern "C" fn(user_data: *mut c_void, response: *const ResponseHandle);
+
+/// Called with each meter frame when set with `wing_console_set_meter_callback()`, laid out like
+/// the frames of `wing_console_read_meter_frame()`. The sources and values are only valid during
+/// the call.
+pub type WingMeterCallback = extern "C" fn(user_data: *mut c_void, id: u16, sources: *const WingMeterSource, source_count: usize,
+                                           values: *const c_float, value_count: usize);
+
+/// The pointer given with a callback, handed back to it on the event thread
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+
+/// How many responses are kept for `wing_console_poll()` before the oldest are dropped
+const RESPONSE_QUEUE_SIZE: usize = 1024;
+
+#[derive(Default)]
+struct Queued {
+    responses: VecDeque<WingResponse>,
+    /// responses dropped from a full queue since `wing_console_dropped_responses()` was called
+    dropped: usize,
+    /// set when the reader thread has ended
+    closed: bool,
+}
+
+/// The responses read while no response callback is set, for `wing_console_poll()`
+#[derive(Default)]
+struct ResponseQueue {
+    queued: Mutex<Queued>,
+    ready: Condvar,
+}
+
+impl ResponseQueue {
+    fn push(&self, response: WingResponse) {
+        let mut queued = self.queued.lock().unwrap();
+        if queued.responses.len() >= RESPONSE_QUEUE_SIZE {
+            queued.responses.pop_front();
+            queued.dropped += 1;
         }
+        queued.responses.push_back(response);
+        self.ready.notify_one();
     }
+
+    fn close(&self) {
+        self.queued.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+
+    /// Waits up to `timeout`, or forever with `None`, for the next response
+    fn pop(&self, timeout: Option<Duration>) -> FfiResult<WingResponse> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut queued = self.queued.lock().unwrap();
+        loop {
+            if let Some(response) = queued.responses.pop_front() {
+                return Ok(response);
+            }
+            if queued.closed {
+                return Err(FfiError::new(ErrorCode::Connection, "connection to the console was lost"));
+            }
+            queued = match deadline {
+                None => self.ready.wait(queued).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(FfiError::new(ErrorCode::Timeout, "no response within the timeout"));
+                    }
+                    self.ready.wait_timeout(queued, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+}
+
+/// The threads that read a console for `wing_console_poll()` and the callbacks. Each callback
+/// has a lock of its own, held while it runs, so the two can run at the same time.
+#[derive(Default)]
+struct Events {
+    stop: Arc<AtomicBool>,
+    response: Arc<Mutex<Option<(WingResponseCallback, UserData)>>>,
+    meter: Arc<Mutex<Option<(WingMeterCallback, UserData)>>>,
+    queue: Arc<ResponseQueue>,
+    reader: Option<JoinHandle<()>>,
+    meter_reader: Option<JoinHandle<()>>,
+}
+
+impl Events {
+    /// Starts the thread that reads responses, for `wing_console_poll()` and the response callback
+    fn start_reader(&mut self, console: &WingConsole) {
+        if self.reader.is_some() {
+            return;
+        }
+        let mut wing = console.clone();
+        let stop = self.stop.clone();
+        let callback = self.response.clone();
+        let queue = self.queue.clone();
+        self.reader = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                // fails once the connection is shut down by wing_console_destroy()
+                let Ok(response) = wing.read() else { break };
+                let callback = callback.lock().unwrap();
+                match &*callback {
+                    Some((callback, user_data)) => {
+                        let handle = ResponseHandle { response };
+                        callback(user_data.0, &handle);
+                    }
+                    None => queue.push(response),
+                }
+            }
+            queue.close();
+        }));
+    }
+
+    /// Starts the thread that reads meter frames for the meter callback
+    fn start_meters(&mut self, console: &WingConsole) {
+        if self.meter_reader.is_some() {
+            return;
+        }
+        let mut wing = console.clone();
+        let stop = self.stop.clone();
+        let callback = self.meter.clone();
+        self.meter_reader = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                if !wing.has_meters() {
+                    std::thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+                // a short timeout, so the thread notices when it has to stop
+                let frame = match wing.try_read_meters(Some(Duration::from_millis(100))) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => continue,
+                    // a bad packet; keep going, the console connection is still fine
+                    Err(_) => {
+                        std::thread::sleep(Duration::from_millis(100));
+                        continue;
+                    }
+                };
+                let (sources, values) = meter_frame(&frame);
+                let callback = callback.lock().unwrap();
+                if let Some((callback, user_data)) = &*callback {
+                    callback(user_data.0, frame.id, sources.as_ptr(), sources.len(), values.as_ptr(), values.len());
+                }
+            }
+        }));
+    }
+
+    fn join(mut self) {
+        for thread in [self.reader.take(), self.meter_reader.take()].into_iter().flatten() {
+            let _ = thread.join();
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// The event threads of each console handle, by handle address
+    static ref EVENTS: Mutex<HashMap<usize, Events>> = Mutex::new(HashMap::new());
+}
+
+/// Runs `f` on the events of the handle
+fn with_events<T>(handle: *mut WingConsoleHandle, f: impl FnOnce(&mut Events, &WingConsole) -> T) -> FfiResult<T> {
+    let console = console(handle)?;
+    let mut events = EVENTS.lock().unwrap();
+    Ok(f(events.entry(handle as usize).or_default(), console))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_poll(handle: *mut WingConsoleHandle, timeout_ms: c_int) -> *mut ResponseHandle {
+    guard(ptr::null_mut(), || {
+        let queue = with_events(handle, |events, console| {
+            events.start_reader(console);
+            events.queue.clone()
+        })?;
+        let timeout = (timeout_ms >= 0).then(|| Duration::from_millis(timeout_ms as u64));
+        let response = queue.pop(timeout)?;
+        Ok(Box::into_raw(Box::new(ResponseHandle { response })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_dropped_responses(handle: *mut WingConsoleHandle) -> usize {
+    guard(0, || {
+        let queue = with_events(handle, |events, _| events.queue.clone())?;
+        let mut queued = queue.queued.lock().unwrap();
+        Ok(std::mem::take(&mut queued.dropped))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_set_response_callback(handle: *mut WingConsoleHandle, callback: Option<WingResponseCallback>, user_data: *mut c_void) -> c_int {
+    status(|| {
+        let slot = with_events(handle, |events, console| {
+            events.start_reader(console);
+            events.response.clone()
+        })?;
+        // waits for a callback that is running to return
+        *slot.lock().unwrap() = callback.map(|c| (c, UserData(user_data)));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_set_meter_callback(handle: *mut WingConsoleHandle, callback: Option<WingMeterCallback>, user_data: *mut c_void) -> c_int {
+    status(|| {
+        let slot = with_events(handle, |events, console| {
+            events.start_meters(console);
+            events.meter.clone()
+        })?;
+        *slot.lock().unwrap() = callback.map(|c| (c, UserData(user_data)));
+        Ok(())
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_response_destroy(handle: *mut ResponseHandle) {
-    unsafe {
-        drop(Box::from_raw(handle));
-    }
+    guard((), || {
+        if !handle.is_null() {
+            drop(unsafe { Box::from_raw(handle) });
+        }
+        Ok(())
+    })
 }
 
 
 #[no_mangle]
 pub extern "C" fn wing_console_set_string(handle: *mut WingConsoleHandle, id: i32, value: *const c_char) -> c_int {
-    unsafe {
-        if let Ok(value) = CStr::from_ptr(value).to_str() {
-            if (*handle).console.set_string(id, value).is_ok() {
-                0
-            } else {
-                -1
-            }
-        } else {
-            -1
-        }
-    }
+    status(|| Ok(console(handle)?.set_string(id, c_str(value, "value")?)?))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_set_float(handle: *mut WingConsoleHandle, id: i32, value: c_float) -> c_int {
-    unsafe {
-        if (*handle).console.set_float(id, value).is_ok() {
-            0
-        } else {
-            -1
-        }
-    }
+    status(|| Ok(console(handle)?.set_float(id, value)?))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_set_int(handle: *mut WingConsoleHandle, id: i32, value: c_int) -> c_int {
-    unsafe {
-        if (*handle).console.set_int(id, value).is_ok() {
-            0
-        } else {
-            -1
-        }
-    }
+    status(|| Ok(console(handle)?.set_int(id, value)?))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_request_node_definition(handle: *mut WingConsoleHandle, id: i32) -> c_int {
-    unsafe {
-        if (*handle).console.request_node_definition(id).is_ok() {
-            0
-        } else {
-            -1
-        }
-    }
+    status(|| Ok(console(handle)?.request_node_definition(id)?))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_request_node_data(handle: *mut WingConsoleHandle, id: i32) -> c_int {
-    unsafe {
-        if (*handle).console.request_node_data(id).is_ok() {
-            0
-        } else {
-            -1
-        }
-    }
+    status(|| Ok(console(handle)?.request_node_data(id)?))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_response_get_type(handle: *const ResponseHandle) -> ResponseType {
-    match unsafe { &(*handle).response } {
+    guard(ResponseType::End, || Ok(match response(handle)? {
         WingResponse::RequestEnd => ResponseType::End,
         WingResponse::NodeDef(_) => ResponseType::NodeDefinition,
         WingResponse::NodeData(_, _) => ResponseType::NodeData,
-    }
+    }))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_get_id(handle: *const ResponseHandle) -> i32 {
-    unsafe {
-        if let WingResponse::NodeData(id, _) = &(*handle).response {
-            *id
-        } else {
-            0
-        }
-    }
+    guard(0, || Ok(node_data(handle)?.0))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_get_string(handle: *const ResponseHandle) -> *const c_char {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            let s = data.get_string();
-            CString::new(&s[..]).unwrap().into_raw()
-        } else {
-            ptr::null()
-        }
-    }
+    guard(ptr::null(), || to_c_string(&node_data(handle)?.1.get_string()))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_get_float(handle: *const ResponseHandle) -> c_float {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            data.get_float()
-        } else {
-            0.0
-        }
-    }
+    guard(0.0, || Ok(node_data(handle)?.1.get_float()))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_get_int(handle: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            data.get_int()
-        } else {
-            0
-        }
-    }
+    guard(0, || Ok(node_data(handle)?.1.get_int()))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_has_string(handle: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            if data.has_string() { 1 } else { 0 }
-        } else {
-            0
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_data(handle)?.1.has_string() as c_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_has_float(handle: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            if data.has_float() { 1 } else { 0 }
-        } else {
-            0
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_data(handle)?.1.has_float() as c_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_data_has_int(handle: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeData(_, data) = &(*handle).response {
-            if data.has_int() { 1 } else { 0 }
-        } else {
-            0
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_data(handle)?.1.has_int() as c_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_name_to_id(name: *const c_char, out_id: *mut i32) -> c_int {
-    unsafe {
-        if let Ok(name_str) = CStr::from_ptr(name).to_str() {
-            if let Some(id) = WingConsole::name_to_id(name_str) {
-                *out_id = id;
-                1
-            } else {
-                0
-            }
-        } else {
-            0
+    optional(out_id, || Ok(WingConsole::name_to_id(c_str(name, "name")?)))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_name_to_def(name: *const c_char, ret: *mut *mut ResponseHandle) -> c_int {
+    optional(ret, || {
+        let def = WingConsole::name_to_def(c_str(name, "name")?);
+        Ok(def.map(|def| Box::into_raw(Box::new(ResponseHandle { response: WingResponse::NodeDef(def.clone()) }))))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_id_to_defs(id: i32) -> *mut WingDefinitionsHandle {
+    guard(ptr::null_mut(), || {
+        let defs = WingConsole::id_to_defs(id).unwrap_or_default();
+        Ok(Box::into_raw(Box::new(WingDefinitionsHandle { defs })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_find_names(pattern: *const c_char) -> *mut WingDefinitionsHandle {
+    guard(ptr::null_mut(), || {
+        let defs = WingConsole::find_names(c_str(pattern, "pattern")?)?;
+        Ok(Box::into_raw(Box::new(WingDefinitionsHandle { defs })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_definitions_destroy(handle: *mut WingDefinitionsHandle) {
+    guard((), || {
+        if !handle.is_null() {
+            drop(unsafe { Box::from_raw(handle) });
         }
-    }
+        Ok(())
+    })
 }
 
 #[no_mangle]
-pub extern "C" fn wing_node_definition_get_id(def: *const ResponseHandle) -> i32 {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            def.id
-        } else {
-            panic!("Invalid response type");
+pub extern "C" fn wing_definitions_count(handle: *const WingDefinitionsHandle) -> c_int {
+    guard_with(|code| code as c_int, || Ok(definitions(handle)?.len() as c_int))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_definitions_get_name(handle: *const WingDefinitionsHandle, index: c_int) -> *const c_char {
+    guard(ptr::null(), || to_c_string(&definition(handle, index)?.0))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_definitions_get_definition(handle: *const WingDefinitionsHandle, index: c_int) -> *mut ResponseHandle {
+    guard(ptr::null_mut(), || {
+        let def = definition(handle, index)?.1.clone();
+        Ok(Box::into_raw(Box::new(ResponseHandle { response: WingResponse::NodeDef(def) })))
+    })
+}
+
+/// Like `wingprop -j`: an array of the definitions as JSON, each with its `fullname`
+#[no_mangle]
+pub extern "C" fn wing_definitions_to_json(handle: *const WingDefinitionsHandle) -> *const c_char {
+    guard(ptr::null(), || {
+        let mut json = jzon::array![];
+        for (name, def) in definitions(handle)? {
+            let mut def_json = def.to_json();
+            def_json.insert("fullname", name.as_str()).map_err(|e| FfiError::new(ErrorCode::Internal, e.to_string()))?;
+            json.push(def_json).map_err(|e| FfiError::new(ErrorCode::Internal, e.to_string()))?;
         }
-    }
+        to_c_string(&json.dump())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_node_definition_to_json(def: *const ResponseHandle) -> *const c_char {
+    guard(ptr::null(), || to_c_string(&node_def(def)?.to_json().dump()))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_node_definition_to_description(def: *const ResponseHandle) -> *const c_char {
+    guard(ptr::null(), || to_c_string(&node_def(def)?.to_description()))
+}
+
+#[no_mangle]
+pub extern "C" fn wing_node_definition_get_id(def: *const ResponseHandle) -> i32 {
+    guard(0, || Ok(node_def(def)?.id))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_parent_id(def: *const ResponseHandle) -> i32 {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            def.parent_id
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(0, || Ok(node_def(def)?.parent_id))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_index(def: *const ResponseHandle) -> u16 {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            def.index
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(0, || Ok(node_def(def)?.index))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_type(def: *const ResponseHandle) -> NodeType {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            def.node_type
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(NodeType::Node, || Ok(node_def(def)?.node_type))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_unit(def: *const ResponseHandle) -> NodeUnit {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            def.unit
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(NodeUnit::None, || Ok(node_def(def)?.unit))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_name(def: *const ResponseHandle) -> *const c_char {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            CString::new(&def.name[..]).unwrap().into_raw()
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(ptr::null(), || to_c_string(&node_def(def)?.name))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_long_name(def: *const ResponseHandle) -> *const c_char {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            CString::new(&def.long_name[..]).unwrap().into_raw()
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard(ptr::null(), || to_c_string(&node_def(def)?.long_name))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_is_read_only(def: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if def.read_only { 1 } else { 0 }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_def(def)?.read_only as c_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_min_float(def: *const ResponseHandle, ret: *mut c_float) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(min_float) = def.min_float {
-                *ret = min_float;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.min_float))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_max_float(def: *const ResponseHandle, ret: *mut c_float) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(max_float) = def.max_float {
-                *ret = max_float;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.max_float))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_steps(def: *const ResponseHandle, ret: *mut c_int) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(steps) = def.steps {
-                *ret = steps;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.steps))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_min_int(def: *const ResponseHandle, ret: *mut c_int) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(min_int) = def.min_int {
-                *ret = min_int;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.min_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_max_int(def: *const ResponseHandle, ret: *mut c_int) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(max_int) = def.max_int {
-                *ret = max_int;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.max_int))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_max_string_len(def: *const ResponseHandle, ret: *mut c_int) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(max_string_len) = def.max_string_len {
-                *ret = max_string_len as i32;
-                1
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || Ok(node_def(def)?.max_string_len.map(|len| len as c_int)))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_string_enum_count(def: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(string_enum) = &def.string_enum {
-                string_enum.len() as c_int
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_def(def)?.string_enum.as_ref().map_or(0, |e| e.len() as c_int)))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_float_enum_count(def: *const ResponseHandle) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(float_enum) = &def.float_enum {
-                float_enum.len() as c_int
-            } else {
-                0
-            }
-        } else {
-            0
-        }
-    }
+    guard_with(|code| code as c_int, || Ok(node_def(def)?.float_enum.as_ref().map_or(0, |e| e.len() as c_int)))
 }
 
 #[no_mangle]
 pub extern "C" fn wing_node_definition_get_float_enum_item(def: *const ResponseHandle, index: c_int, ret: *mut c_float) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(item) = &def.float_enum {
-                if let Some(item) = item.get(index as usize) {
-                    *ret = item.item;
-                    1
-                } else {
-                    0
-                }
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+    optional(ret, || {
+        let items = node_def(def)?.float_enum.as_deref().unwrap_or_default();
+        Ok(usize::try_from(index).ok().and_then(|i| items.get(i)).map(|item| item.item))
+    })
 }
 
 #[no_mangle]
-pub extern "C" fn wing_node_definition_get_float_enum_long_item(def: *const ResponseHandle, index: c_int, ret: *mut *mut c_char) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(item) = &def.float_enum {
-                if let Some(item) = item.get(index as usize) {
-                    *ret = CString::new(&item.long_item[..]).unwrap().into_raw();
-                    1
-                } else {
-                    0
-                }
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+pub extern "C" fn wing_node_definition_get_float_enum_long_item(def: *const ResponseHandle, index: c_int, ret: *mut *const c_char) -> c_int {
+    optional(ret, || {
+        let items = node_def(def)?.float_enum.as_deref().unwrap_or_default();
+        usize::try_from(index).ok().and_then(|i| items.get(i)).map(|item| to_c_string(&item.long_item)).transpose()
+    })
 }
 
 #[no_mangle]
-pub extern "C" fn wing_node_definition_get_string_enum_item(def: *const ResponseHandle, index: c_int, ret: *mut *mut c_char) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(item) = &def.string_enum {
-                if let Some(item) = item.get(index as usize) {
-                    *ret = CString::new(&item.item[..]).unwrap().into_raw();
-                    1
-                } else {
-                    0
-                }
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+pub extern "C" fn wing_node_definition_get_string_enum_item(def: *const ResponseHandle, index: c_int, ret: *mut *const c_char) -> c_int {
+    optional(ret, || {
+        let items = node_def(def)?.string_enum.as_deref().unwrap_or_default();
+        usize::try_from(index).ok().and_then(|i| items.get(i)).map(|item| to_c_string(&item.item)).transpose()
+    })
 }
+
 #[no_mangle]
-pub extern "C" fn wing_node_definition_get_string_enum_long_item(def: *const ResponseHandle, index: c_int, ret: *mut *mut c_char) -> c_int {
-    unsafe {
-        if let WingResponse::NodeDef(def) = &(*def).response {
-            if let Some(item) = &def.string_enum {
-                if let Some(item) = item.get(index as usize) {
-                    *ret = CString::new(&item.long_item[..]).unwrap().into_raw();
-                    1
-                } else {
-                    0
-                }
-            } else {
-                0
-            }
-        } else {
-            panic!("Invalid response type");
-        }
-    }
+pub extern "C" fn wing_node_definition_get_string_enum_long_item(def: *const ResponseHandle, index: c_int, ret: *mut *const c_char) -> c_int {
+    optional(ret, || {
+        let items = node_def(def)?.string_enum.as_deref().unwrap_or_default();
+        usize::try_from(index).ok().and_then(|i| items.get(i)).map(|item| to_c_string(&item.long_item)).transpose()
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn wing_console_request_meter(handle: *mut WingConsoleHandle, meters: *const u16, meters_count: usize) -> u16 {
-    unsafe {
-        let meters : Vec<Meter> = std::slice::from_raw_parts(meters, meters_count).iter().map(|m|
-            match m & 0xff00 {
-                0xa000 => Meter::Channel((m & 0xff) as u8),
-                0xa100 => Meter::Aux((m & 0xff) as u8),
-                0xa200 => Meter::Bus((m & 0xff) as u8),
-                0xa300 => Meter::Main((m & 0xff) as u8),
-                0xa400 => Meter::Matrix((m & 0xff) as u8),
-                0xa500 => Meter::Dca((m & 0xff) as u8),
-                0xa600 => Meter::Fx((m & 0xff) as u8),
-                0xa700 => Meter::Source((m & 0xff) as u8),
-                0xa800 => Meter::Output((m & 0xff) as u8),
-                0xa900 => Meter::Monitor,
-                0xaa00 => Meter::Rta,
-                0xab00 => Meter::Channel2((m & 0xff) as u8),
-                0xac00 => Meter::Aux2((m & 0xff) as u8),
-                0xad00 => Meter::Bus2((m & 0xff) as u8),
-                0xae00 => Meter::Main2((m & 0xff) as u8),
-                0xaf00 => Meter::Matrix2((m & 0xff) as u8),
-                _ => panic!("Invalid meter number")
-            }
-        ).collect();
-        (*handle).console.request_meter(&meters).unwrap_or_default()
+    // meter ids start at 1, so 0 is the error
+    guard(0, || {
+        let codes = match meters_count {
+            0 => &[][..],
+            _ if meters.is_null() => return Err(FfiError::invalid("meters is NULL")),
+            _ => unsafe { std::slice::from_raw_parts(meters, meters_count) },
+        };
+        let meters = codes.iter()
+            .map(|code| Meter::from_code(*code).ok_or_else(|| FfiError::invalid(format!("unknown meter code 0x{:04x}", code))))
+            .collect::<FfiResult<Vec<Meter>>>()?;
+        Ok(console(handle)?.request_meter(&meters)?)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_cancel_meter(handle: *mut WingConsoleHandle, id: u16) -> c_int {
+    status(|| Ok(console(handle)?.cancel_meter(id)?))
+}
+
+/// One meter of a frame from `wing_console_read_meter_frame()`, and where its values are
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct WingMeterSource {
+    /// The meter, like `METER_ID()` in the C API
+    pub meter: u16,
+    /// Number of values of the meter
+    pub count: u16,
+    /// Index of the meter's first value in the values of the frame
+    pub offset: u32,
+}
+
+/// The values of a meter for C: levels in dB, or 0 and 1 for the gate and dynamics state
+fn meter_values(values: &MeterValues) -> Vec<f32> {
+    match values {
+        MeterValues::StripState(s) => vec![s.gate_active as u8 as f32, s.dyn_active as u8 as f32],
+        values => values.levels(),
+    }
+}
+
+/// The sources and values of a frame for C, one source per meter, with the values in order
+fn meter_frame(frame: &MeterFrame) -> (Vec<WingMeterSource>, Vec<f32>) {
+    let mut sources = Vec::with_capacity(frame.meters.len());
+    let mut values = Vec::new();
+    for (meter, levels) in &frame.meters {
+        let levels = meter_values(levels);
+        sources.push(WingMeterSource { meter: meter.code(), count: levels.len() as u16, offset: values.len() as u32 });
+        values.extend(levels);
+    }
+    (sources, values)
+}
+
+fn read_meter_frame(console: &mut WingConsole, timeout_ms: c_int) -> FfiResult<MeterFrame> {
+    if timeout_ms < 0 {
+        return Ok(console.read_meters()?);
+    }
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+    loop {
+        if let Some(frame) = console.try_read_meters(Some(deadline.saturating_duration_since(Instant::now())))? {
+            return Ok(frame);
+        }
+        if Instant::now() >= deadline {
+            return Err(FfiError::new(ErrorCode::Timeout, "no meters within the timeout"));
+        }
     }
 }
 
 #[no_mangle]
-pub extern "C" fn wing_console_read_meter(handle: *mut WingConsoleHandle, ret_id: *mut u16, ret_data: *mut i16) -> c_int {
-    unsafe {
-        if let Ok((id, data)) = (*handle).console.read_meters() {
-            *ret_id = id;
-            ptr::copy_nonoverlapping(data.as_ptr(), ret_data, data.len());
-            data.len() as c_int
-        } else {
-            -1
+pub extern "C" fn wing_meter_value_count(meter: u16) -> c_int {
+    guard_with(|code| code as c_int, || {
+        let meter = Meter::from_code(meter).ok_or_else(|| FfiError::invalid(format!("unknown meter code 0x{:04x}", meter)))?;
+        Ok(meter.value_count() as c_int)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_meter_size(handle: *mut WingConsoleHandle, id: u16, out_sources: *mut usize, out_values: *mut usize) -> c_int {
+    status(|| {
+        let (out_sources, out_values) = (out(out_sources)?, out(out_values)?);
+        let meters = console(handle)?.meter_subscription(id)
+            .ok_or_else(|| FfiError::new(ErrorCode::Meter, format!("no meter subscription with id {}", id)))?;
+        *out_sources = meters.len();
+        *out_values = meters.iter().map(Meter::value_count).sum();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wing_console_read_meter_frame(handle: *mut WingConsoleHandle, timeout_ms: c_int, out_id: *mut u16,
+                                                sources: *mut WingMeterSource, sources_capacity: usize, out_sources: *mut usize,
+                                                values: *mut c_float, values_capacity: usize, out_values: *mut usize) -> c_int {
+    status(|| {
+        let (out_id, out_sources, out_values) = (out(out_id)?, out(out_sources)?, out(out_values)?);
+        if (sources.is_null() && sources_capacity > 0) || (values.is_null() && values_capacity > 0) {
+            return Err(FfiError::invalid("sources or values is NULL"));
         }
+        let handle = console_handle(handle)?;
+        let frame = match handle.meter_frame.take() {
+            Some(frame) => frame,
+            None => read_meter_frame(&mut handle.console, timeout_ms)?,
+        };
 
-    }
+        let (frame_sources, frame_values) = meter_frame(&frame);
+        *out_id = frame.id;
+        *out_sources = frame_sources.len();
+        *out_values = frame_values.len();
+        if frame_sources.len() > sources_capacity || frame_values.len() > values_capacity {
+            // kept for the next call, with bigger buffers
+            handle.meter_frame = Some(frame);
+            return Err(FfiError::new(ErrorCode::BufferTooSmall, format!(
+                "meter frame needs {} sources and {} values, buffers have room for {} and {}",
+                frame_sources.len(), frame_values.len(), sources_capacity, values_capacity)));
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(frame_sources.as_ptr(), sources, frame_sources.len());
+            ptr::copy_nonoverlapping(frame_values.as_ptr(), values, frame_values.len());
+        }
+        Ok(())
+    })
 }
@@ -38,7 +38,12 @@
 //! WingConsole wing = WingConsole::connect(None);
 //! ```
 //!
-//! There is also `WingConsole::scan()` which can be used to scan for Wing mixers.
+//! In a venue with several Wings, connect to one by its name or serial number instead, with
+//! `WingConsole::connect_to()`. `ConnectOptions` also sets the connect timeout, the local
+//! interface and TCP keepalive.
+//!
+//! There is also `WingConsole::scan()` which can be used to scan for Wing mixers, and `Discovery`
+//! for more control over how they are found.
 //!
 //! ### Communication Model
 //!
@@ -66,19 +71,42 @@
 //!   this request. This ID will returned when you read the meters data.
 //!
 //! - `WingConsole::read_meters()` will block and return you messages from the Wing mixer as they
-//!   come in. It includes the ID returned from the `request_meter()` call for you to help correlate.
+//!   come in, as a `MeterFrame` with the levels of each requested meter in dB. It includes the ID
+//!   returned from the `request_meter()` call for you to help correlate.
+//!
+//! - You can have several meter subscriptions at once, each with its own ID. `WingConsole::cancel_meter()`
+//!   ends one, and `WingConsole::subscribe_meter()` sends a subscription's frames to its own receiver.
 //!
 //! All these calls are thread safe.
 
 
 mod console;
 mod node;
+mod meter;
+mod rta;
+mod discovery;
+mod model;
+mod connect;
 mod ffi;
 mod propmap;
+pub mod schema;
+pub mod typed;
+pub mod codegen;
+pub mod crawler;
+pub mod export;
+pub mod recording;
+pub mod osc;
+pub mod oscbridge;
 
-pub use console::{WingConsole, DiscoveryInfo, Meter};
-pub use node::{WingNodeDef, WingNodeData, NodeType, NodeUnit};
-pub use ffi::{WingConsoleHandle, ResponseHandle};
+pub use console::{WingConsole, Meter};
+pub use discovery::{Discovery, DiscoveryInfo, DiscoveryWatcher, DiscoveryEvent};
+pub use connect::ConnectOptions;
+pub use model::{WingModel, WingCapabilities, FirmwareVersion};
+pub use meter::{MeterFrame, MeterValues, StripMeter, StripStateMeter, DcaMeter, FxMeter, MonitorMeter, RTA_BANDS,
+                BallisticsConfig, MeterBallistics, MeterProcessor, MeterSource};
+pub use rta::{RtaSpectrum, RtaBin, FeedbackDetector, FeedbackConfig, FeedbackCandidate, rta_frequency, RTA_BINS_PER_OCTAVE};
+pub use node::{WingNodeDef, WingNodeData, NodeType, NodeUnit, FADER_MIN_DB, FADER_MAX_DB};
+pub use ffi::{WingConsoleHandle, ResponseHandle, ResponseType, ErrorCode, WingMeterSource, WING_API_VERSION};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -92,6 +120,22 @@ pub enum Error {
     ConnectionError,
     #[error("Failed to discover Wing console")]
     DiscoveryError,
+    #[error("Invalid schema: {0}")]
+    SchemaError(String),
+    #[error("Invalid pattern: {0}")]
+    PatternError(String),
+    #[error("Index out of range: {0}")]
+    IndexError(String),
+    #[error("Invalid meter data: {0}")]
+    MeterError(String),
+    #[error("Invalid meter packet: {0}")]
+    MeterPacketError(String),
+    #[error("Invalid value: {0}")]
+    ValueError(String),
+    #[error("OSC error: {0}")]
+    OscError(String),
+    #[error("No answer in time for ids {0:?}")]
+    Timeout(Vec<i32>),
 }
 
 pub enum WingResponse {
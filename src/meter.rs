@@ -0,0 +1,685 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::{Result, Error};
+use crate::console::Meter;
+use crate::rta::RtaSpectrum;
+
+/// Number of bands in an RTA meter
+pub const RTA_BANDS: usize = 120;
+
+impl Meter {
+    /// The meter as a 16 bit number, with the type in the high byte and the index in the low byte,
+    /// like `METER_ID()` in the C API
+    pub fn code(&self) -> u16 {
+        let (kind, index) = match self {
+            Meter::Channel(n) => (0xa0, *n),
+            Meter::Aux(n) => (0xa1, *n),
+            Meter::Bus(n) => (0xa2, *n),
+            Meter::Main(n) => (0xa3, *n),
+            Meter::Matrix(n) => (0xa4, *n),
+            Meter::Dca(n) => (0xa5, *n),
+            Meter::Fx(n) => (0xa6, *n),
+            Meter::Source(n) => (0xa7, *n),
+            Meter::Output(n) => (0xa8, *n),
+            Meter::Monitor => (0xa9, 0),
+            Meter::Rta => (0xaa, 0),
+            Meter::Channel2(n) => (0xab, *n),
+            Meter::Aux2(n) => (0xac, *n),
+            Meter::Bus2(n) => (0xad, *n),
+            Meter::Main2(n) => (0xae, *n),
+            Meter::Matrix2(n) => (0xaf, *n),
+        };
+        (kind << 8) | index as u16
+    }
+
+    /// The meter for a number from `code()`
+    pub fn from_code(code: u16) -> Option<Self> {
+        let n = (code & 0xff) as u8;
+        Some(match code >> 8 {
+            0xa0 => Meter::Channel(n),
+            0xa1 => Meter::Aux(n),
+            0xa2 => Meter::Bus(n),
+            0xa3 => Meter::Main(n),
+            0xa4 => Meter::Matrix(n),
+            0xa5 => Meter::Dca(n),
+            0xa6 => Meter::Fx(n),
+            0xa7 => Meter::Source(n),
+            0xa8 => Meter::Output(n),
+            0xa9 => Meter::Monitor,
+            0xaa => Meter::Rta,
+            0xab => Meter::Channel2(n),
+            0xac => Meter::Aux2(n),
+            0xad => Meter::Bus2(n),
+            0xae => Meter::Main2(n),
+            0xaf => Meter::Matrix2(n),
+            _ => return None,
+        })
+    }
+
+    /// A short name for the meter, numbered from 1 like on the console, for example "ch1" for
+    /// `Meter::Channel(0)`
+    pub fn name(&self) -> String {
+        match self {
+            Meter::Channel(n) => format!("ch{}", *n as u16 + 1),
+            Meter::Aux(n) => format!("aux{}", *n as u16 + 1),
+            Meter::Bus(n) => format!("bus{}", *n as u16 + 1),
+            Meter::Main(n) => format!("main{}", *n as u16 + 1),
+            Meter::Matrix(n) => format!("mtx{}", *n as u16 + 1),
+            Meter::Dca(n) => format!("dca{}", *n as u16 + 1),
+            Meter::Fx(n) => format!("fx{}", *n as u16 + 1),
+            Meter::Source(n) => format!("src{}", *n as u16 + 1),
+            Meter::Output(n) => format!("out{}", *n as u16 + 1),
+            Meter::Monitor => "mon".to_string(),
+            Meter::Rta => "rta".to_string(),
+            Meter::Channel2(n) => format!("ch{}state", *n as u16 + 1),
+            Meter::Aux2(n) => format!("aux{}state", *n as u16 + 1),
+            Meter::Bus2(n) => format!("bus{}state", *n as u16 + 1),
+            Meter::Main2(n) => format!("main{}state", *n as u16 + 1),
+            Meter::Matrix2(n) => format!("mtx{}state", *n as u16 + 1),
+        }
+    }
+
+    /// The meter for a name from `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mon" => return Some(Meter::Monitor),
+            "rta" => return Some(Meter::Rta),
+            _ => {}
+        }
+        let (name, state) = match name.strip_suffix("state") {
+            Some(n) => (n, true),
+            None => (name, false),
+        };
+        let split = name.find(|c: char| c.is_ascii_digit())?;
+        let n = name[split..].parse::<u16>().ok().filter(|n| (1..=256).contains(n))?;
+        let n = (n - 1) as u8;
+        Some(match (&name[..split], state) {
+            ("ch", false) => Meter::Channel(n),
+            ("aux", false) => Meter::Aux(n),
+            ("bus", false) => Meter::Bus(n),
+            ("main", false) => Meter::Main(n),
+            ("mtx", false) => Meter::Matrix(n),
+            ("dca", false) => Meter::Dca(n),
+            ("fx", false) => Meter::Fx(n),
+            ("src", false) => Meter::Source(n),
+            ("out", false) => Meter::Output(n),
+            ("ch", true) => Meter::Channel2(n),
+            ("aux", true) => Meter::Aux2(n),
+            ("bus", true) => Meter::Bus2(n),
+            ("main", true) => Meter::Main2(n),
+            ("mtx", true) => Meter::Matrix2(n),
+            _ => return None,
+        })
+    }
+
+    /// Number of 16 bit values the Wing sends for this meter in each packet
+    pub fn value_count(&self) -> usize {
+        match self {
+            Meter::Channel(_) |
+            Meter::Aux(_) |
+            Meter::Bus(_) |
+            Meter::Main(_) |
+            Meter::Matrix(_) => 8,
+            Meter::Dca(_) |
+            Meter::Fx(_) |
+            Meter::Monitor => 4,
+            Meter::Source(_) |
+            Meter::Output(_) => 1,
+            Meter::Rta => RTA_BANDS,
+            Meter::Channel2(_) |
+            Meter::Aux2(_) |
+            Meter::Bus2(_) |
+            Meter::Main2(_) |
+            Meter::Matrix2(_) => 2,
+        }
+    }
+}
+
+/// Levels of a channel, aux, bus, main or matrix strip, in dB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StripMeter {
+    pub input_left: f32,
+    pub input_right: f32,
+    pub output_left: f32,
+    pub output_right: f32,
+    /// Level of the gate's key signal
+    pub gate_key: f32,
+    /// Gain applied by the gate, 0 dB when open and negative when closing
+    pub gate_reduction: f32,
+    /// Level of the dynamics' key signal
+    pub dyn_key: f32,
+    /// Gain applied by the dynamics, 0 dB when not compressing and negative when compressing
+    pub dyn_reduction: f32,
+}
+
+/// Gate and dynamics state of a strip, from `Meter::Channel2()` and friends
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StripStateMeter {
+    pub gate_active: bool,
+    pub dyn_active: bool,
+}
+
+/// Levels of a DCA, in dB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DcaMeter {
+    pub pre_fader_left: f32,
+    pub pre_fader_right: f32,
+    pub post_fader_left: f32,
+    pub post_fader_right: f32,
+}
+
+/// Levels of an FX slot, in dB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FxMeter {
+    pub input_left: f32,
+    pub input_right: f32,
+    pub output_left: f32,
+    pub output_right: f32,
+}
+
+/// Levels of the monitor and solo buses, in dB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MonitorMeter {
+    pub left: f32,
+    pub right: f32,
+    pub solo_left: f32,
+    pub solo_right: f32,
+}
+
+/// The decoded values of one requested `Meter`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeterValues {
+    Strip(StripMeter),
+    StripState(StripStateMeter),
+    Dca(DcaMeter),
+    Fx(FxMeter),
+    /// Level of an input source or an output, in dB
+    Level(f32),
+    Monitor(MonitorMeter),
+    Rta(RtaSpectrum),
+}
+
+/// One meter packet from the Wing, decoded according to the list of meters it was requested with
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterFrame {
+    /// The id returned by `WingConsole::request_meter()`
+    pub id: u16,
+    /// The values of each requested meter, in the order they were requested
+    pub meters: Vec<(Meter, MeterValues)>,
+}
+
+impl MeterFrame {
+    /// Decodes the raw values of a meter packet. Returns `Error::MeterPacketError` if the number
+    /// of values does not match the requested meters.
+    pub fn decode(id: u16, requested: &[Meter], values: &[i16]) -> Result<Self> {
+        let expected: usize = requested.iter().map(|m| m.value_count()).sum();
+        if values.len() != expected {
+            return Err(Error::MeterPacketError(format!("packet for meter id {} has {} values, expected {}",
+                id, values.len(), expected)));
+        }
+
+        let mut meters = Vec::with_capacity(requested.len());
+        let mut rest = values;
+        for meter in requested {
+            let (v, r) = rest.split_at(meter.value_count());
+            rest = r;
+            let db = |i: usize| v[i] as f32 / 256.0;
+            let values = match meter {
+                Meter::Channel(_) |
+                Meter::Aux(_) |
+                Meter::Bus(_) |
+                Meter::Main(_) |
+                Meter::Matrix(_) => MeterValues::Strip(StripMeter {
+                    input_left: db(0),
+                    input_right: db(1),
+                    output_left: db(2),
+                    output_right: db(3),
+                    gate_key: db(4),
+                    gate_reduction: db(5),
+                    dyn_key: db(6),
+                    dyn_reduction: db(7),
+                }),
+                Meter::Channel2(_) |
+                Meter::Aux2(_) |
+                Meter::Bus2(_) |
+                Meter::Main2(_) |
+                Meter::Matrix2(_) => MeterValues::StripState(StripStateMeter {
+                    gate_active: v[0] != 0,
+                    dyn_active: v[1] != 0,
+                }),
+                Meter::Dca(_) => MeterValues::Dca(DcaMeter {
+                    pre_fader_left: db(0),
+                    pre_fader_right: db(1),
+                    post_fader_left: db(2),
+                    post_fader_right: db(3),
+                }),
+                Meter::Fx(_) => MeterValues::Fx(FxMeter {
+                    input_left: db(0),
+                    input_right: db(1),
+                    output_left: db(2),
+                    output_right: db(3),
+                }),
+                Meter::Source(_) |
+                Meter::Output(_) => MeterValues::Level(db(0)),
+                Meter::Monitor => MeterValues::Monitor(MonitorMeter {
+                    left: db(0),
+                    right: db(1),
+                    solo_left: db(2),
+                    solo_right: db(3),
+                }),
+                Meter::Rta => MeterValues::Rta(RtaSpectrum::from_levels(&(0..RTA_BANDS).map(db).collect::<Vec<_>>())),
+            };
+            meters.push((meter.clone(), values));
+        }
+        Ok(MeterFrame { id, meters })
+    }
+
+    /// The raw values of the packet this frame was decoded from, in 1/256 dB
+    pub fn encode(&self) -> Vec<i16> {
+        let raw = |db: f32| (db * 256.0).round() as i16;
+        let mut values = Vec::new();
+        for (_, v) in &self.meters {
+            match v {
+                MeterValues::StripState(s) => values.extend([s.gate_active as i16, s.dyn_active as i16]),
+                v => values.extend(v.levels().into_iter().map(raw)),
+            }
+        }
+        values
+    }
+
+    /// The values of a requested meter
+    pub fn get(&self, meter: &Meter) -> Option<&MeterValues> {
+        self.meters.iter().find(|(m, _)| m == meter).map(|(_, v)| v)
+    }
+
+    /// The spectrum of a requested `Meter::Rta`
+    pub fn rta(&self) -> Option<&RtaSpectrum> {
+        match self.get(&Meter::Rta) {
+            Some(MeterValues::Rta(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The levels of a requested channel, aux, bus, main or matrix meter
+    pub fn strip(&self, meter: &Meter) -> Option<&StripMeter> {
+        match self.get(meter) {
+            Some(MeterValues::Strip(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl StripMeter {
+    /// Index of `output_left` in `MeterValues::levels()`, for `MeterProcessor::get()`
+    pub const OUTPUT_LEFT: usize = 2;
+    /// Index of `output_right` in `MeterValues::levels()`, for `MeterProcessor::get()`
+    pub const OUTPUT_RIGHT: usize = 3;
+}
+
+impl MeterValues {
+    /// The levels of this meter in dB, in the order the Wing sends them. Gate and dynamics
+    /// state has no levels.
+    pub fn levels(&self) -> Vec<f32> {
+        match self {
+            MeterValues::Strip(s) => vec![s.input_left, s.input_right, s.output_left, s.output_right,
+                                          s.gate_key, s.gate_reduction, s.dyn_key, s.dyn_reduction],
+            MeterValues::StripState(_) => Vec::new(),
+            MeterValues::Dca(d) => vec![d.pre_fader_left, d.pre_fader_right, d.post_fader_left, d.post_fader_right],
+            MeterValues::Fx(f) => vec![f.input_left, f.input_right, f.output_left, f.output_right],
+            MeterValues::Level(l) => vec![*l],
+            MeterValues::Monitor(m) => vec![m.left, m.right, m.solo_left, m.solo_right],
+            MeterValues::Rta(spectrum) => spectrum.levels(),
+        }
+    }
+
+    /// Names of the values in `levels()`, such as "output_left"
+    pub fn level_names(&self) -> Vec<String> {
+        let names: &[&str] = match self {
+            MeterValues::Strip(_) => &["input_left", "input_right", "output_left", "output_right",
+                                       "gate_key", "gate_reduction", "dyn_key", "dyn_reduction"],
+            MeterValues::StripState(_) => &[],
+            MeterValues::Dca(_) => &["pre_fader_left", "pre_fader_right", "post_fader_left", "post_fader_right"],
+            MeterValues::Fx(_) => &["input_left", "input_right", "output_left", "output_right"],
+            MeterValues::Level(_) => &["level"],
+            MeterValues::Monitor(_) => &["left", "right", "solo_left", "solo_right"],
+            MeterValues::Rta(spectrum) => return spectrum.bins.iter().map(|b| format!("{:.0}Hz", b.frequency)).collect(),
+        };
+        names.iter().map(|n| n.to_string()).collect()
+    }
+}
+
+/// Something meter frames can be read from: a `WingConsole`, or a `MeterPlayer` replaying a
+/// recording
+pub trait MeterSource {
+    fn read_meters(&mut self) -> Result<MeterFrame>;
+}
+
+/// Settings for how `MeterBallistics` follows a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct BallisticsConfig {
+    /// Time constant for the smoothed level to rise
+    pub attack: Duration,
+    /// Time constant for the smoothed level to fall
+    pub release: Duration,
+    /// How long a peak is held before it starts to fall
+    pub peak_hold: Duration,
+    /// How fast a peak falls after it was held, in dB per second
+    pub peak_decay: f32,
+    /// Level in dB at or above which a meter latches `clipped`
+    pub clip_threshold: f32,
+    /// Level in dB at or above which a meter latches `over`
+    pub over_threshold: f32,
+    /// Number of smoothed levels to keep in `history`, 0 for none
+    pub history_len: usize,
+}
+
+impl Default for BallisticsConfig {
+    fn default() -> Self {
+        BallisticsConfig {
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(300),
+            peak_hold: Duration::from_millis(1500),
+            peak_decay: 20.0,
+            clip_threshold: 0.0,
+            over_threshold: -6.0,
+            history_len: 0,
+        }
+    }
+}
+
+/// Smoothing, peak hold and clip detection for one level, such as the left output of a channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterBallistics {
+    /// The level smoothed with the attack and release time constants, in dB. The smoothing
+    /// happens on power, so this follows the RMS of the level.
+    pub level: f32,
+    /// The highest recent level in dB, held for `peak_hold` and then falling
+    pub peak: f32,
+    /// Set when the level reached `clip_threshold`, until `reset_latches()`
+    pub clipped: bool,
+    /// Set when the level reached `over_threshold`, until `reset_latches()`
+    pub over: bool,
+    /// The most recent smoothed levels, oldest first
+    pub history: VecDeque<f32>,
+    peak_time: Option<Instant>,
+    last_update: Option<Instant>,
+}
+
+impl Default for MeterBallistics {
+    fn default() -> Self {
+        MeterBallistics {
+            level: SILENCE_DB,
+            peak: SILENCE_DB,
+            clipped: false,
+            over: false,
+            history: VecDeque::new(),
+            peak_time: None,
+            last_update: None,
+        }
+    }
+}
+
+/// The level meters start from and fall to
+const SILENCE_DB: f32 = -128.0;
+
+impl MeterBallistics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Takes a new level in dB that was measured at `now`
+    pub fn update(&mut self, config: &BallisticsConfig, db: f32, now: Instant) {
+        let first = self.last_update.is_none();
+        let dt = self.last_update.map(|t| now.saturating_duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        // smooth on power, with the attack time constant while rising and release while falling
+        let tau = if db > self.level { config.attack } else { config.release }.as_secs_f32();
+        let coef = if tau <= 0.0 || first { 1.0 } else { 1.0 - (-dt / tau).exp() };
+        let power = db_to_power(self.level) + (db_to_power(db) - db_to_power(self.level)) * coef;
+        self.level = power_to_db(power).max(SILENCE_DB);
+
+        let held = self.peak_time.map(|t| now.saturating_duration_since(t)).unwrap_or_default();
+        if db >= self.peak || first {
+            self.peak = db;
+            self.peak_time = Some(now);
+        } else if held > config.peak_hold {
+            let falling = (held - config.peak_hold).as_secs_f32().min(dt);
+            self.peak = (self.peak - config.peak_decay * falling).max(db);
+        }
+
+        self.clipped |= db >= config.clip_threshold;
+        self.over |= db >= config.over_threshold;
+
+        if config.history_len > 0 {
+            while self.history.len() >= config.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.level);
+        }
+    }
+
+    /// Clears `clipped` and `over`
+    pub fn reset_latches(&mut self) {
+        self.clipped = false;
+        self.over = false;
+    }
+}
+
+pub(crate) fn db_to_power(db: f32) -> f32 {
+    10f32.powf(db / 10.0)
+}
+
+pub(crate) fn power_to_db(power: f32) -> f32 {
+    10.0 * power.max(f32::MIN_POSITIVE).log10()
+}
+
+/// Applies `MeterBallistics` to every level of the frames it is given, for meter displays and
+/// for monitoring without one
+#[derive(Debug, Clone, Default)]
+pub struct MeterProcessor {
+    pub config: BallisticsConfig,
+    meters: HashMap<(Meter, usize), MeterBallistics>,
+}
+
+impl MeterProcessor {
+    pub fn new(config: BallisticsConfig) -> Self {
+        MeterProcessor { config, meters: HashMap::new() }
+    }
+
+    /// Takes the levels of a frame that was just received
+    pub fn process(&mut self, frame: &MeterFrame) {
+        self.process_at(frame, Instant::now());
+    }
+
+    /// Takes the levels of a frame that was received at `now`
+    pub fn process_at(&mut self, frame: &MeterFrame, now: Instant) {
+        for (meter, values) in &frame.meters {
+            for (index, db) in values.levels().into_iter().enumerate() {
+                self.meters.entry((meter.clone(), index)).or_default().update(&self.config, db, now);
+            }
+        }
+    }
+
+    /// The ballistics of a level of a meter, by its index in `MeterValues::levels()`, for example
+    /// `get(&Meter::Channel(0), StripMeter::OUTPUT_LEFT)`
+    pub fn get(&self, meter: &Meter, index: usize) -> Option<&MeterBallistics> {
+        self.meters.get(&(meter.clone(), index))
+    }
+
+    /// All levels that have been processed, with their meter and index
+    pub fn iter(&self) -> impl Iterator<Item = (&Meter, usize, &MeterBallistics)> {
+        self.meters.iter().map(|((m, i), b)| (m, *i, b))
+    }
+
+    /// Clears the clip and over latches of all levels
+    pub fn reset_latches(&mut self) {
+        self.meters.values_mut().for_each(|b| b.reset_latches());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Meter; 16] = [
+        Meter::Channel(0), Meter::Aux(1), Meter::Bus(2), Meter::Main(3), Meter::Matrix(4),
+        Meter::Dca(5), Meter::Fx(6), Meter::Source(7), Meter::Output(255), Meter::Monitor,
+        Meter::Rta, Meter::Channel2(39), Meter::Aux2(7), Meter::Bus2(15), Meter::Main2(3),
+        Meter::Matrix2(7),
+    ];
+
+    /// Alternating 0 dB and -6 dB, or off and on for gate and dynamics state
+    fn raw(meters: &[Meter]) -> Vec<i16> {
+        let mut values = Vec::new();
+        for meter in meters {
+            let state = matches!(meter, Meter::Channel2(_) | Meter::Aux2(_) | Meter::Bus2(_) | Meter::Main2(_) | Meter::Matrix2(_));
+            values.extend((0..meter.value_count() as i16).map(|i| if state { i % 2 } else { (i % 2) * -256 * 6 }));
+        }
+        values
+    }
+
+    #[test]
+    fn codes_and_names() {
+        for meter in ALL {
+            assert_eq!(Meter::from_code(meter.code()), Some(meter.clone()));
+            assert_eq!(Meter::from_name(&meter.name()), Some(meter.clone()));
+        }
+        assert_eq!(Meter::Channel(0).code(), 0xa000);
+        assert_eq!(Meter::Channel(0).name(), "ch1");
+        assert_eq!(Meter::Output(255).name(), "out256");
+        assert_eq!(Meter::from_code(0xb000), None);
+        for name in ["ch0", "ch257", "ch", "xyz1", "monstate", "rta1"] {
+            assert_eq!(Meter::from_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn decode_encode_round_trip() {
+        let values = raw(&ALL);
+        let frame = MeterFrame::decode(7, &ALL, &values).unwrap();
+        assert_eq!(frame.id, 7);
+        assert_eq!(frame.meters.len(), ALL.len());
+        assert_eq!(frame.encode(), values);
+        let strip = frame.strip(&Meter::Channel(0)).unwrap();
+        assert_eq!((strip.input_left, strip.input_right), (0.0, -6.0));
+        assert_eq!(frame.get(&Meter::Source(7)), Some(&MeterValues::Level(0.0)));
+        assert_eq!(frame.rta().unwrap().bins.len(), RTA_BANDS);
+        match frame.get(&Meter::Channel2(39)) {
+            Some(MeterValues::StripState(s)) => assert!(!s.gate_active && s.dyn_active),
+            v => panic!("{:?}", v),
+        }
+        assert!(frame.strip(&Meter::Channel(1)).is_none());
+        assert_eq!(frame.get(&Meter::Fx(6)).unwrap().level_names(), ["input_left", "input_right", "output_left", "output_right"]);
+    }
+
+    #[test]
+    fn levels_in_fractions_of_db() {
+        let frame = MeterFrame::decode(1, &[Meter::Source(0), Meter::Output(0)], &[-32768, 384]).unwrap();
+        assert_eq!(frame.get(&Meter::Source(0)), Some(&MeterValues::Level(-128.0)));
+        assert_eq!(frame.get(&Meter::Output(0)), Some(&MeterValues::Level(1.5)));
+        // levels out of the packet's range are saturated when encoding
+        let frame = MeterFrame { id: 1, meters: vec![(Meter::Source(0), MeterValues::Level(f32::NEG_INFINITY))] };
+        assert_eq!(frame.encode(), [i16::MIN]);
+    }
+
+    #[test]
+    fn wrong_packet_size() {
+        let meters = [Meter::Channel(0), Meter::Dca(0)];
+        let values = raw(&meters);
+        for len in [0, 1, values.len() - 1] {
+            assert!(matches!(MeterFrame::decode(1, &meters, &values[..len]), Err(Error::MeterPacketError(_))), "{} values", len);
+        }
+        let mut long = values.clone();
+        long.push(0);
+        assert!(matches!(MeterFrame::decode(1, &meters, &long), Err(Error::MeterPacketError(_))));
+        // nothing requested and nothing sent
+        assert_eq!(MeterFrame::decode(1, &[], &[]).unwrap().meters, []);
+    }
+
+    fn ms(t: Instant, ms: u64) -> Instant {
+        t + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn attack_and_release() {
+        let config = BallisticsConfig::default();
+        let t = Instant::now();
+        let mut b = MeterBallistics::new();
+        b.update(&config, -128.0, t);
+        assert_eq!(b.level, SILENCE_DB);
+        // one time constant covers 63 % of the step in power
+        b.update(&config, 0.0, ms(t, 10));
+        assert!((b.level - power_to_db(1.0 - (-1f32).exp())).abs() < 0.01, "{}", b.level);
+        b.update(&config, 0.0, ms(t, 200));
+        assert!(b.level > -0.01);
+        b.update(&config, -128.0, ms(t, 500));
+        assert!((b.level - power_to_db((-1f32).exp())).abs() < 0.01, "{}", b.level);
+        b.update(&config, f32::NEG_INFINITY, ms(t, 20000));
+        assert_eq!(b.level, SILENCE_DB);
+    }
+
+    #[test]
+    fn first_level_is_taken_as_is() {
+        let mut b = MeterBallistics::new();
+        b.update(&BallisticsConfig::default(), -20.0, Instant::now());
+        assert!((b.level - -20.0).abs() < 1e-4);
+        assert_eq!(b.peak, -20.0);
+    }
+
+    #[test]
+    fn peak_hold_and_decay() {
+        let config = BallisticsConfig::default();
+        let t = Instant::now();
+        let mut b = MeterBallistics::new();
+        b.update(&config, -10.0, t);
+        b.update(&config, -40.0, ms(t, 1000));
+        assert_eq!(b.peak, -10.0);
+        b.update(&config, -40.0, ms(t, 1500));
+        assert_eq!(b.peak, -10.0);
+        // falls at 20 dB/s once the hold is over
+        b.update(&config, -40.0, ms(t, 2000));
+        assert!((b.peak - -20.0).abs() < 0.01, "{}", b.peak);
+        b.update(&config, -40.0, ms(t, 2500));
+        assert!((b.peak - -30.0).abs() < 0.01, "{}", b.peak);
+        // but not below the level
+        b.update(&config, -35.0, ms(t, 3000));
+        assert_eq!(b.peak, -35.0);
+        b.update(&config, -5.0, ms(t, 3100));
+        assert_eq!(b.peak, -5.0);
+    }
+
+    #[test]
+    fn latches_and_history() {
+        let config = BallisticsConfig { history_len: 3, ..Default::default() };
+        let t = Instant::now();
+        let mut b = MeterBallistics::new();
+        b.update(&config, -7.0, t);
+        assert!(!b.over && !b.clipped);
+        b.update(&config, -6.0, ms(t, 10));
+        assert!(b.over && !b.clipped);
+        b.update(&config, 0.0, ms(t, 20));
+        b.update(&config, -60.0, ms(t, 30));
+        assert!(b.over && b.clipped);
+        assert_eq!(b.history.len(), 3);
+        assert_eq!(b.history.back(), Some(&b.level));
+        b.reset_latches();
+        assert!(!b.over && !b.clipped);
+    }
+
+    #[test]
+    fn processor_follows_every_level() {
+        let t = Instant::now();
+        let mut p = MeterProcessor::new(BallisticsConfig::default());
+        let frame = MeterFrame::decode(1, &[Meter::Channel(0), Meter::Source(2)], &[0, 0, 256, -256, 0, 0, 0, 0, 0]).unwrap();
+        p.process_at(&frame, t);
+        assert_eq!(p.iter().count(), 9);
+        assert!((p.get(&Meter::Channel(0), StripMeter::OUTPUT_LEFT).unwrap().level - 1.0).abs() < 1e-4);
+        assert_eq!(p.get(&Meter::Channel(0), StripMeter::OUTPUT_RIGHT).map(|b| b.peak), Some(-1.0));
+        assert!(p.get(&Meter::Channel(0), StripMeter::OUTPUT_LEFT).unwrap().clipped);
+        assert!(p.get(&Meter::Source(2), 1).is_none());
+        p.reset_latches();
+        assert!(p.iter().all(|(_, _, b)| !b.clipped && !b.over));
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+use crate::console::Meter;
+
+/// The kind of Wing console, from the model string in `DiscoveryInfo`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WingModel {
+    /// The full size Wing, and the Wing BK
+    Wing,
+    WingCompact,
+    WingRack,
+    /// A model string this version of libwing doesn't know
+    Unknown(String),
+}
+
+impl WingModel {
+    /// Parses a model string as sent in the discovery answer, for example `ngc-full` or
+    /// `wing-compact`
+    pub fn parse(model: &str) -> Self {
+        let lower = model.trim().to_ascii_lowercase();
+        let kind = lower.strip_prefix("ngc-").or_else(|| lower.strip_prefix("wing-")).unwrap_or(&lower);
+        match kind {
+            "full" | "wing" | "bk" => WingModel::Wing,
+            "compact" => WingModel::WingCompact,
+            "rack" => WingModel::WingRack,
+            _ => WingModel::Unknown(model.to_string()),
+        }
+    }
+
+    /// The name the model is sold under
+    pub fn name(&self) -> &str {
+        match self {
+            WingModel::Wing => "WING",
+            WingModel::WingCompact => "WING Compact",
+            WingModel::WingRack => "WING Rack",
+            WingModel::Unknown(model) => model,
+        }
+    }
+
+    /// What the model has. An unknown model is assumed to be like the full size Wing.
+    pub fn capabilities(&self) -> WingCapabilities {
+        let (local_inputs, local_outputs) = match self {
+            WingModel::Wing | WingModel::Unknown(_) => (8, 8),
+            WingModel::WingCompact | WingModel::WingRack => (24, 8),
+        };
+        WingCapabilities {
+            channels: 40,
+            aux: 8,
+            buses: 16,
+            mains: 4,
+            matrices: 8,
+            dcas: 16,
+            fx: 16,
+            local_inputs,
+            local_outputs,
+        }
+    }
+}
+
+impl fmt::Display for WingModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How many of each thing a model has. All models share the same mixing engine, so only the
+/// local inputs and outputs differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WingCapabilities {
+    pub channels: u8,
+    pub aux: u8,
+    pub buses: u8,
+    pub mains: u8,
+    pub matrices: u8,
+    pub dcas: u8,
+    pub fx: u8,
+    /// Analogue inputs on the console itself
+    pub local_inputs: u8,
+    /// Analogue outputs on the console itself
+    pub local_outputs: u8,
+}
+
+impl WingCapabilities {
+    /// Whether the meter exists on this model, i.e. its index is in range
+    pub fn supports_meter(&self, meter: &Meter) -> bool {
+        match *meter {
+            Meter::Channel(n) | Meter::Channel2(n) => n < self.channels,
+            Meter::Aux(n) | Meter::Aux2(n) => n < self.aux,
+            Meter::Bus(n) | Meter::Bus2(n) => n < self.buses,
+            Meter::Main(n) | Meter::Main2(n) => n < self.mains,
+            Meter::Matrix(n) | Meter::Matrix2(n) => n < self.matrices,
+            Meter::Dca(n) => n < self.dcas,
+            Meter::Fx(n) => n < self.fx,
+            Meter::Source(_) | Meter::Output(_) | Meter::Monitor | Meter::Rta => true,
+        }
+    }
+
+    /// Returns an `Error::IndexError` naming the first meter that doesn't exist on this model
+    pub fn check_meters(&self, meters: &[Meter]) -> crate::Result<()> {
+        match meters.iter().find(|m| !self.supports_meter(m)) {
+            Some(m) => Err(Error::IndexError(format!("meter {} does not exist on this console", m.name()))),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the strip a property path like `/ch/41/fdr` points to exists on this model. Paths
+    /// outside of the numbered strips are always allowed.
+    pub fn supports_path(&self, path: &str) -> bool {
+        let mut parts = path.trim_start_matches('/').split('/');
+        let count = match parts.next() {
+            Some("ch") => self.channels,
+            Some("aux") => self.aux,
+            Some("bus") => self.buses,
+            Some("main") => self.mains,
+            Some("mtx") => self.matrices,
+            Some("dca") => self.dcas,
+            Some("fx") => self.fx,
+            _ => return true,
+        };
+        match parts.next().map(|n| n.parse::<u16>()) {
+            Some(Ok(n)) => n >= 1 && n <= count as u16,
+            _ => true,
+        }
+    }
+}
+
+/// A firmware version as sent in the discovery answer, like `3.0.5-0-g0c2b9d4a:release`.
+///
+/// Versions compare by major, minor, patch and build number, so
+/// `firmware >= FirmwareVersion::new(3, 0, 5)` checks for a feature that came with 3.0.5. Versions
+/// that only differ in hash or channel are ordered by those, to keep the order total.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    /// Number of commits after the release tag, 0 for a release
+    pub build: u32,
+    /// Abbreviated git commit hash, without the `g` prefix, or empty
+    pub hash: String,
+    /// Release channel, for example `release` or `develop`, or empty
+    pub channel: String,
+}
+
+impl FirmwareVersion {
+    pub fn new(major: u16, minor: u16, patch: u16) -> Self {
+        FirmwareVersion { major, minor, patch, build: 0, hash: String::new(), channel: String::new() }
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = Error;
+
+    /// Parses `major.minor.patch[-build[-ghash]][:channel]`
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (version, channel) = s.trim().split_once(':').unwrap_or((s.trim(), ""));
+        let mut parts = version.splitn(3, '-');
+        let mut numbers = parts.next().unwrap_or("").split('.').map(|n| n.parse::<u16>().map_err(|_| Error::InvalidData));
+        let major = numbers.next().ok_or(Error::InvalidData)??;
+        let minor = numbers.next().unwrap_or(Ok(0))?;
+        let patch = numbers.next().unwrap_or(Ok(0))?;
+        if numbers.next().is_some() {
+            return Err(Error::InvalidData);
+        }
+        let build = match parts.next() {
+            Some(b) => b.parse().map_err(|_| Error::InvalidData)?,
+            None => 0,
+        };
+        let hash = parts.next().map(|h| h.strip_prefix('g').unwrap_or(h).to_string()).unwrap_or_default();
+        Ok(FirmwareVersion { major, minor, patch, build, hash, channel: channel.to_string() })
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.build != 0 || !self.hash.is_empty() {
+            write!(f, "-{}", self.build)?;
+        }
+        if !self.hash.is_empty() {
+            write!(f, "-g{}", self.hash)?;
+        }
+        if !self.channel.is_empty() {
+            write!(f, ":{}", self.channel)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> FirmwareVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_versions() {
+        let v = version("3.0.5-12-g0c2b9d4a:release");
+        assert_eq!((v.major, v.minor, v.patch, v.build), (3, 0, 5, 12));
+        assert_eq!((v.hash.as_str(), v.channel.as_str()), ("0c2b9d4a", "release"));
+        assert_eq!(version("3.0.5"), FirmwareVersion::new(3, 0, 5));
+        assert_eq!(version(" 3.1 "), FirmwareVersion::new(3, 1, 0));
+        assert_eq!(version("3"), FirmwareVersion::new(3, 0, 0));
+        assert_eq!(version("3.0.5:develop").channel, "develop");
+        assert_eq!(version("3.0.5-7").build, 7);
+    }
+
+    #[test]
+    fn malformed_versions() {
+        for s in ["", ":release", "x", "3.x.5", "3..5", "3.0.5.1", "3.0.5-x", "3.0.5-", "-1.0.0", "70000.0.0", "3.0.-5"] {
+            assert!(matches!(s.parse::<FirmwareVersion>(), Err(Error::InvalidData)), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in ["3.0.5", "3.0.5-12-g0c2b9d4a:release", "3.0.5-3", "3.0.5-0-gabc", "2.1.0:develop"] {
+            assert_eq!(version(s).to_string(), s);
+        }
+        assert_eq!(version("3.1").to_string(), "3.1.0");
+        assert_eq!(version("3.0.5-0").to_string(), "3.0.5");
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(version("3.0.10") > version("3.0.9"));
+        assert!(version("3.1.0") > version("3.0.99"));
+        assert!(version("10.0.0") > version("9.9.9"));
+        assert!(version("3.0.5-1") > version("3.0.5"));
+        assert!(version("3.0.5-0-gabc:release") >= FirmwareVersion::new(3, 0, 5));
+        assert!(version("3.0.4-99") < FirmwareVersion::new(3, 0, 5));
+        // hash and channel only break ties
+        assert!(version("3.0.5-1-gaaa") < version("3.0.5-1-gbbb"));
+        assert_ne!(version("3.0.5:develop"), version("3.0.5:release"));
+        let mut versions = [version("3.1.0"), version("2.9.0"), version("3.0.5-2"), version("3.0.5")];
+        versions.sort();
+        assert_eq!(versions.iter().map(|v| v.to_string()).collect::<Vec<_>>(), ["2.9.0", "3.0.5", "3.0.5-2", "3.1.0"]);
+    }
+
+    #[test]
+    fn models() {
+        assert_eq!(WingModel::parse("ngc-full"), WingModel::Wing);
+        assert_eq!(WingModel::parse("WING-COMPACT"), WingModel::WingCompact);
+        assert_eq!(WingModel::parse("rack"), WingModel::WingRack);
+        assert_eq!(WingModel::parse("ngc-xl"), WingModel::Unknown("ngc-xl".into()));
+        assert_eq!(WingModel::parse("ngc-xl").to_string(), "ngc-xl");
+        let caps = WingModel::WingRack.capabilities();
+        assert_eq!(caps.local_inputs, 24);
+        assert!(caps.supports_path("/ch/40/fdr"));
+        assert!(!caps.supports_path("/ch/41/fdr"));
+        assert!(!caps.supports_path("/dca/0/fdr"));
+        assert!(caps.supports_path("/cfg/rate"));
+        assert!(caps.check_meters(&[Meter::Channel(39), Meter::Rta]).is_ok());
+        assert!(matches!(caps.check_meters(&[Meter::Fx(16)]), Err(Error::IndexError(_))));
+    }
+}
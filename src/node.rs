@@ -24,6 +24,67 @@ pub enum NodeUnit {
     Octaves = 7,
 }
 
+impl NodeType {
+    /// The name used for this type in `to_json()` output and propmap.jsonl
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeType::Node             => "node",
+            NodeType::LinearFloat      => "linear float",
+            NodeType::LogarithmicFloat => "log float",
+            NodeType::FaderLevel       => "fader level",
+            NodeType::Integer          => "integer",
+            NodeType::StringEnum       => "string enum",
+            NodeType::FloatEnum        => "float enum",
+            NodeType::String           => "string",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "node"         => Some(NodeType::Node),
+            "linear float" => Some(NodeType::LinearFloat),
+            "log float"    => Some(NodeType::LogarithmicFloat),
+            "fader level"  => Some(NodeType::FaderLevel),
+            "integer"      => Some(NodeType::Integer),
+            "string enum"  => Some(NodeType::StringEnum),
+            "float enum"   => Some(NodeType::FloatEnum),
+            "string"       => Some(NodeType::String),
+            _ => None,
+        }
+    }
+}
+
+impl NodeUnit {
+    /// The name used for this unit in `to_json()` output and propmap.jsonl. `NodeUnit::None` is
+    /// an empty string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeUnit::None         => "",
+            NodeUnit::Db           => "dB",
+            NodeUnit::Percent      => "%",
+            NodeUnit::Milliseconds => "ms",
+            NodeUnit::Hertz        => "Hz",
+            NodeUnit::Meters       => "meters",
+            NodeUnit::Seconds      => "seconds",
+            NodeUnit::Octaves      => "octaves",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            ""        => Some(NodeUnit::None),
+            "dB"      => Some(NodeUnit::Db),
+            "%"       => Some(NodeUnit::Percent),
+            "ms"      => Some(NodeUnit::Milliseconds),
+            "Hz"      => Some(NodeUnit::Hertz),
+            "meters"  => Some(NodeUnit::Meters),
+            "seconds" => Some(NodeUnit::Seconds),
+            "octaves" => Some(NodeUnit::Octaves),
+            _ => None,
+        }
+    }
+}
+
 pub struct StringEnumItem {
     pub item: String,
     pub long_item: String,
@@ -239,18 +300,13 @@ impl Clone for WingNodeDef {
     }
 }
 
+#[derive(Clone, Debug, Default)]
 pub struct WingNodeData {
     string_value: Option<String>,
     float_value: Option<f32>,
     int_value: Option<i32>,
 }
 
-impl Default for WingNodeData {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl WingNodeData {
     pub fn new() -> Self {
         Self {
@@ -330,6 +386,28 @@ impl WingNodeData {
     pub fn has_int(&self) -> bool {
         self.int_value.is_some()
     }
+
+    /// The value as a float, converting from an integer or parsing a string if needed
+    pub fn to_float(&self) -> Option<f32> {
+        if let Some(f) = self.float_value {
+            Some(f)
+        } else if let Some(i) = self.int_value {
+            Some(i as f32)
+        } else {
+            self.string_value.as_ref().and_then(|s| s.trim().parse::<f32>().ok())
+        }
+    }
+
+    /// The value as an integer, rounding a float or parsing a string if needed
+    pub fn to_int(&self) -> Option<i32> {
+        if let Some(i) = self.int_value {
+            Some(i)
+        } else if let Some(f) = self.float_value {
+            Some(f.round() as i32)
+        } else {
+            self.string_value.as_ref().and_then(|s| s.trim().parse::<i32>().ok())
+        }
+    }
 }
 
 impl WingNodeDef {
@@ -476,25 +554,9 @@ impl WingNodeDef {
             json.insert("longname", self.long_name.clone()).unwrap();
         }
 
-        match self.node_type {
-            NodeType::Node             => { json.insert("type", "node").unwrap(); }
-            NodeType::LinearFloat      => { json.insert("type", "linear float").unwrap(); }
-            NodeType::LogarithmicFloat => { json.insert("type", "log float").unwrap(); }
-            NodeType::Integer          => { json.insert("type", "integer").unwrap(); }
-            NodeType::String           => { json.insert("type", "string").unwrap(); }
-            NodeType::FaderLevel       => { json.insert("type", "fader level").unwrap(); }
-            NodeType::StringEnum       => { json.insert("type", "string enum").unwrap(); }
-            NodeType::FloatEnum        => { json.insert("type", "float enum").unwrap(); }
-        }
-        match self.unit {
-            NodeUnit::None         => { }
-            NodeUnit::Db           => { json.insert("unit", "dB").unwrap(); }
-            NodeUnit::Percent      => { json.insert("unit", "%").unwrap(); }
-            NodeUnit::Milliseconds => { json.insert("unit", "ms").unwrap(); }
-            NodeUnit::Hertz        => { json.insert("unit", "Hz").unwrap(); }
-            NodeUnit::Meters       => { json.insert("unit", "meters").unwrap(); }
-            NodeUnit::Seconds      => { json.insert("unit", "seconds").unwrap(); }
-            NodeUnit::Octaves      => { json.insert("unit", "octaves").unwrap(); }
+        json.insert("type", self.node_type.as_str()).unwrap();
+        if self.unit != NodeUnit::None {
+            json.insert("unit", self.unit.as_str()).unwrap();
         }
 
         if self.read_only {
@@ -542,4 +604,691 @@ impl WingNodeDef {
         }
         json
     }
+
+    /// Builds a definition from the JSON produced by `to_json()`, such as a line of
+    /// propmap.jsonl. The `parent_id` is not part of that JSON, so it is set to 0. Returns `None`
+    /// if the JSON is missing the id or has an unknown type or unit.
+    pub fn from_json(json: &jzon::JsonValue) -> Option<Self> {
+        let id = json["id"].as_i32()?;
+        let node_type = NodeType::from_name(json["type"].as_str()?)?;
+        let unit = NodeUnit::from_name(json["unit"].as_str().unwrap_or(""))?;
+
+        let mut def = WingNodeDef {
+            id,
+            parent_id: 0,
+            index: json["index"].as_u16().unwrap_or(0),
+            name: json["name"].as_str().unwrap_or("").to_string(),
+            long_name: json["longname"].as_str().unwrap_or("").to_string(),
+            node_type,
+            unit,
+            read_only: json["read_only"].as_bool().unwrap_or(false),
+            min_float: json["minfloat"].as_f32(),
+            max_float: json["maxfloat"].as_f32(),
+            steps: json["steps"].as_i32(),
+            min_int: json["minint"].as_i32(),
+            max_int: json["maxint"].as_i32(),
+            max_string_len: json["maxstringlen"].as_u16(),
+            string_enum: None,
+            float_enum: None,
+            raw: Vec::new(),
+        };
+
+        if json["items"].is_array() {
+            match node_type {
+                NodeType::StringEnum => {
+                    def.string_enum = Some(json["items"].members().map(|j| StringEnumItem {
+                        item: j["item"].as_str().unwrap_or("").to_string(),
+                        long_item: j["longitem"].as_str().unwrap_or("").to_string(),
+                    }).collect());
+                }
+                NodeType::FloatEnum => {
+                    def.float_enum = Some(json["items"].members().map(|j| FloatEnumItem {
+                        item: j["item"].as_f32().unwrap_or(0.0),
+                        long_item: j["longitem"].as_str().unwrap_or("").to_string(),
+                    }).collect());
+                }
+                _ => {}
+            }
+        }
+
+        def.raw = def.to_bytes();
+        Some(def)
+    }
+
+    /// Encodes this definition in the Wing's native format, the inverse of `from_bytes()`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = Vec::new();
+        raw.extend_from_slice(&self.parent_id.to_be_bytes());
+        raw.extend_from_slice(&self.id.to_be_bytes());
+        raw.extend_from_slice(&self.index.to_be_bytes());
+        raw.push(self.name.len() as u8);
+        raw.extend_from_slice(self.name.as_bytes());
+        raw.push(self.long_name.len() as u8);
+        raw.extend_from_slice(self.long_name.as_bytes());
+
+        let flags = ((self.read_only as u16) << 9) | ((self.node_type as u16) << 4) | self.unit as u16;
+        raw.extend_from_slice(&flags.to_be_bytes());
+
+        match self.node_type {
+            NodeType::Node | NodeType::FaderLevel => { }
+            NodeType::String => {
+                raw.extend_from_slice(&self.max_string_len.unwrap_or(0).to_be_bytes());
+            }
+            NodeType::LinearFloat |
+                NodeType::LogarithmicFloat => {
+                    raw.extend_from_slice(&self.min_float.unwrap_or(0.0).to_be_bytes());
+                    raw.extend_from_slice(&self.max_float.unwrap_or(0.0).to_be_bytes());
+                    raw.extend_from_slice(&self.steps.unwrap_or(0).to_be_bytes());
+                }
+            NodeType::Integer => {
+                raw.extend_from_slice(&self.min_int.unwrap_or(0).to_be_bytes());
+                raw.extend_from_slice(&self.max_int.unwrap_or(0).to_be_bytes());
+            }
+            NodeType::StringEnum => {
+                let items = self.string_enum.as_deref().unwrap_or(&[]);
+                raw.extend_from_slice(&(items.len() as u16).to_be_bytes());
+                for item in items {
+                    raw.push(item.item.len() as u8);
+                    raw.extend_from_slice(item.item.as_bytes());
+                    raw.push(item.long_item.len() as u8);
+                    raw.extend_from_slice(item.long_item.as_bytes());
+                }
+            }
+            NodeType::FloatEnum => {
+                let items = self.float_enum.as_deref().unwrap_or(&[]);
+                raw.extend_from_slice(&(items.len() as u16).to_be_bytes());
+                for item in items {
+                    raw.extend_from_slice(&item.item.to_be_bytes());
+                    raw.push(item.long_item.len() as u8);
+                    raw.extend_from_slice(item.long_item.as_bytes());
+                }
+            }
+        }
+        raw
+    }
+}
+
+/// The lowest fader level, which the Wing shows as -inf
+pub const FADER_MIN_DB: f32 = -144.0;
+/// The highest fader level
+pub const FADER_MAX_DB: f32 = 10.0;
+
+/// Breakpoints of the fader law as (position, dB). Between them the level is linear in dB, like
+/// the markings on the Wing's faders. Below the first segment the level drops to -inf. The Wing
+/// sends no range for fader levels, so all of them use this law; a `min_float`/`max_float` from a
+/// JSON definition only limits the levels it reaches, since the law fixes the dB at each position.
+const FADER_LAW: [(f32, f32); 5] = [
+    (0.0, -90.0),
+    (0.0625, -60.0),
+    (0.25, -30.0),
+    (0.5, -10.0),
+    (1.0, 10.0),
+];
+
+impl WingNodeDef {
+    /// The position of a value on the property's scale, from 0.0 to 1.0, as a UI slider would
+    /// show it. Linear floats and integers are linear between their minimum and maximum,
+    /// logarithmic floats (such as frequencies in Hz) are logarithmic, fader levels follow the
+    /// fader law, and float enums go by item. Values out of range are clamped. Returns `None` for
+    /// nodes, strings and string enums, and for properties without a range.
+    pub fn to_normalized(&self, value: f32) -> Option<f32> {
+        let pos = match self.node_type {
+            NodeType::LinearFloat => linear_to_pos(value, self.min_float?, self.max_float?),
+            NodeType::LogarithmicFloat => {
+                let (min, max) = (self.min_float?, self.max_float?);
+                if min > 0.0 && max > min {
+                    (value.max(min) / min).ln() / (max / min).ln()
+                } else {
+                    linear_to_pos(value, min, max)
+                }
+            }
+            NodeType::FaderLevel => {
+                let (min, max) = self.fader_range();
+                fader_to_pos(value.clamp(min, max))
+            }
+            NodeType::Integer => linear_to_pos(value, self.min_int? as f32, self.max_int? as f32),
+            NodeType::FloatEnum => {
+                let items = self.float_enum.as_ref().filter(|i| !i.is_empty())?;
+                let nearest = items.iter().enumerate()
+                    .min_by(|a, b| (a.1.item - value).abs().total_cmp(&(b.1.item - value).abs()))?.0;
+                index_to_pos(nearest, items.len())
+            }
+            NodeType::Node | NodeType::String | NodeType::StringEnum => return None,
+        };
+        Some(pos.clamp(0.0, 1.0))
+    }
+
+    /// The value at a position on the property's scale, the inverse of `to_normalized()`. The
+    /// position is clamped to 0.0..1.0. The value is not quantized; see `quantize()`.
+    pub fn from_normalized(&self, position: f32) -> Option<f32> {
+        let pos = position.clamp(0.0, 1.0);
+        match self.node_type {
+            NodeType::LinearFloat => Some(pos_to_linear(pos, self.min_float?, self.max_float?)),
+            NodeType::LogarithmicFloat => {
+                let (min, max) = (self.min_float?, self.max_float?);
+                if min > 0.0 && max > min {
+                    Some(min * (max / min).powf(pos))
+                } else {
+                    Some(pos_to_linear(pos, min, max))
+                }
+            }
+            NodeType::FaderLevel => {
+                let (min, max) = self.fader_range();
+                Some(pos_to_fader(pos).clamp(min, max))
+            }
+            NodeType::Integer => Some(pos_to_linear(pos, self.min_int? as f32, self.max_int? as f32).round()),
+            NodeType::FloatEnum => {
+                let items = self.float_enum.as_ref().filter(|i| !i.is_empty())?;
+                Some(items[pos_to_index(pos, items.len())].item)
+            }
+            NodeType::Node | NodeType::String | NodeType::StringEnum => None,
+        }
+    }
+
+    /// Snaps a value to the nearest value the property can take. With `steps`, the scale is
+    /// divided into that many evenly spaced positions (evenly on the property's own scale, so
+    /// logarithmically for logarithmic floats). Integers are rounded and float enums snap to the
+    /// nearest item. Values out of range are clamped.
+    pub fn quantize(&self, value: f32) -> Option<f32> {
+        match self.node_type {
+            NodeType::LinearFloat |
+            NodeType::LogarithmicFloat |
+            NodeType::FaderLevel => {
+                if let Some(steps) = self.steps.filter(|s| *s > 1) {
+                    let intervals = (steps - 1) as f32;
+                    let pos = (self.to_normalized(value)? * intervals).round() / intervals;
+                    self.from_normalized(pos)
+                } else if self.node_type == NodeType::FaderLevel {
+                    let (min, max) = self.fader_range();
+                    Some(value.clamp(min, max))
+                } else {
+                    Some(value.clamp(self.min_float?.min(self.max_float?), self.max_float?.max(self.min_float?)))
+                }
+            }
+            NodeType::Integer => Some(value.round().clamp(self.min_int? as f32, self.max_int? as f32)),
+            NodeType::FloatEnum => self.from_normalized(self.to_normalized(value)?),
+            NodeType::Node | NodeType::String | NodeType::StringEnum => None,
+        }
+    }
+
+    /// The position of a property value on its scale, like `to_normalized()`. String enums go by
+    /// item.
+    pub fn data_to_normalized(&self, data: &WingNodeData) -> Option<f32> {
+        if self.node_type == NodeType::StringEnum {
+            let items = self.string_enum.as_ref()?;
+            let value = data.get_string();
+            let i = items.iter().position(|i| i.item == value)?;
+            return Some(index_to_pos(i, items.len()));
+        }
+        self.to_normalized(data.to_float()?)
+    }
+
+    /// The quantized property value at a position on its scale, ready to send to the Wing
+    pub fn normalized_to_data(&self, position: f32) -> Option<WingNodeData> {
+        match self.node_type {
+            NodeType::StringEnum => {
+                let items = self.string_enum.as_ref().filter(|i| !i.is_empty())?;
+                let i = pos_to_index(position.clamp(0.0, 1.0), items.len());
+                Some(WingNodeData::with_string(items[i].item.clone()))
+            }
+            NodeType::Integer => Some(WingNodeData::with_i32(self.from_normalized(position)? as i32)),
+            _ => Some(WingNodeData::with_float(self.quantize(self.from_normalized(position)?)?)),
+        }
+    }
+
+    /// The levels a fader level can take: `FADER_MIN_DB` to `FADER_MAX_DB`, narrowed to the
+    /// definition's own range if it has one
+    fn fader_range(&self) -> (f32, f32) {
+        let min = self.min_float.map_or(FADER_MIN_DB, |m| m.max(FADER_MIN_DB));
+        let max = self.max_float.map_or(FADER_MAX_DB, |m| m.min(FADER_MAX_DB));
+        if min <= max { (min, max) } else { (FADER_MIN_DB, FADER_MAX_DB) }
+    }
+}
+
+fn linear_to_pos(value: f32, min: f32, max: f32) -> f32 {
+    if max == min { 0.0 } else { (value - min) / (max - min) }
+}
+
+fn pos_to_linear(pos: f32, min: f32, max: f32) -> f32 {
+    min + pos * (max - min)
+}
+
+fn index_to_pos(i: usize, len: usize) -> f32 {
+    if len > 1 { i as f32 / (len - 1) as f32 } else { 0.0 }
+}
+
+fn pos_to_index(pos: f32, len: usize) -> usize {
+    ((pos * (len - 1) as f32).round() as usize).min(len - 1)
+}
+
+fn fader_to_pos(db: f32) -> f32 {
+    if db <= FADER_LAW[0].1 {
+        return 0.0;
+    }
+    for w in FADER_LAW.windows(2) {
+        let ((p0, d0), (p1, d1)) = (w[0], w[1]);
+        if db <= d1 {
+            return p0 + (db - d0) / (d1 - d0) * (p1 - p0);
+        }
+    }
+    1.0
+}
+
+fn pos_to_fader(pos: f32) -> f32 {
+    if pos <= 0.0 {
+        return FADER_MIN_DB;
+    }
+    for w in FADER_LAW.windows(2) {
+        let ((p0, d0), (p1, d1)) = (w[0], w[1]);
+        if pos <= p1 {
+            return d0 + (pos - p0) / (p1 - p0) * (d1 - d0);
+        }
+    }
+    FADER_MAX_DB
+}
+
+impl NodeUnit {
+    /// The symbol shown after a value, such as "dB" or "Hz"
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            NodeUnit::None => "",
+            NodeUnit::Db => "dB",
+            NodeUnit::Percent => "%",
+            NodeUnit::Milliseconds => "ms",
+            NodeUnit::Hertz => "Hz",
+            NodeUnit::Meters => "m",
+            NodeUnit::Seconds => "s",
+            NodeUnit::Octaves => "oct",
+        }
+    }
+}
+
+impl WingNodeDef {
+    /// Formats a property value for people, with its unit and a precision that suits it, for
+    /// example "-6.5 dB", "1.2 kHz", "35 ms", "50 %" or "-inf dB". Enum values are shown by their
+    /// long name if they have one.
+    pub fn format_value(&self, data: &WingNodeData) -> String {
+        match self.node_type {
+            NodeType::Node => String::new(),
+            NodeType::String => data.get_string(),
+            NodeType::StringEnum => {
+                let value = data.get_string();
+                self.string_enum.iter().flatten()
+                    .find(|i| i.item == value && !i.long_item.is_empty())
+                    .map(|i| i.long_item.clone())
+                    .unwrap_or(value)
+            }
+            NodeType::FloatEnum => {
+                let Some(value) = data.to_float() else { return data.get_string() };
+                self.float_enum.iter().flatten()
+                    .find(|i| i.item == value && !i.long_item.is_empty())
+                    .map(|i| i.long_item.clone())
+                    .unwrap_or_else(|| self.format_number(value))
+            }
+            NodeType::Integer => match data.to_int() {
+                Some(value) => with_unit(value.to_string(), self.unit.symbol()),
+                None => data.get_string(),
+            },
+            NodeType::LinearFloat |
+            NodeType::LogarithmicFloat |
+            NodeType::FaderLevel => match data.to_float() {
+                Some(value) => self.format_number(value),
+                None => data.get_string(),
+            },
+        }
+    }
+
+    fn format_number(&self, value: f32) -> String {
+        let is_floor = self.node_type == NodeType::FaderLevel && value <= FADER_MIN_DB;
+        if is_floor || value == f32::NEG_INFINITY {
+            return with_unit("-inf".to_string(), self.unit.symbol());
+        }
+        match self.unit {
+            NodeUnit::Hertz if value.abs() >= 1000.0 => {
+                with_unit(format_decimals(value / 1000.0, if value.abs() >= 10000.0 { 1 } else { 2 }), "kHz")
+            }
+            NodeUnit::Db => with_unit(format_decimals(value, 1), "dB"),
+            NodeUnit::Percent => with_unit(format_decimals(value, if value.abs() >= 10.0 { 0 } else { 1 }), "%"),
+            unit => {
+                let decimals = self.step_decimals().unwrap_or(
+                    if value.abs() >= 100.0 { 0 } else if value.abs() >= 10.0 { 1 } else { 2 });
+                with_unit(format_decimals(value, decimals), unit.symbol())
+            }
+        }
+    }
+
+    /// The number of decimals needed to show the steps of a linear float
+    fn step_decimals(&self) -> Option<usize> {
+        if self.node_type != NodeType::LinearFloat {
+            return None;
+        }
+        let steps = self.steps.filter(|s| *s > 1)?;
+        let step = ((self.max_float? - self.min_float?) / (steps - 1) as f32).abs();
+        if step <= 0.0 {
+            return None;
+        }
+        Some((-step.log10()).ceil().clamp(0.0, 3.0) as usize)
+    }
+
+    /// Parses a value typed by a person into a property value. Understands the property's unit and
+    /// related ones ("1.2 kHz" or "1200" for Hz, "0.5 s" for ms), by symbol or by name ("3 meters",
+    /// "0.5 seconds"), "-inf" for levels, and enum items
+    /// by name or long name. Returns `None` if the text is not a valid value for the property.
+    /// The value is not clamped to the property's range.
+    pub fn parse_value(&self, s: &str) -> Option<WingNodeData> {
+        let s = s.trim();
+        match self.node_type {
+            NodeType::Node => None,
+            NodeType::String => Some(WingNodeData::with_string(s.to_string())),
+            NodeType::StringEnum => {
+                let items = self.string_enum.as_ref()?;
+                items.iter().find(|i| i.item == s)
+                    .or_else(|| items.iter().find(|i| i.item.eq_ignore_ascii_case(s) || i.long_item.eq_ignore_ascii_case(s)))
+                    .map(|i| WingNodeData::with_string(i.item.clone()))
+            }
+            NodeType::FloatEnum => {
+                let items = self.float_enum.as_ref()?;
+                if let Some(i) = items.iter().find(|i| !i.long_item.is_empty() && i.long_item.eq_ignore_ascii_case(s)) {
+                    return Some(WingNodeData::with_float(i.item));
+                }
+                let value = self.parse_number(s)?;
+                items.iter()
+                    .find(|i| (i.item - value).abs() <= 1e-4 * value.abs().max(1.0))
+                    .map(|i| WingNodeData::with_float(i.item))
+            }
+            NodeType::Integer => {
+                let value = self.parse_number(s)?;
+                if value.fract() != 0.0 {
+                    return None;
+                }
+                Some(WingNodeData::with_i32(value as i32))
+            }
+            NodeType::LinearFloat |
+            NodeType::LogarithmicFloat |
+            NodeType::FaderLevel => Some(WingNodeData::with_float(self.parse_number(s)?)),
+        }
+    }
+
+    fn parse_number(&self, s: &str) -> Option<f32> {
+        let lower = s.to_ascii_lowercase();
+        let (number, suffix) = if let Some(rest) = lower.strip_prefix("-inf") {
+            let floor = if self.node_type == NodeType::FaderLevel { FADER_MIN_DB } else { self.min_float? };
+            (floor, rest.trim())
+        } else {
+            // the number ends where the unit starts; "e" only counts as an exponent before a digit
+            let bytes = lower.as_bytes();
+            let split = (0..bytes.len())
+                .find(|&i| {
+                    let c = bytes[i];
+                    !(c.is_ascii_digit() || c == b'.' || c == b'-' || c == b'+' ||
+                      c == b'e' && bytes.get(i + 1).is_some_and(|n| n.is_ascii_digit() || *n == b'-'))
+                })
+                .unwrap_or(bytes.len());
+            (lower[..split].trim().parse::<f32>().ok()?, lower[split..].trim())
+        };
+
+        // the unit of the suffix, by symbol or by the name as_str() gives it, and its scale
+        let (unit, scale) = match suffix {
+            "" => return Some(number),
+            "db" => (NodeUnit::Db, 1.0),
+            "%" | "percent" => (NodeUnit::Percent, 1.0),
+            "ms" | "millisecond" | "milliseconds" => (NodeUnit::Milliseconds, 1.0),
+            "hz" | "hertz" => (NodeUnit::Hertz, 1.0),
+            "khz" | "kilohertz" => (NodeUnit::Hertz, 1000.0),
+            "m" | "meter" | "meters" | "metre" | "metres" => (NodeUnit::Meters, 1.0),
+            "s" | "sec" | "second" | "seconds" => (NodeUnit::Seconds, 1.0),
+            "oct" | "octave" | "octaves" => (NodeUnit::Octaves, 1.0),
+            _ => return None,
+        };
+        // the factor that converts that unit to the property's unit
+        let factor = match (unit, self.unit) {
+            (from, to) if from == to => 1.0,
+            (NodeUnit::Seconds, NodeUnit::Milliseconds) => 1000.0,
+            (NodeUnit::Milliseconds, NodeUnit::Seconds) => 0.001,
+            _ => return None,
+        };
+        Some(number * scale * factor)
+    }
+}
+
+fn with_unit(value: String, symbol: &str) -> String {
+    if symbol.is_empty() { value } else { format!("{} {}", value, symbol) }
+}
+
+/// Formats with at most `decimals` decimals, dropping trailing zeros
+fn format_decimals(value: f32, decimals: usize) -> String {
+    let s = format!("{:.*}", decimals, value);
+    let s = if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.').to_string() } else { s };
+    if s == "-0" { "0".to_string() } else { s }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNITS: [NodeUnit; 8] = [
+        NodeUnit::None, NodeUnit::Db, NodeUnit::Percent, NodeUnit::Milliseconds,
+        NodeUnit::Hertz, NodeUnit::Meters, NodeUnit::Seconds, NodeUnit::Octaves,
+    ];
+
+    fn def(json: jzon::JsonValue) -> WingNodeDef {
+        WingNodeDef::from_json(&json).unwrap()
+    }
+
+    fn float_def(node_type: NodeType, unit: NodeUnit, min: f32, max: f32) -> WingNodeDef {
+        def(jzon::object!{ "id": 1, "type": node_type.as_str(), "unit": unit.as_str(), "minfloat": min, "maxfloat": max })
+    }
+
+    fn close(a: Option<f32>, b: f32) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-3)
+    }
+
+    #[test]
+    fn fader_law() {
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level", "unit": "dB" });
+        for (pos, db) in FADER_LAW {
+            assert!(close(fdr.to_normalized(db), pos), "{} dB", db);
+            assert!(close(fdr.from_normalized(pos), if pos == 0.0 { FADER_MIN_DB } else { db }), "{}", pos);
+        }
+        assert!(close(fdr.to_normalized(-20.0), 0.375));
+        assert!(close(fdr.from_normalized(0.75), 0.0));
+        for db in [-85.0, -45.0, -6.0, 0.0, 5.5] {
+            assert!(close(fdr.from_normalized(fdr.to_normalized(db).unwrap()), db), "{} dB", db);
+        }
+        // -inf and anything below the law's first segment are the bottom of the fader
+        assert_eq!(fdr.to_normalized(f32::NEG_INFINITY), Some(0.0));
+        assert_eq!(fdr.to_normalized(FADER_MIN_DB), Some(0.0));
+        assert_eq!(fdr.to_normalized(-100.0), Some(0.0));
+        assert_eq!(fdr.to_normalized(20.0), Some(1.0));
+        assert_eq!(fdr.from_normalized(-1.0), Some(FADER_MIN_DB));
+        assert_eq!(fdr.from_normalized(2.0), Some(FADER_MAX_DB));
+    }
+
+    #[test]
+    fn fader_range_limits_levels() {
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level", "minfloat": -30, "maxfloat": 0 });
+        assert!(close(fdr.to_normalized(-10.0), 0.5));
+        assert_eq!(fdr.to_normalized(-60.0), Some(0.25));
+        assert_eq!(fdr.from_normalized(1.0), Some(0.0));
+        assert_eq!(fdr.quantize(6.0), Some(0.0));
+        // a range that is upside down is ignored
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level", "minfloat": 5, "maxfloat": -5 });
+        assert_eq!(fdr.from_normalized(1.0), Some(FADER_MAX_DB));
+    }
+
+    #[test]
+    fn normalisation() {
+        let lin = float_def(NodeType::LinearFloat, NodeUnit::Milliseconds, 0.0, 500.0);
+        assert!(close(lin.to_normalized(125.0), 0.25));
+        assert!(close(lin.from_normalized(0.25), 125.0));
+        assert_eq!(lin.to_normalized(-10.0), Some(0.0));
+        assert_eq!(lin.to_normalized(600.0), Some(1.0));
+
+        let log = float_def(NodeType::LogarithmicFloat, NodeUnit::Hertz, 20.0, 20000.0);
+        assert!(close(log.to_normalized(632.4555), 0.5));
+        assert!(close(log.from_normalized(0.5).map(|f| f / 100.0), 6.324555));
+        assert_eq!(log.to_normalized(0.0), Some(0.0));
+
+        let int = def(jzon::object!{ "id": 1, "type": "integer", "minint": -4, "maxint": 4 });
+        assert!(close(int.to_normalized(0.0), 0.5));
+        assert_eq!(int.from_normalized(0.6), Some(1.0));
+
+        let en = def(jzon::object!{ "id": 1, "type": "float enum", "items": [{"item": 44100}, {"item": 48000}, {"item": 96000}] });
+        assert_eq!(en.to_normalized(48000.0), Some(0.5));
+        assert_eq!(en.to_normalized(50000.0), Some(0.5));
+        assert_eq!(en.from_normalized(0.9), Some(96000.0));
+
+        // no range, or no scale at all
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "linear float" }).to_normalized(1.0), None);
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "string" }).to_normalized(1.0), None);
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "float enum", "items": [] }).to_normalized(1.0), None);
+        assert_eq!(float_def(NodeType::LinearFloat, NodeUnit::None, 1.0, 1.0).to_normalized(1.0), Some(0.0));
+    }
+
+    #[test]
+    fn normalised_data() {
+        let en = def(jzon::object!{ "id": 1, "type": "string enum", "items": [{"item": "A"}, {"item": "B"}, {"item": "C"}] });
+        assert_eq!(en.data_to_normalized(&WingNodeData::with_string("C".into())), Some(1.0));
+        assert_eq!(en.data_to_normalized(&WingNodeData::with_string("D".into())), None);
+        assert_eq!(en.normalized_to_data(0.4).map(|d| d.get_string()), Some("B".into()));
+        let int = def(jzon::object!{ "id": 1, "type": "integer", "minint": 0, "maxint": 10 });
+        assert_eq!(int.normalized_to_data(0.26).and_then(|d| d.to_int()), Some(3));
+    }
+
+    #[test]
+    fn quantize() {
+        let lin = def(jzon::object!{ "id": 1, "type": "linear float", "minfloat": 0, "maxfloat": 10, "steps": 11 });
+        assert!(close(lin.quantize(3.4), 3.0));
+        assert!(close(lin.quantize(3.6), 4.0));
+        assert_eq!(lin.quantize(12.0), Some(10.0));
+        // steps are even on a logarithmic scale
+        let log = def(jzon::object!{ "id": 1, "type": "log float", "minfloat": 10, "maxfloat": 1000, "steps": 3 });
+        assert!(close(log.quantize(80.0), 100.0));
+        assert!(close(log.quantize(20.0), 10.0));
+        // without steps, only clamped, also with min and max swapped
+        let free = float_def(NodeType::LinearFloat, NodeUnit::None, 5.0, -5.0);
+        assert_eq!(free.quantize(1.234), Some(1.234));
+        assert_eq!(free.quantize(-9.0), Some(-5.0));
+        let int = def(jzon::object!{ "id": 1, "type": "integer", "minint": 0, "maxint": 3 });
+        assert_eq!(int.quantize(1.5), Some(2.0));
+        assert_eq!(int.quantize(7.0), Some(3.0));
+        let en = def(jzon::object!{ "id": 1, "type": "float enum", "items": [{"item": 0.5}, {"item": 1}, {"item": 2}] });
+        assert_eq!(en.quantize(1.4), Some(1.0));
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level" });
+        assert_eq!(fdr.quantize(f32::NEG_INFINITY), Some(FADER_MIN_DB));
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "string enum", "items": [] }).quantize(1.0), None);
+    }
+
+    #[test]
+    fn format_parse_round_trip_for_every_unit() {
+        for unit in UNITS {
+            for node_type in [NodeType::LinearFloat, NodeType::LogarithmicFloat] {
+                let def = float_def(node_type, unit, 1.0, 20000.0);
+                for value in [1.0, 2.5, 12.3, 440.0, 1250.0, 19000.0] {
+                    let text = def.format_value(&WingNodeData::with_float(value));
+                    let parsed = def.parse_value(&text)
+                        .unwrap_or_else(|| panic!("{:?} {:?}: can't parse {:?}", node_type, unit, text));
+                    // formatting rounds to a few digits, and the rounded value formats the same
+                    assert!((parsed.get_float() - value).abs() <= value * 0.05, "{:?}: {} -> {:?} -> {:?}", unit, value, text, parsed);
+                    assert_eq!(def.format_value(&parsed), text, "{:?}", unit);
+                }
+            }
+            let def = def(jzon::object!{ "id": 1, "type": "integer", "unit": unit.as_str(), "minint": -10, "maxint": 10 });
+            let text = def.format_value(&WingNodeData::with_i32(-7));
+            assert_eq!(def.parse_value(&text).and_then(|d| d.to_int()), Some(-7), "{:?}: {:?}", unit, text);
+        }
+    }
+
+    fn format(def: &WingNodeDef, value: f32) -> String {
+        def.format_value(&WingNodeData::with_float(value))
+    }
+
+    fn parse(def: &WingNodeDef, s: &str) -> Option<f32> {
+        def.parse_value(s).map(|d| d.get_float())
+    }
+
+    #[test]
+    fn format_values() {
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level", "unit": "dB" });
+        assert_eq!(format(&fdr, -6.54), "-6.5 dB");
+        assert_eq!(format(&fdr, FADER_MIN_DB), "-inf dB");
+        assert_eq!(format(&fdr, f32::NEG_INFINITY), "-inf dB");
+        assert_eq!(format(&fdr, -0.01), "0 dB");
+        let hz = float_def(NodeType::LogarithmicFloat, NodeUnit::Hertz, 20.0, 20000.0);
+        assert_eq!(format(&hz, 440.0), "440 Hz");
+        assert_eq!(format(&hz, 1234.0), "1.23 kHz");
+        assert_eq!(format(&hz, 12500.0), "12.5 kHz");
+        let pct = float_def(NodeType::LinearFloat, NodeUnit::Percent, 0.0, 100.0);
+        assert_eq!(format(&pct, 5.26), "5.3 %");
+        assert_eq!(format(&pct, 50.0), "50 %");
+        let steps = def(jzon::object!{ "id": 1, "type": "linear float", "unit": "ms", "minfloat": 0, "maxfloat": 10, "steps": 101 });
+        assert_eq!(format(&steps, 2.5), "2.5 ms");
+        assert_eq!(format(&float_def(NodeType::LinearFloat, NodeUnit::None, 0.0, 1.0), 0.123), "0.12");
+        let en = def(jzon::object!{ "id": 1, "type": "string enum", "items": [{"item": "HPF", "longitem": "High Pass"}, {"item": "LPF"}] });
+        assert_eq!(en.format_value(&WingNodeData::with_string("HPF".into())), "High Pass");
+        assert_eq!(en.format_value(&WingNodeData::with_string("LPF".into())), "LPF");
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "node" }).format_value(&WingNodeData::with_float(1.0)), "");
+        // values of the wrong kind are shown as they are
+        assert_eq!(fdr.format_value(&WingNodeData::with_string("x".into())), "x");
+    }
+
+    #[test]
+    fn parse_values() {
+        let fdr = def(jzon::object!{ "id": 1, "type": "fader level", "unit": "dB" });
+        assert_eq!(parse(&fdr, "-6 dB"), Some(-6.0));
+        assert_eq!(parse(&fdr, " -6dB "), Some(-6.0));
+        assert_eq!(parse(&fdr, "-inf"), Some(FADER_MIN_DB));
+        assert_eq!(parse(&fdr, "-INF dB"), Some(FADER_MIN_DB));
+        assert_eq!(parse(&fdr, "-6 Hz"), None);
+        assert_eq!(parse(&fdr, "loud"), None);
+        assert_eq!(parse(&fdr, ""), None);
+        // -inf is the bottom of the range for other levels, and there is none without a range
+        let gain = float_def(NodeType::LinearFloat, NodeUnit::Db, -80.0, 0.0);
+        assert_eq!(parse(&gain, "-inf"), Some(-80.0));
+        assert_eq!(parse(&def(jzon::object!{ "id": 1, "type": "linear float", "unit": "dB" }), "-inf"), None);
+
+        let hz = float_def(NodeType::LogarithmicFloat, NodeUnit::Hertz, 20.0, 20000.0);
+        assert_eq!(parse(&hz, "1.2k Hz"), None);
+        assert_eq!(parse(&hz, "1.2kHz"), Some(1200.0));
+        assert_eq!(parse(&hz, "1e3"), Some(1000.0));
+        assert_eq!(parse(&hz, "2e-1 kHz"), Some(200.0));
+        let s = float_def(NodeType::LinearFloat, NodeUnit::Seconds, 0.0, 10.0);
+        assert_eq!(parse(&s, "250 ms"), Some(0.25));
+
+        let int = def(jzon::object!{ "id": 1, "type": "integer", "minint": 0, "maxint": 10 });
+        assert_eq!(int.parse_value("3").and_then(|d| d.to_int()), Some(3));
+        assert!(int.parse_value("1.5").is_none());
+
+        let en = def(jzon::object!{ "id": 1, "type": "string enum", "items": [{"item": "HPF", "longitem": "High Pass"}, {"item": "hpf2"}] });
+        assert_eq!(en.parse_value("High pass").map(|d| d.get_string()), Some("HPF".into()));
+        assert_eq!(en.parse_value("hpf").map(|d| d.get_string()), Some("HPF".into()));
+        assert_eq!(en.parse_value("HPF2").map(|d| d.get_string()), Some("hpf2".into()));
+        assert!(en.parse_value("BPF").is_none());
+
+        let rate = def(jzon::object!{ "id": 1, "type": "float enum", "items": [{"item": 44100, "longitem": "44.1k"}, {"item": 48000}] });
+        assert_eq!(parse(&rate, "44.1K"), Some(44100.0));
+        assert_eq!(parse(&rate, "48000"), Some(48000.0));
+        assert_eq!(parse(&rate, "96000"), None);
+
+        assert_eq!(def(jzon::object!{ "id": 1, "type": "string" }).parse_value(" a b ").map(|d| d.get_string()), Some("a b".into()));
+        assert!(def(jzon::object!{ "id": 1, "type": "node" }).parse_value("1").is_none());
+    }
+
+    #[test]
+    fn parse_accepts_unit_names() {
+        for unit in UNITS {
+            let def = float_def(NodeType::LinearFloat, unit, 0.0, 100.0);
+            let text = format!("3 {}", unit.as_str());
+            assert_eq!(def.parse_value(&text).map(|d| d.get_float()), Some(3.0), "{:?}", text);
+        }
+        let ms = float_def(NodeType::LinearFloat, NodeUnit::Milliseconds, 0.0, 500.0);
+        assert_eq!(ms.parse_value("0.5 seconds").map(|d| d.get_float()), Some(500.0));
+        assert_eq!(ms.parse_value("0.5 s").map(|d| d.get_float()), Some(500.0));
+        let hz = float_def(NodeType::LogarithmicFloat, NodeUnit::Hertz, 20.0, 20000.0);
+        assert_eq!(hz.parse_value("1.2 kHz").map(|d| d.get_float()), Some(1200.0));
+        assert!(hz.parse_value("1.2 ms").is_none());
+        let m = float_def(NodeType::LinearFloat, NodeUnit::Meters, 0.0, 100.0);
+        assert_eq!(m.parse_value("3 M").map(|d| d.get_float()), Some(3.0));
+        assert!(m.parse_value("3 seconds").is_none());
+    }
 }
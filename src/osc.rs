@@ -0,0 +1,625 @@
+//! The OSC protocol, for when the Native protocol can't be used.
+//!
+//! The Wing also speaks OSC on UDP port 2223. The OSC address of a property is its full name
+//! from the propmap, like `/ch/1/fdr`, so `OscConsole` takes the same ids and names as
+//! `WingConsole` and returns the same `WingResponse` and `WingNodeData` values. Only the model
+//! segments of FX, EQ and dynamics properties are left out: `/fx/1/HALL/3` is `/fx/1/3` over OSC,
+//! whichever model is loaded. As OSC doesn't tell which model that is, ids and addresses shared
+//! by several models are an error where the model matters; use the full name with the model.
+//!
+//! ```ignore
+//! let mut osc = OscConsole::connect(Some("192.168.1.100"))?;
+//! osc.set_float(WingConsole::name_to_id("/ch/1/fdr").unwrap(), -10.0)?;
+//! println!("{}", osc.get("/ch/1/name")?.get_string());
+//! osc.subscribe()?;
+//! loop {
+//!     if let WingResponse::NodeData(id, data) = osc.read()? { ... }
+//! }
+//! ```
+//!
+//! To get a value, a message with the address and no arguments is sent, and the Wing answers
+//! with the value as text followed by numbers, the last one being the value itself. A message
+//! with one argument sets the value. After a `/*s` message the Wing sends every change for 10
+//! seconds, so `OscConsole` renews it while subscribed. Node definitions and meters are only
+//! available through the Native protocol.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::{Result, Error, WingResponse};
+use crate::console::WingConsole;
+use crate::discovery::Discovery;
+use crate::node::{WingNodeDef, WingNodeData, NodeType};
+use crate::propmap::NAME_TO_DEF;
+use crate::schema::PathPattern;
+
+/// UDP port of the OSC protocol
+pub const OSC_PORT: u16 = 2223;
+
+/// Address that asks the Wing to send changes for the next 10 seconds
+pub const SUBSCRIBE_ADDRESS: &str = "/*s";
+
+/// How often a subscription is renewed
+const SUBSCRIBE_RENEW: Duration = Duration::from_secs(5);
+
+lazy_static::lazy_static! {
+    /// The full names of the properties at each OSC address. An address of a model-specific
+    /// property has one for each model.
+    static ref ADDRESS_TO_NAMES: HashMap<String, Vec<String>> = {
+        let mut names = HashMap::<String, Vec<String>>::new();
+        for fullname in NAME_TO_DEF.keys() {
+            names.entry(WingConsole::strip_models(fullname)).or_default().push(fullname.clone());
+        }
+        for list in names.values_mut() {
+            list.sort();
+        }
+        names
+    };
+}
+/// How long `get()` waits for an answer
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_PACKET: usize = 65536;
+
+/// An argument of an OSC message
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+}
+
+/// An OSC message
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(address: &str, args: Vec<OscArg>) -> Self {
+        OscMessage { address: address.to_string(), args }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_string(&mut buf, &self.address);
+        let mut tags = String::from(",");
+        for arg in &self.args {
+            tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Blob(_) => 'b',
+            });
+        }
+        write_string(&mut buf, &tags);
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(i) => buf.extend_from_slice(&i.to_be_bytes()),
+                OscArg::Float(f) => buf.extend_from_slice(&f.to_be_bytes()),
+                OscArg::String(s) => write_string(&mut buf, s),
+                OscArg::Blob(b) => {
+                    buf.extend_from_slice(&(b.len() as i32).to_be_bytes());
+                    buf.extend_from_slice(b);
+                    buf.resize(buf.len().next_multiple_of(4), 0);
+                }
+            }
+        }
+        buf
+    }
+
+    /// Decodes a packet, which is a message or a bundle of them. The messages of a bundle are
+    /// returned in order, and their time tags are ignored.
+    pub fn decode_packet(packet: &[u8]) -> Result<Vec<OscMessage>> {
+        let mut messages = Vec::new();
+        decode_into(packet, &mut messages)?;
+        Ok(messages)
+    }
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<OscMessage>) -> Result<()> {
+    let mut r = Reader { buf: packet, pos: 0 };
+    if packet.starts_with(b"#bundle\0") {
+        r.pos = 16; // "#bundle" and the time tag
+        while r.pos < packet.len() {
+            let len = r.int()?;
+            let element = r.bytes(usize::try_from(len).map_err(|_| osc_error("negative bundle element size"))?)?;
+            decode_into(element, messages)?;
+        }
+        return Ok(());
+    }
+
+    let address = r.string()?;
+    if !address.starts_with('/') {
+        return Err(osc_error(&format!("invalid address {:?}", address)));
+    }
+    // a message without a type tag string has no arguments
+    let tags = if r.pos < packet.len() { r.string()? } else { ",".to_string() };
+    let Some(tags) = tags.strip_prefix(',') else { return Err(osc_error("missing type tags")) };
+    let mut args = Vec::new();
+    for tag in tags.chars() {
+        args.push(match tag {
+            'i' => OscArg::Int(r.int()?),
+            'f' => OscArg::Float(f32::from_bits(r.int()? as u32)),
+            's' | 'S' => OscArg::String(r.string()?),
+            'b' => {
+                let len = usize::try_from(r.int()?).map_err(|_| osc_error("negative blob size"))?;
+                let blob = r.bytes(len)?.to_vec();
+                r.pos = r.pos.next_multiple_of(4);
+                OscArg::Blob(blob)
+            }
+            'T' => OscArg::Int(1),
+            'F' => OscArg::Int(0),
+            'N' | 'I' => continue,
+            t => return Err(osc_error(&format!("unsupported type tag '{}'", t))),
+        });
+    }
+    messages.push(OscMessage { address, args });
+    Ok(())
+}
+
+fn osc_error(message: &str) -> Error {
+    Error::OscError(message.to_string())
+}
+
+/// Writes a string with a terminating zero, padded to 4 bytes
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.buf.len()).ok_or_else(|| osc_error("truncated packet"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn int(&mut self) -> Result<i32> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let rest = &self.buf[self.pos.min(self.buf.len())..];
+        let len = rest.iter().position(|b| *b == 0).ok_or_else(|| osc_error("unterminated string"))?;
+        let s = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos = (self.pos + len + 1).next_multiple_of(4).min(self.buf.len());
+        Ok(s)
+    }
+}
+
+/// The OSC address of a property, its full name without model segments, or `None` if the name
+/// is not in the propmap
+pub fn name_to_address(fullname: &str) -> Option<String> {
+    WingConsole::name_to_def(fullname).map(|_| WingConsole::strip_models(fullname))
+}
+
+/// The properties an OSC address points to, with their full names: one for most addresses, and
+/// one for each model at the address of a model-specific property. A full name with its model
+/// segments points to that model's property only. A trailing `/` is ignored.
+pub fn address_to_defs(address: &str) -> Vec<(String, WingNodeDef)> {
+    let name = address.strip_suffix('/').filter(|a| !a.is_empty()).unwrap_or(address);
+    if let Some(def) = WingConsole::name_to_def(name) {
+        return vec![(name.to_string(), def.clone())];
+    }
+    ADDRESS_TO_NAMES.get(name).into_iter().flatten()
+        .filter_map(|n| WingConsole::name_to_def(n).map(|def| (n.clone(), def.clone())))
+        .collect()
+}
+
+/// The property an OSC address points to, with its full name, or `None` if it is not in the
+/// propmap or is shared by several models. See `address_to_defs()`.
+pub fn address_to_def(address: &str) -> Option<(String, WingNodeDef)> {
+    let mut defs = address_to_defs(address);
+    if defs.len() == 1 { defs.pop() } else { None }
+}
+
+/// The OSC addresses that match an OSC address pattern, sorted. See `schema::PathPattern`.
+pub fn find_addresses(pattern: &str) -> Result<Vec<String>> {
+    let pattern = PathPattern::parse(pattern)?;
+    let mut found: Vec<String> = ADDRESS_TO_NAMES.keys().filter(|a| pattern.matches(a)).cloned().collect();
+    found.sort();
+    Ok(found)
+}
+
+/// Converts the arguments of an OSC message to a value of the property
+pub fn args_to_data(def: &WingNodeDef, args: &[OscArg]) -> Option<WingNodeData> {
+    let string = args.iter().find_map(|a| match a { OscArg::String(s) => Some(s), _ => None });
+    let number = args.iter().rev().find_map(|a| match a {
+        OscArg::Float(f) => Some(*f),
+        OscArg::Int(i) => Some(*i as f32),
+        _ => None,
+    });
+    match def.node_type {
+        NodeType::Node => None,
+        NodeType::Integer => args.iter().rev()
+            .find_map(|a| match a { OscArg::Int(i) => Some(WingNodeData::with_i32(*i)), _ => None })
+            .or_else(|| number.map(|f| WingNodeData::with_i32(f.round() as i32)))
+            .or_else(|| string.and_then(|s| def.parse_value(s))),
+        NodeType::StringEnum | NodeType::String => string.map(|s| WingNodeData::with_string(s.clone()))
+            .or_else(|| number.map(|f| WingNodeData::with_string(f.to_string()))),
+        NodeType::LinearFloat | NodeType::LogarithmicFloat | NodeType::FaderLevel | NodeType::FloatEnum =>
+            number.map(WingNodeData::with_float)
+            .or_else(|| string.and_then(|s| def.parse_value(s))),
+    }
+}
+
+/// Converts a value of the property to the argument that sets it over OSC. A value given as a
+/// string, like "-6 dB", is parsed with `WingNodeDef::parse_value()` first.
+pub fn data_to_arg(def: &WingNodeDef, data: &WingNodeData) -> Option<OscArg> {
+    let parsed;
+    let data = if data.has_string() && !data.has_float() && !data.has_int()
+        && !matches!(def.node_type, NodeType::StringEnum | NodeType::String) {
+        parsed = def.parse_value(&data.get_string())?;
+        &parsed
+    } else {
+        data
+    };
+    match def.node_type {
+        NodeType::Node => None,
+        NodeType::Integer => data.to_int().map(OscArg::Int),
+        NodeType::StringEnum | NodeType::String => Some(OscArg::String(data.get_string())),
+        NodeType::LinearFloat | NodeType::LogarithmicFloat | NodeType::FaderLevel | NodeType::FloatEnum =>
+            data.to_float().map(OscArg::Float),
+    }
+}
+
+/// The arguments the Wing answers with for a value: the value as text, and for numbers the
+/// 0.0 to 1.0 position and the value itself
+pub fn data_to_args(def: &WingNodeDef, data: &WingNodeData) -> Vec<OscArg> {
+    let position = OscArg::Float(def.data_to_normalized(data).unwrap_or(0.0));
+    match def.node_type {
+        NodeType::Node => vec![],
+        NodeType::StringEnum | NodeType::String => vec![OscArg::String(data.get_string())],
+        NodeType::Integer => vec![OscArg::String(def.format_value(data)), position, OscArg::Int(data.to_int().unwrap_or(0))],
+        NodeType::LinearFloat | NodeType::LogarithmicFloat | NodeType::FaderLevel | NodeType::FloatEnum =>
+            vec![OscArg::String(def.format_value(data)), position, OscArg::Float(data.to_float().unwrap_or(0.0))],
+    }
+}
+
+/// A connection to a Wing over OSC, with the same get, set and subscribe calls as `WingConsole`
+pub struct OscConsole {
+    socket: UdpSocket,
+    /// when the subscription has to be renewed, if subscribed
+    renew: Option<Instant>,
+    /// messages received while waiting for an answer, returned by `read()` later
+    pending: VecDeque<OscMessage>,
+    timeout: Duration,
+}
+
+impl OscConsole {
+    /// Connects to the console at `host_or_ip`, or to the first console that answers discovery
+    pub fn connect(host_or_ip: Option<&str>) -> Result<Self> {
+        let host = match host_or_ip {
+            Some(host) => host.to_string(),
+            None => Discovery::new().stop_on_first(true).scan()?
+                .into_iter().next().ok_or(Error::DiscoveryError)?.ip,
+        };
+        let addr = (host.as_str(), OSC_PORT).to_socket_addrs()?
+            .find(|a| a.is_ipv4())
+            .ok_or(Error::ConnectionError)?;
+        OscConsole::connect_addr(addr)
+    }
+
+    /// Connects to an OSC server at any address and port, for example an `OscBridge`
+    pub fn connect_addr(addr: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+        socket.connect(addr)?;
+        Ok(OscConsole { socket, renew: None, pending: VecDeque::new(), timeout: DEFAULT_TIMEOUT })
+    }
+
+    /// How long `get()` and `get_data()` wait for an answer. Default is 1 second.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn send(&self, message: &OscMessage) -> Result<()> {
+        self.socket.send(&message.encode())?;
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for the next message, or forever if `None`
+    pub fn recv(&mut self, timeout: Option<Duration>) -> Result<Option<OscMessage>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(Some(message));
+            }
+            self.renew_subscription()?;
+            let now = Instant::now();
+            if deadline.is_some_and(|d| d <= now) {
+                return Ok(None);
+            }
+            // wake up in time to renew the subscription
+            let wait = [deadline.map(|d| d - now), self.renew.map(|r| r.saturating_duration_since(now))]
+                .into_iter().flatten().min();
+            self.socket.set_read_timeout(wait.map(|w| w.max(Duration::from_millis(1))))?;
+            let mut buf = vec![0u8; MAX_PACKET];
+            match self.socket.recv(&mut buf) {
+                Ok(len) => self.pending.extend(OscMessage::decode_packet(&buf[..len])?),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Returns the next value the Wing sends, as an answer to a request or as a change while
+    /// subscribed. Messages for addresses that are not in the propmap are skipped, and so are
+    /// those for addresses whose properties differ between models in id or type.
+    pub fn read(&mut self) -> Result<WingResponse> {
+        loop {
+            let Some(message) = self.recv(None)? else { continue };
+            let defs = address_to_defs(&message.address);
+            let Some((_, def)) = defs.first() else { continue };
+            if defs.iter().any(|(_, d)| d.id != def.id || d.node_type != def.node_type) {
+                continue;
+            }
+            if let Some(data) = args_to_data(def, &message.args) {
+                return Ok(WingResponse::NodeData(def.id, data));
+            }
+        }
+    }
+
+    /// Asks the Wing to send every change until `unsubscribe()`
+    pub fn subscribe(&mut self) -> Result<()> {
+        self.send(&OscMessage::new(SUBSCRIBE_ADDRESS, vec![]))?;
+        self.renew = Some(Instant::now() + SUBSCRIBE_RENEW);
+        Ok(())
+    }
+
+    /// Stops renewing the subscription. The Wing stops sending changes within 10 seconds.
+    pub fn unsubscribe(&mut self) {
+        self.renew = None;
+    }
+
+    fn renew_subscription(&mut self) -> Result<()> {
+        if self.renew.is_some_and(|r| r <= Instant::now()) {
+            self.subscribe()?;
+        }
+        Ok(())
+    }
+
+    /// Requests the value of a property. The answer is returned by `read()`.
+    pub fn request_node_data(&mut self, id: i32) -> Result<()> {
+        let address = Self::id_to_address(id)?;
+        self.send(&OscMessage::new(&address, vec![]))
+    }
+
+    /// Requests the value of a property by full name or OSC address. The answer is returned by
+    /// `read()`.
+    pub fn request_data(&mut self, fullname: &str) -> Result<()> {
+        let address = address_to_defs(fullname).first()
+            .and_then(|(name, _)| name_to_address(name))
+            .ok_or_else(|| unknown(fullname))?;
+        self.send(&OscMessage::new(&address, vec![]))
+    }
+
+    /// Requests the value of a property and waits for it
+    pub fn get_data(&mut self, id: i32) -> Result<WingNodeData> {
+        let name = Self::id_to_name(id)?;
+        self.get(&name)
+    }
+
+    /// Requests the value of a property by full name and waits for it. Anything else that
+    /// arrives in the meantime is kept for `read()`.
+    pub fn get(&mut self, fullname: &str) -> Result<WingNodeData> {
+        let (name, def) = property(fullname)?;
+        let address = WingConsole::strip_models(&name);
+        self.send(&OscMessage::new(&address, vec![]))?;
+        let deadline = Instant::now() + self.timeout;
+        let mut others = Vec::new();
+        let mut result = Err(Error::OscError(format!("no answer for {}", name)));
+        while let Some(message) = self.recv(Some(deadline.saturating_duration_since(Instant::now())))? {
+            if message.address == address {
+                if let Some(data) = args_to_data(&def, &message.args) {
+                    result = Ok(data);
+                    break;
+                }
+            }
+            others.push(message);
+        }
+        for message in others.into_iter().rev() {
+            self.pending.push_front(message);
+        }
+        result
+    }
+
+    pub fn set_string(&mut self, id: i32, value: &str) -> Result<()> {
+        self.send(&OscMessage::new(&Self::id_to_address(id)?, vec![OscArg::String(value.to_string())]))
+    }
+
+    pub fn set_float(&mut self, id: i32, value: f32) -> Result<()> {
+        self.send(&OscMessage::new(&Self::id_to_address(id)?, vec![OscArg::Float(value)]))
+    }
+
+    pub fn set_int(&mut self, id: i32, value: i32) -> Result<()> {
+        self.send(&OscMessage::new(&Self::id_to_address(id)?, vec![OscArg::Int(value)]))
+    }
+
+    /// Sets a property by full name, converting the value to the property's type
+    pub fn set(&mut self, fullname: &str, value: &WingNodeData) -> Result<()> {
+        let (name, def) = property(fullname)?;
+        self.set_data_at(&WingConsole::strip_models(&name), &name, &def, value)
+    }
+
+    /// Sets a property, converting the value to the property's type like `WingConsole::set_data()`
+    pub fn set_data(&mut self, def: &WingNodeDef, value: &WingNodeData) -> Result<()> {
+        let address = Self::id_to_address(def.id)?;
+        self.set_data_at(&address, &address, def, value)
+    }
+
+    fn set_data_at(&mut self, address: &str, name: &str, def: &WingNodeDef, value: &WingNodeData) -> Result<()> {
+        let arg = data_to_arg(def, value)
+            .ok_or_else(|| Error::OscError(format!("invalid value {:?} for {}", value.get_string(), name)))?;
+        self.send(&OscMessage::new(address, vec![arg]))
+    }
+
+    /// The OSC address of an id. The names of an id shared by several FX or EQ models usually
+    /// have the same address; if they don't, which one is meant depends on the model loaded, so
+    /// that is an error.
+    fn id_to_address(id: i32) -> Result<String> {
+        let defs = WingConsole::id_to_defs(id).unwrap_or_default();
+        let mut addresses: Vec<String> = defs.iter().map(|(name, _)| WingConsole::strip_models(name)).collect();
+        addresses.sort();
+        addresses.dedup();
+        match addresses.len() {
+            0 => Err(Error::IndexError(format!("unknown property id {}", id))),
+            1 => Ok(addresses.remove(0)),
+            _ => Err(Error::IndexError(format!("property id {} is at a different address for each model, use its full name", id))),
+        }
+    }
+
+    /// The full name of an id. An id shared by several FX or EQ models is a different property
+    /// for each, and OSC doesn't tell which model is loaded, so that is an error.
+    fn id_to_name(id: i32) -> Result<String> {
+        let mut defs = WingConsole::id_to_defs(id).unwrap_or_default();
+        match defs.len() {
+            0 => Err(Error::IndexError(format!("unknown property id {}", id))),
+            1 => Ok(defs.remove(0).0),
+            _ => Err(Error::IndexError(format!("property id {} is shared by several models, use its full name", id))),
+        }
+    }
+}
+
+/// The property at a full name or OSC address, or an error if it is unknown or is a different
+/// property for each model
+fn property(fullname: &str) -> Result<(String, WingNodeDef)> {
+    let mut defs = address_to_defs(fullname);
+    match defs.len() {
+        0 => Err(unknown(fullname)),
+        1 => Ok(defs.remove(0)),
+        _ => Err(Error::IndexError(format!("{} is a different property for each model, use its full name with the model", fullname))),
+    }
+}
+
+fn unknown(fullname: &str) -> Error {
+    Error::IndexError(format!("unknown property {}", fullname))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_one(packet: &[u8]) -> OscMessage {
+        let mut messages = OscMessage::decode_packet(packet).unwrap();
+        assert_eq!(messages.len(), 1);
+        messages.remove(0)
+    }
+
+    fn is_osc_error(packet: &[u8]) -> bool {
+        matches!(OscMessage::decode_packet(packet), Err(Error::OscError(_)))
+    }
+
+    #[test]
+    fn strings_are_padded_to_four_bytes() {
+        assert_eq!(OscMessage::new("/ch", vec![]).encode(), b"/ch\0,\0\0\0");
+        assert_eq!(OscMessage::new("/abc", vec![]).encode(), b"/abc\0\0\0\0,\0\0\0");
+        let m = OscMessage::new("/a", vec![OscArg::String(String::new()), OscArg::String("xyz".into())]);
+        assert_eq!(m.encode(), b"/a\0\0,ss\0\0\0\0\0xyz\0");
+    }
+
+    #[test]
+    fn numbers_are_big_endian() {
+        let m = OscMessage::new("/a", vec![OscArg::Int(-2), OscArg::Float(1.0)]);
+        assert_eq!(m.encode(), b"/a\0\0,if\0\xff\xff\xff\xfe\x3f\x80\0\0");
+    }
+
+    #[test]
+    fn blobs_are_padded_to_four_bytes() {
+        let m = OscMessage::new("/a", vec![OscArg::Blob(vec![1, 2, 3, 4, 5]), OscArg::Blob(vec![]), OscArg::Int(7)]);
+        let packet = m.encode();
+        assert_eq!(packet, b"/a\0\0,bbi\0\0\0\0\0\0\0\x05\x01\x02\x03\x04\x05\0\0\0\0\0\0\0\0\0\0\x07");
+        assert_eq!(decode_one(&packet), m);
+    }
+
+    #[test]
+    fn round_trip() {
+        let m = OscMessage::new("/ch/1/name", vec![
+            OscArg::String("Vocals".into()), OscArg::Float(-6.5), OscArg::Int(i32::MIN), OscArg::Blob(vec![0; 4]),
+        ]);
+        assert_eq!(decode_one(&m.encode()), m);
+        assert_eq!(decode_one(&OscMessage::new("/", vec![]).encode()), OscMessage::new("/", vec![]));
+    }
+
+    #[test]
+    fn lenient_decoding() {
+        // no type tag string, and no padding after the last string
+        assert_eq!(decode_one(b"/ch/1/fdr\0"), OscMessage::new("/ch/1/fdr", vec![]));
+        assert_eq!(decode_one(b"/a\0\0,s\0\0abc\0").args, [OscArg::String("abc".into())]);
+        // true, false, nil and infinitum
+        assert_eq!(decode_one(b"/a\0\0,TFNI\0\0\0").args, [OscArg::Int(1), OscArg::Int(0)]);
+    }
+
+    #[test]
+    fn bundles() {
+        let a = OscMessage::new("/a", vec![OscArg::Int(1)]).encode();
+        let b = OscMessage::new("/b", vec![]).encode();
+        let mut inner = b"#bundle\0\0\0\0\0\0\0\0\x01".to_vec();
+        inner.extend_from_slice(&(b.len() as i32).to_be_bytes());
+        inner.extend_from_slice(&b);
+        let mut bundle = b"#bundle\0\0\0\0\0\0\0\0\x01".to_vec();
+        for element in [&a, &inner] {
+            bundle.extend_from_slice(&(element.len() as i32).to_be_bytes());
+            bundle.extend_from_slice(element);
+        }
+        let messages = OscMessage::decode_packet(&bundle).unwrap();
+        assert_eq!(messages.iter().map(|m| m.address.as_str()).collect::<Vec<_>>(), ["/a", "/b"]);
+        assert!(OscMessage::decode_packet(b"#bundle\0\0\0\0\0\0\0\0\x01").unwrap().is_empty());
+
+        // an element longer than the bundle, or with a negative size
+        let mut long = bundle.clone();
+        long.truncate(long.len() - 1);
+        assert!(is_osc_error(&long));
+        assert!(is_osc_error(b"#bundle\0\0\0\0\0\0\0\0\x01\xff\xff\xff\xff"));
+    }
+
+    #[test]
+    fn malformed_packets() {
+        assert!(is_osc_error(b""));
+        assert!(is_osc_error(b"/ch/1/fdr"));
+        assert!(is_osc_error(b"ch\0\0,\0\0\0"));
+        assert!(is_osc_error(b"/a\0\0i\0\0\0"));
+        assert!(is_osc_error(b"/a\0\0,x\0\0"));
+        assert!(is_osc_error(b"/a\0\0,s\0\0abc"));
+        // arguments cut off
+        let packet = OscMessage::new("/a", vec![OscArg::Float(1.0), OscArg::Blob(vec![1, 2, 3])]).encode();
+        for len in [8, 10, 12, 14, 17] {
+            assert!(is_osc_error(&packet[..len]), "{} bytes", len);
+        }
+        assert!(is_osc_error(b"/a\0\0,b\0\0\xff\xff\xff\xff"));
+        assert!(is_osc_error(b"/a\0\0,b\0\0\x7f\xff\xff\xff"));
+    }
+
+    #[test]
+    fn values_and_arguments() {
+        let fdr = WingNodeDef::from_json(&jzon::object!{ "id": 1, "type": "fader level", "unit": "dB" }).unwrap();
+        let on = WingNodeDef::from_json(&jzon::object!{ "id": 2, "type": "integer", "minint": 0, "maxint": 1 }).unwrap();
+        let mode = WingNodeDef::from_json(&jzon::object!{ "id": 3, "type": "string enum", "items": [{"item": "A"}, {"item": "B"}] }).unwrap();
+
+        // the Wing answers with text, position and value
+        let args = data_to_args(&fdr, &WingNodeData::with_float(-10.0));
+        assert_eq!(args, [OscArg::String("-10 dB".into()), OscArg::Float(0.5), OscArg::Float(-10.0)]);
+        assert_eq!(args_to_data(&fdr, &args).map(|d| d.get_float()), Some(-10.0));
+        assert_eq!(args_to_data(&fdr, &[OscArg::String("-6 dB".into())]).map(|d| d.get_float()), Some(-6.0));
+        assert_eq!(args_to_data(&on, &[OscArg::String("ON".into()), OscArg::Float(1.0), OscArg::Int(1)]).and_then(|d| d.to_int()), Some(1));
+        assert_eq!(args_to_data(&on, &[OscArg::Float(0.6)]).and_then(|d| d.to_int()), Some(1));
+        assert_eq!(args_to_data(&mode, &[OscArg::String("B".into())]).map(|d| d.get_string()), Some("B".into()));
+        assert!(args_to_data(&fdr, &[]).is_none());
+
+        assert_eq!(data_to_arg(&fdr, &WingNodeData::with_string("-inf".into())), Some(OscArg::Float(crate::node::FADER_MIN_DB)));
+        assert_eq!(data_to_arg(&on, &WingNodeData::with_i32(1)), Some(OscArg::Int(1)));
+        assert_eq!(data_to_arg(&mode, &WingNodeData::with_string("A".into())), Some(OscArg::String("A".into())));
+        assert_eq!(data_to_arg(&fdr, &WingNodeData::with_string("loud".into())), None);
+    }
+}
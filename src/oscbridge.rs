@@ -0,0 +1,185 @@
+//! An OSC server that controls a Wing over the Native protocol.
+//!
+//! Show control software like QLab, TouchOSC or Companion often only speaks OSC. `OscBridge`
+//! listens for OSC on a local UDP port and executes what it receives on a `WingConsole`:
+//!
+//! - a message with no arguments gets the value, which is sent back like the Wing would
+//! - a message with an argument sets the value
+//! - `/*s` subscribes the sender to changes for 10 seconds, like on the Wing
+//!
+//! Addresses are those of the Wing, property full names without the model segments of FX, EQ and
+//! dynamics properties (`/fx/1/3`, not `/fx/1/HALL/3`), and are executed on the property of the
+//! model currently loaded. They may be OSC address patterns like `/ch/*/mute` or `/bus/[1-4]/fdr`
+//! to get or set many properties at once. Changes made on the console, or by another OSC client,
+//! are sent to every subscribed client.
+//!
+//! ```ignore
+//! let wing = WingConsole::connect(None)?;
+//! OscBridge::new(wing, "0.0.0.0:2223".parse()?)?.run()?;
+//! ```
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{Result, WingResponse};
+use crate::console::WingConsole;
+use crate::node::{WingNodeDef, WingNodeData, NodeType};
+use crate::osc::{OscMessage, SUBSCRIBE_ADDRESS, address_to_defs, args_to_data, data_to_args, find_addresses, name_to_address};
+use crate::schema::PathPattern;
+
+const DEFAULT_SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PACKET: usize = 65536;
+
+#[derive(Default)]
+struct BridgeState {
+    /// subscribed clients, and when their subscription ends
+    subscribers: HashMap<SocketAddr, Instant>,
+    /// clients waiting for the value of an id, and the address they asked for
+    waiting: HashMap<i32, Vec<(SocketAddr, String)>>,
+}
+
+impl BridgeState {
+    fn subscribers(&mut self, except: Option<SocketAddr>) -> Vec<SocketAddr> {
+        let now = Instant::now();
+        self.subscribers.retain(|_, until| *until > now);
+        self.subscribers.keys().filter(|a| Some(**a) != except).cloned().collect()
+    }
+}
+
+/// Translates OSC to the Native protocol, see the module documentation
+pub struct OscBridge {
+    wing: WingConsole,
+    socket: UdpSocket,
+    subscription_timeout: Duration,
+    state: Arc<Mutex<BridgeState>>,
+}
+
+impl OscBridge {
+    /// Listens for OSC on `listen`, for example `0.0.0.0:2223`. Reads the current FX and EQ
+    /// models from the console, so ids shared by several models are sent with the right name.
+    pub fn new(mut wing: WingConsole, listen: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(listen)?;
+        wing.sync_models()?;
+        Ok(OscBridge { wing, socket, subscription_timeout: DEFAULT_SUBSCRIPTION_TIMEOUT, state: Default::default() })
+    }
+
+    /// How long a client stays subscribed after its last `/*s`. Default is 10 seconds.
+    pub fn subscription_timeout(mut self, timeout: Duration) -> Self {
+        self.subscription_timeout = timeout;
+        self
+    }
+
+    /// The address the bridge listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Runs the bridge until the connection to the console fails. Changes from the console are
+    /// read on a second thread.
+    pub fn run(mut self) -> Result<()> {
+        let (errors, failed) = mpsc::channel();
+        let reader = self.wing.clone();
+        let socket = self.socket.try_clone()?;
+        let state = self.state.clone();
+        std::thread::spawn(move || {
+            let mut wing = reader;
+            loop {
+                match wing.read() {
+                    Ok(WingResponse::NodeData(id, data)) => forward(&wing, &socket, &state, id, &data),
+                    Ok(_) => {}
+                    Err(e) => {
+                        let _ = errors.send(e);
+                        return;
+                    }
+                }
+            }
+        });
+
+        self.socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            if let Ok(e) = failed.try_recv() {
+                return Err(e);
+            }
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(r) => r,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+                // on Windows an ICMP port unreachable from a client that went away shows up here
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // a client sending garbage is not a reason to stop
+            let Ok(messages) = OscMessage::decode_packet(&buf[..len]) else { continue };
+            for message in messages {
+                self.handle(message, from)?;
+            }
+        }
+    }
+
+    fn handle(&mut self, message: OscMessage, from: SocketAddr) -> Result<()> {
+        if message.address == SUBSCRIBE_ADDRESS {
+            self.state.lock().unwrap().subscribers.insert(from, Instant::now() + self.subscription_timeout);
+            return Ok(());
+        }
+
+        let addresses = if PathPattern::is_pattern(&message.address) {
+            find_addresses(&message.address).unwrap_or_default()
+        } else {
+            vec![message.address.clone()]
+        };
+
+        for address in addresses {
+            let Some((name, def)) = self.property(&address) else { continue };
+            if message.args.is_empty() {
+                self.state.lock().unwrap().waiting.entry(def.id).or_default().push((from, address));
+                self.wing.request_node_data(def.id)?;
+            } else if let Some(data) = args_to_data(&def, &message.args).filter(|_| !def.read_only) {
+                self.wing.set_data(&def, &data)?;
+                // the console doesn't send our own changes back, so tell the other clients here
+                let subscribers = self.state.lock().unwrap().subscribers(Some(from));
+                let address = name_to_address(&name).unwrap_or(address);
+                send(&self.socket, &subscribers, &OscMessage::new(&address, data_to_args(&def, &data)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The property at an address. An address of a model-specific property is that property of
+    /// each model, of which only the one of the model currently loaded exists on the console.
+    fn property(&self, address: &str) -> Option<(String, WingNodeDef)> {
+        let mut props: Vec<(String, WingNodeDef)> = address_to_defs(address).into_iter()
+            .filter(|(name, def)| def.node_type != NodeType::Node && self.wing.has_current_models(name))
+            .collect();
+        if props.len() == 1 { props.pop() } else { None }
+    }
+}
+
+/// Sends a value from the console to the clients waiting for it, or if nobody asked for it, it is
+/// a change and goes to the subscribers
+fn forward(wing: &WingConsole, socket: &UdpSocket, state: &Mutex<BridgeState>, id: i32, data: &WingNodeData) {
+    // an id shared by several models is only sent once its model is known
+    let Some((name, def)) = wing.resolve_id(id) else { return };
+    let Some(address) = name_to_address(&name) else { return };
+    let args = data_to_args(&def, data);
+
+    let mut state = state.lock().unwrap();
+    match state.waiting.remove(&id) {
+        Some(waiting) => {
+            for (client, address) in waiting {
+                send(socket, &[client], &OscMessage::new(&address, args.clone()));
+            }
+        }
+        None => send(socket, &state.subscribers(None), &OscMessage::new(&address, args)),
+    }
+}
+
+fn send(socket: &UdpSocket, clients: &[SocketAddr], message: &OscMessage) {
+    let packet = message.encode();
+    for client in clients {
+        // a client that went away is dropped when its subscription runs out
+        let _ = socket.send_to(&packet, client);
+    }
+}
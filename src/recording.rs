@@ -0,0 +1,223 @@
+//! Recording of meter frames to a file, and replaying them.
+//!
+//! A `MeterRecorder` writes every frame it is given with the time it was recorded at, and the
+//! list of meters each meter id was requested with. Packets that can't be decoded are skipped, so
+//! one bad packet doesn't end a recording. A `MeterPlayer` reads the file back and
+//! returns the frames from `read_meters()` at the speed they were recorded, or faster, so code
+//! written against a `MeterSource` works the same on a recording as on a console.
+//!
+//! ```ignore
+//! let mut recorder = MeterRecorder::create("show.wmr")?;
+//! loop {
+//!     recorder.record_from(&mut wing)?;
+//! }
+//!
+//! let mut player = MeterPlayer::open("show.wmr")?.speed(4.0);
+//! while let Ok(frame) = player.read_meters() { ... }
+//! ```
+//!
+//! The file starts with the magic "WMTR" and a version byte, followed by records. Numbers are
+//! big endian like on the wire.
+//!
+//! - Layout: `0x01`, meter id (u16), meter count (u16), and the `Meter::code()` of each meter (u16)
+//! - Frame: `0x02`, time since the start of the recording in microseconds (u64), meter id (u16),
+//!   value count (u16), and the raw values in 1/256 dB (i16)
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::{Result, Error};
+use crate::console::Meter;
+use crate::meter::{MeterFrame, MeterSource};
+
+const MAGIC: &[u8; 4] = b"WMTR";
+const VERSION: u8 = 1;
+const LAYOUT: u8 = 1;
+const FRAME: u8 = 2;
+
+/// Writes meter frames to a recording
+pub struct MeterRecorder<W: Write> {
+    out: W,
+    start: Instant,
+    layouts: HashMap<u16, Vec<Meter>>,
+    skipped: usize,
+}
+
+impl MeterRecorder<BufWriter<File>> {
+    /// Creates a recording file, replacing it if it exists
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        MeterRecorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> MeterRecorder<W> {
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(MeterRecorder { out, start: Instant::now(), layouts: HashMap::new(), skipped: 0 })
+    }
+
+    /// Reads the next frame from a console or other source and records it. A packet that is
+    /// truncated or doesn't match the meters requested (`Error::MeterPacketError`) is skipped and
+    /// counted in `skipped()`; other errors, such as having no meters requested, are returned.
+    /// Returns whether a frame was recorded.
+    pub fn record_from<S: MeterSource>(&mut self, source: &mut S) -> Result<bool> {
+        match source.read_meters() {
+            Ok(frame) => {
+                self.record(&frame)?;
+                Ok(true)
+            }
+            Err(Error::MeterPacketError(_)) => {
+                self.skipped += 1;
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The number of packets `record_from()` skipped
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Records a frame that was just received
+    pub fn record(&mut self, frame: &MeterFrame) -> Result<()> {
+        self.record_at(frame, self.start.elapsed())
+    }
+
+    /// Records a frame at a time since the start of the recording
+    pub fn record_at(&mut self, frame: &MeterFrame, time: Duration) -> Result<()> {
+        let meters: Vec<Meter> = frame.meters.iter().map(|(m, _)| m.clone()).collect();
+        if self.layouts.get(&frame.id) != Some(&meters) {
+            let mut buf = vec![LAYOUT];
+            buf.extend_from_slice(&frame.id.to_be_bytes());
+            buf.extend_from_slice(&(meters.len() as u16).to_be_bytes());
+            for m in &meters {
+                buf.extend_from_slice(&m.code().to_be_bytes());
+            }
+            self.out.write_all(&buf)?;
+            self.layouts.insert(frame.id, meters);
+        }
+
+        let values = frame.encode();
+        let mut buf = Vec::with_capacity(13 + values.len() * 2);
+        buf.push(FRAME);
+        buf.extend_from_slice(&(time.as_micros() as u64).to_be_bytes());
+        buf.extend_from_slice(&frame.id.to_be_bytes());
+        buf.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        for v in values {
+            buf.extend_from_slice(&v.to_be_bytes());
+        }
+        self.out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Flushes the recording and returns the writer
+    pub fn finish(mut self) -> Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads the frames of a recording back
+pub struct MeterPlayer<R: Read> {
+    input: R,
+    layouts: HashMap<u16, Vec<Meter>>,
+    speed: f32,
+    /// when the first frame was returned, and the time it was recorded at
+    start: Option<(Instant, Duration)>,
+}
+
+impl MeterPlayer<BufReader<File>> {
+    /// Opens a recording file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        MeterPlayer::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> MeterPlayer<R> {
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut header = [0u8; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(Error::MeterError("not a meter recording".to_string()));
+        }
+        Ok(MeterPlayer { input, layouts: HashMap::new(), speed: 1.0, start: None })
+    }
+
+    /// Sets how fast `read_meters()` replays the recording: 1.0 is real time, 2.0 twice as fast,
+    /// and 0.0 as fast as the frames can be read
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Reads the next frame and the time it was recorded at, without waiting. Returns `None` at
+    /// the end of the recording.
+    pub fn next_frame(&mut self) -> Result<Option<(Duration, MeterFrame)>> {
+        loop {
+            let mut kind = [0u8; 1];
+            match self.input.read_exact(&mut kind) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            match kind[0] {
+                LAYOUT => {
+                    let id = self.read_u16()?;
+                    let count = self.read_u16()?;
+                    let mut meters = Vec::with_capacity(count as usize);
+                    for _ in 0..count {
+                        let code = self.read_u16()?;
+                        meters.push(Meter::from_code(code)
+                            .ok_or_else(|| Error::MeterError(format!("unknown meter {:#06x} in recording", code)))?);
+                    }
+                    self.layouts.insert(id, meters);
+                }
+                FRAME => {
+                    let mut time = [0u8; 8];
+                    self.input.read_exact(&mut time)?;
+                    let time = Duration::from_micros(u64::from_be_bytes(time));
+                    let id = self.read_u16()?;
+                    let count = self.read_u16()?;
+                    let mut values = Vec::with_capacity(count as usize);
+                    for _ in 0..count {
+                        values.push(self.read_u16()? as i16);
+                    }
+                    let meters = self.layouts.get(&id)
+                        .ok_or_else(|| Error::MeterError(format!("frame for meter id {} without a layout", id)))?;
+                    return Ok(Some((time, MeterFrame::decode(id, meters, &values)?)));
+                }
+                k => return Err(Error::MeterError(format!("unknown record type {} in recording", k))),
+            }
+        }
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        let mut b = [0u8; 2];
+        self.input.read_exact(&mut b)?;
+        Ok(u16::from_be_bytes(b))
+    }
+}
+
+impl<R: Read> MeterSource for MeterPlayer<R> {
+    /// Returns the next frame once its time has come, relative to the first frame and scaled by
+    /// `speed()`. Returns an `Error::Io` with `ErrorKind::UnexpectedEof` at the end of the
+    /// recording.
+    fn read_meters(&mut self) -> Result<MeterFrame> {
+        let (time, frame) = self.next_frame()?
+            .ok_or_else(|| Error::Io(ErrorKind::UnexpectedEof.into()))?;
+        if self.speed > 0.0 {
+            let (start, first) = *self.start.get_or_insert_with(|| (Instant::now(), time));
+            let due = start + time.saturating_sub(first).div_f32(self.speed);
+            let now = Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+        }
+        Ok(frame)
+    }
+}
@@ -0,0 +1,265 @@
+use std::time::{Duration, Instant};
+
+use crate::meter::{RTA_BANDS, db_to_power, power_to_db};
+
+/// Number of RTA bins per octave
+pub const RTA_BINS_PER_OCTAVE: usize = 12;
+
+/// Centre frequency of the lowest RTA bin, in Hz
+const RTA_LOWEST_HZ: f32 = 20.0;
+
+/// Centre frequency of an RTA bin in Hz. The bins are 1/12 octave apart, from 20 Hz up to about
+/// 19.4 kHz.
+pub fn rta_frequency(bin: usize) -> f32 {
+    RTA_LOWEST_HZ * 2f32.powf(bin as f32 / RTA_BINS_PER_OCTAVE as f32)
+}
+
+/// One frequency bin of an RTA spectrum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RtaBin {
+    /// Centre frequency in Hz
+    pub frequency: f32,
+    /// Level in dB
+    pub level: f32,
+}
+
+/// A spectrum from `Meter::Rta`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtaSpectrum {
+    /// The bins from low to high frequency
+    pub bins: Vec<RtaBin>,
+}
+
+impl RtaSpectrum {
+    /// Makes a spectrum from the level of each bin in dB
+    pub fn from_levels(levels: &[f32]) -> Self {
+        RtaSpectrum {
+            bins: levels.iter().enumerate().map(|(i, l)| RtaBin { frequency: rta_frequency(i), level: *l }).collect(),
+        }
+    }
+
+    /// The level of each bin in dB
+    pub fn levels(&self) -> Vec<f32> {
+        self.bins.iter().map(|b| b.level).collect()
+    }
+
+    /// Combines every `bins_per_band` bins into one, for example 4 for 1/3 octave bands or 12 for
+    /// octave bands. The levels are averaged on power and the frequency is the geometric centre.
+    pub fn average(&self, bins_per_band: usize) -> Vec<RtaBin> {
+        self.bins.chunks(bins_per_band.max(1)).map(|band| {
+            let power = band.iter().map(|b| db_to_power(b.level)).sum::<f32>() / band.len() as f32;
+            let frequency = (band[0].frequency * band[band.len() - 1].frequency).sqrt();
+            RtaBin { frequency, level: power_to_db(power) }
+        }).collect()
+    }
+
+    /// The level of the bins with a centre frequency from `low` to `high` Hz, averaged on power
+    pub fn level_between(&self, low: f32, high: f32) -> Option<f32> {
+        let powers: Vec<f32> = self.bins.iter()
+            .filter(|b| b.frequency >= low && b.frequency <= high)
+            .map(|b| db_to_power(b.level))
+            .collect();
+        if powers.is_empty() {
+            return None;
+        }
+        Some(power_to_db(powers.iter().sum::<f32>() / powers.len() as f32))
+    }
+
+    /// Bins that stand out from the bins around them: at least `min_level` dB, and `prominence`
+    /// dB above the average of the bins 2 to `width` bins away on each side. The bins right next
+    /// to a peak are left out of the average, so a peak can be a bin or two wide.
+    pub fn narrow_peaks(&self, prominence: f32, min_level: f32, width: usize) -> Vec<usize> {
+        let width = width.max(2);
+        (0..self.bins.len()).filter(|&i| {
+            let level = self.bins[i].level;
+            if level < min_level {
+                return false;
+            }
+            let left = i.saturating_sub(1);
+            let right = i + 1;
+            if self.bins[left].level > level || self.bins.get(right).is_some_and(|b| b.level >= level) {
+                return false;
+            }
+            let around: Vec<f32> = (i.saturating_sub(width)..=(i + width).min(self.bins.len() - 1))
+                .filter(|&j| j.abs_diff(i) >= 2)
+                .map(|j| db_to_power(self.bins[j].level))
+                .collect();
+            !around.is_empty() && level - power_to_db(around.iter().sum::<f32>() / around.len() as f32) >= prominence
+        }).collect()
+    }
+}
+
+/// Settings for `FeedbackDetector`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedbackConfig {
+    /// How far in dB a bin has to stand out from the bins around it
+    pub prominence: f32,
+    /// Level in dB below which peaks are ignored
+    pub min_level: f32,
+    /// How many bins on each side a peak is compared with
+    pub width: usize,
+    /// How long a peak has to last to be reported
+    pub sustain: Duration,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        FeedbackConfig {
+            prominence: 12.0,
+            min_level: -40.0,
+            width: 6,
+            sustain: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A narrow peak that has lasted long enough to possibly be feedback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeedbackCandidate {
+    pub bin: usize,
+    /// Centre frequency in Hz
+    pub frequency: f32,
+    /// Level in dB
+    pub level: f32,
+    /// How long the peak has lasted
+    pub duration: Duration,
+}
+
+/// Finds narrow peaks in a stream of RTA spectrums that last for a while, which is what
+/// feedback looks like. Music has peaks too, but they move around or end quickly.
+#[derive(Debug, Clone, Default)]
+pub struct FeedbackDetector {
+    pub config: FeedbackConfig,
+    /// When each bin started to be a peak
+    since: Vec<Option<Instant>>,
+}
+
+impl FeedbackDetector {
+    pub fn new(config: FeedbackConfig) -> Self {
+        FeedbackDetector { config, since: vec![None; RTA_BANDS] }
+    }
+
+    /// Takes a spectrum that was just received and returns the peaks that have lasted at least
+    /// `sustain`
+    pub fn process(&mut self, spectrum: &RtaSpectrum) -> Vec<FeedbackCandidate> {
+        self.process_at(spectrum, Instant::now())
+    }
+
+    /// Takes a spectrum that was received at `now` and returns the peaks that have lasted at
+    /// least `sustain`
+    pub fn process_at(&mut self, spectrum: &RtaSpectrum, now: Instant) -> Vec<FeedbackCandidate> {
+        self.since.resize(spectrum.bins.len(), None);
+        let peaks = spectrum.narrow_peaks(self.config.prominence, self.config.min_level, self.config.width);
+
+        // a peak that drifts to the next bin is still the same peak
+        let mut since = vec![None; spectrum.bins.len()];
+        for &i in &peaks {
+            let before = (i.saturating_sub(1)..=(i + 1).min(self.since.len() - 1)).filter_map(|j| self.since[j]).min();
+            since[i] = Some(before.unwrap_or(now));
+        }
+        self.since = since;
+
+        peaks.into_iter().filter_map(|i| {
+            let duration = now.saturating_duration_since(self.since[i]?);
+            (duration >= self.config.sustain).then(|| FeedbackCandidate {
+                bin: i,
+                frequency: spectrum.bins[i].frequency,
+                level: spectrum.bins[i].level,
+                duration,
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flat spectrum at -60 dB with the given bins set to other levels
+    fn spectrum(peaks: &[(usize, f32)]) -> RtaSpectrum {
+        let mut levels = vec![-60.0; RTA_BANDS];
+        for &(bin, level) in peaks {
+            levels[bin] = level;
+        }
+        RtaSpectrum::from_levels(&levels)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn bin_frequencies() {
+        assert_eq!(rta_frequency(0), 20.0);
+        assert!(close(rta_frequency(RTA_BINS_PER_OCTAVE), 40.0));
+        assert!(close(rta_frequency(4), 20.0 * 2f32.powf(1.0 / 3.0)));
+        assert!((rta_frequency(RTA_BANDS - 1) - 19400.0).abs() < 100.0);
+        let s = spectrum(&[]);
+        assert_eq!(s.bins.len(), RTA_BANDS);
+        assert!(s.bins.windows(2).all(|w| w[0].frequency < w[1].frequency));
+        assert_eq!(s.levels(), vec![-60.0; RTA_BANDS]);
+    }
+
+    #[test]
+    fn band_averaging() {
+        let bands = spectrum(&[]).average(4);
+        assert_eq!(bands.len(), RTA_BANDS / 4);
+        assert!(bands.iter().all(|b| close(b.level, -60.0)));
+        assert!(close(bands[0].frequency, (rta_frequency(0) * rta_frequency(3)).sqrt()));
+        // averaged on power: one bin 30 dB up in a band of 4 adds about 24 dB
+        let bands = spectrum(&[(0, -30.0)]).average(4);
+        assert!(close(bands[0].level, power_to_db((db_to_power(-30.0) + 3.0 * db_to_power(-60.0)) / 4.0)));
+        // a band size that doesn't divide the bins leaves a smaller last band, and 0 is 1
+        assert_eq!(spectrum(&[]).average(7).len(), RTA_BANDS.div_ceil(7));
+        assert_eq!(spectrum(&[]).average(0).len(), RTA_BANDS);
+        assert!(RtaSpectrum::from_levels(&[]).average(4).is_empty());
+    }
+
+    #[test]
+    fn level_between_frequencies() {
+        let s = spectrum(&[(12, -20.0)]);
+        assert!(close(s.level_between(39.0, 41.0).unwrap(), -20.0));
+        assert!(close(s.level_between(0.0, 100000.0).unwrap(),
+            power_to_db((db_to_power(-20.0) + (RTA_BANDS - 1) as f32 * db_to_power(-60.0)) / RTA_BANDS as f32)));
+        assert_eq!(s.level_between(5.0, 10.0), None);
+        assert_eq!(s.level_between(1000.0, 100.0), None);
+    }
+
+    #[test]
+    fn narrow_peaks() {
+        assert_eq!(spectrum(&[(50, -10.0)]).narrow_peaks(12.0, -40.0, 6), [50]);
+        assert_eq!(spectrum(&[(0, -10.0)]).narrow_peaks(12.0, -40.0, 6), [0]);
+        assert_eq!(spectrum(&[(RTA_BANDS - 1, -10.0)]).narrow_peaks(12.0, -40.0, 6), [RTA_BANDS - 1]);
+        // too quiet, or not standing out enough
+        assert!(spectrum(&[(50, -45.0)]).narrow_peaks(12.0, -40.0, 6).is_empty());
+        assert!(spectrum(&[(50, -50.0)]).narrow_peaks(12.0, -70.0, 6).is_empty());
+        // two bins wide is one peak, at the higher bin or the upper one of two equal bins
+        assert_eq!(spectrum(&[(50, -10.0), (51, -15.0)]).narrow_peaks(12.0, -40.0, 6), [50]);
+        assert_eq!(spectrum(&[(50, -10.0), (51, -10.0)]).narrow_peaks(12.0, -40.0, 6), [51]);
+        // a broad hill is not narrow
+        let hill: Vec<(usize, f32)> = (40..60).map(|i| (i, -10.0 - (i as f32 - 50.0).abs())).collect();
+        assert!(spectrum(&hill).narrow_peaks(12.0, -40.0, 6).is_empty());
+        assert!(RtaSpectrum::from_levels(&[-10.0]).narrow_peaks(12.0, -40.0, 6).is_empty());
+        assert!(RtaSpectrum::from_levels(&[]).narrow_peaks(12.0, -40.0, 6).is_empty());
+    }
+
+    #[test]
+    fn feedback_has_to_last() {
+        let t = Instant::now();
+        let at = |ms: u64| t + Duration::from_millis(ms);
+        let mut d = FeedbackDetector::new(FeedbackConfig::default());
+        assert!(d.process_at(&spectrum(&[(60, -10.0)]), at(0)).is_empty());
+        assert!(d.process_at(&spectrum(&[(60, -10.0)]), at(400)).is_empty());
+        // drifting to the next bin is the same peak
+        let found = d.process_at(&spectrum(&[(61, -10.0)]), at(500));
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].bin, found[0].level, found[0].duration), (61, -10.0, Duration::from_millis(500)));
+        assert_eq!(found[0].frequency, rta_frequency(61));
+        // once it is gone it starts over
+        assert!(d.process_at(&spectrum(&[]), at(600)).is_empty());
+        assert!(d.process_at(&spectrum(&[(61, -10.0)]), at(700)).is_empty());
+        // a peak that jumps further is a new one
+        assert!(d.process_at(&spectrum(&[(64, -10.0)]), at(1300)).is_empty());
+        assert!(d.process_at(&RtaSpectrum::from_levels(&[]), at(1400)).is_empty());
+    }
+}
@@ -0,0 +1,664 @@
+//! Loading and comparing property schemas.
+//!
+//! A [`Schema`] is the set of node definitions keyed by their full path name, as written to
+//! propmap.jsonl by **wingschema** or compiled into the library. Two schemas, for example from
+//! two firmware versions, can be compared with [`diff()`].
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::{Result, Error};
+use crate::node::{WingNodeDef, NodeType};
+use crate::propmap::NAME_TO_DEF;
+
+#[derive(Clone, Default)]
+pub struct Schema {
+    nodes: BTreeMap<String, WingNodeDef>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The schema built into the library (see propmap.rs)
+    pub fn builtin() -> Self {
+        Self {
+            nodes: NAME_TO_DEF.iter().map(|(n, d)| (n.clone(), d.clone())).collect(),
+        }
+    }
+
+    /// Parses the contents of a propmap.jsonl file. Each line is the `to_json()` output of a node
+    /// definition with an added "fullname" field. Blank lines are ignored. A full name that is on
+    /// more than one line is an error.
+    pub fn from_jsonl(text: &str) -> Result<Self> {
+        let mut schema = Self::new();
+        let mut lines = HashMap::new();
+        for (lineno, line) in text.lines().enumerate() {
+            if line.trim().is_empty() { continue; }
+            let json = jzon::parse(line)
+                .map_err(|e| Error::SchemaError(format!("line {}: {}", lineno + 1, e)))?;
+            let fullname = json["fullname"].as_str()
+                .ok_or_else(|| Error::SchemaError(format!("line {}: missing fullname", lineno + 1)))?
+                .to_string();
+            let def = WingNodeDef::from_json(&json)
+                .ok_or_else(|| Error::SchemaError(format!("line {}: invalid node definition", lineno + 1)))?;
+            if let Some(first) = lines.insert(fullname.clone(), lineno + 1) {
+                return Err(Error::SchemaError(format!("line {}: {} is already on line {}", lineno + 1, fullname, first)));
+            }
+            schema.insert(fullname, def);
+        }
+        Ok(schema)
+    }
+
+    pub fn load_jsonl<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_jsonl(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn insert(&mut self, fullname: String, def: WingNodeDef) {
+        self.nodes.insert(fullname, def);
+    }
+
+    pub fn get(&self, fullname: &str) -> Option<&WingNodeDef> {
+        self.nodes.get(fullname)
+    }
+
+    /// Iterates over all nodes, sorted by full name
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &WingNodeDef)> {
+        self.nodes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// A single changed attribute of a node. `old` is `None` for something that was added, and `new`
+/// is `None` for something that was removed (for example an enum item).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaChange {
+    Added    { fullname: String, id: i32, node_type: NodeType },
+    Removed  { fullname: String, id: i32, node_type: NodeType },
+    Modified { fullname: String, id: i32, fields: Vec<FieldChange> },
+    /// A node that kept its id under a new full name, and the changes to its other fields
+    Moved    { old_fullname: String, fullname: String, id: i32, fields: Vec<FieldChange> },
+}
+
+impl SchemaChange {
+    pub fn fullname(&self) -> &str {
+        match self {
+            SchemaChange::Added { fullname, .. } |
+            SchemaChange::Removed { fullname, .. } |
+            SchemaChange::Modified { fullname, .. } |
+            SchemaChange::Moved { fullname, .. } => fullname,
+        }
+    }
+}
+
+/// The result of [`diff()`]. Changes are sorted by full name.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaDiff {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|c| matches!(c, SchemaChange::Added { .. }))
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|c| matches!(c, SchemaChange::Removed { .. }))
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|c| matches!(c, SchemaChange::Modified { .. }))
+    }
+
+    pub fn moved(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|c| matches!(c, SchemaChange::Moved { .. }))
+    }
+
+    /// One line per added (`+`) or removed (`-`) node, and an indented line per changed field of
+    /// a modified (`~`) or moved (`>`) node, followed by a summary line.
+    pub fn to_text(&self) -> String {
+        let mut r = String::new();
+        for change in &self.changes {
+            match change {
+                SchemaChange::Added { fullname, id, node_type } => {
+                    r.push_str(&format!("+ {} (id {}, {})\n", fullname, id, node_type.as_str()));
+                }
+                SchemaChange::Removed { fullname, id, node_type } => {
+                    r.push_str(&format!("- {} (id {}, {})\n", fullname, id, node_type.as_str()));
+                }
+                SchemaChange::Modified { fullname, id, fields } => {
+                    r.push_str(&format!("~ {} (id {})\n", fullname, id));
+                    fields_to_text(&mut r, fields);
+                }
+                SchemaChange::Moved { old_fullname, fullname, id, fields } => {
+                    r.push_str(&format!("> {} -> {} (id {})\n", old_fullname, fullname, id));
+                    fields_to_text(&mut r, fields);
+                }
+            }
+        }
+        r.push_str(&format!("{} added, {} removed, {} modified, {} moved\n",
+            self.added().count(), self.removed().count(), self.modified().count(), self.moved().count()));
+        r
+    }
+
+    pub fn to_json(&self) -> jzon::JsonValue {
+        let mut added = jzon::array![];
+        let mut removed = jzon::array![];
+        let mut modified = jzon::array![];
+        let mut moved = jzon::array![];
+
+        for change in &self.changes {
+            match change {
+                SchemaChange::Added { fullname, id, node_type } => {
+                    added.push(jzon::object!{ fullname: fullname.clone(), id: *id, type: node_type.as_str() }).unwrap();
+                }
+                SchemaChange::Removed { fullname, id, node_type } => {
+                    removed.push(jzon::object!{ fullname: fullname.clone(), id: *id, type: node_type.as_str() }).unwrap();
+                }
+                SchemaChange::Modified { fullname, id, fields } => {
+                    modified.push(jzon::object!{ fullname: fullname.clone(), id: *id, changes: fields_to_json(fields) }).unwrap();
+                }
+                SchemaChange::Moved { old_fullname, fullname, id, fields } => {
+                    moved.push(jzon::object!{
+                        old_fullname: old_fullname.clone(),
+                        fullname: fullname.clone(),
+                        id: *id,
+                        changes: fields_to_json(fields),
+                    }).unwrap();
+                }
+            }
+        }
+
+        jzon::object!{
+            added: added,
+            removed: removed,
+            modified: modified,
+            moved: moved,
+        }
+    }
+}
+
+fn fields_to_text(r: &mut String, fields: &[FieldChange]) {
+    for f in fields {
+        match (&f.old, &f.new) {
+            (Some(old), Some(new)) => r.push_str(&format!("    {}: {} -> {}\n", f.field, old, new)),
+            (None, Some(new))      => r.push_str(&format!("    {}: + {}\n", f.field, new)),
+            (Some(old), None)      => r.push_str(&format!("    {}: - {}\n", f.field, old)),
+            (None, None)           => {}
+        }
+    }
+}
+
+fn fields_to_json(fields: &[FieldChange]) -> jzon::JsonValue {
+    let mut changes = jzon::array![];
+    for f in fields {
+        let mut j = jzon::object!{ field: f.field };
+        if let Some(old) = &f.old { j.insert("old", old.clone()).unwrap(); }
+        if let Some(new) = &f.new { j.insert("new", new.clone()).unwrap(); }
+        changes.push(j).unwrap();
+    }
+    changes
+}
+
+/// Compares two schemas node by node, matching nodes by their full name. For nodes present in
+/// both, changes to the id, type, unit, read-only flag, ranges, steps, maximum string length and
+/// enum items are reported. A node that is only in `a` and one that is only in `b` with the same
+/// id are a move, unless other nodes only in `a` or `b` have that id too (as the parameters of
+/// different FX models do).
+pub fn diff(a: &Schema, b: &Schema) -> SchemaDiff {
+    let mut changes = Vec::new();
+
+    let mut removed: HashMap<i32, Vec<&String>> = HashMap::new();
+    for (fullname, old) in a.iter().filter(|(n, _)| b.get(n).is_none()) {
+        removed.entry(old.id).or_default().push(fullname);
+    }
+    let mut added: HashMap<i32, Vec<&String>> = HashMap::new();
+    for (fullname, new) in b.iter().filter(|(n, _)| a.get(n).is_none()) {
+        added.entry(new.id).or_default().push(fullname);
+    }
+    let moved: HashMap<&String, &String> = removed.iter()
+        .filter_map(|(id, from)| match (from.as_slice(), added.get(id).map(|v| v.as_slice())) {
+            ([from], Some([to])) => Some((*to, *from)),
+            _ => None,
+        })
+        .collect();
+    let moved_from: HashSet<&String> = moved.values().copied().collect();
+
+    for (fullname, old) in a.iter() {
+        match b.get(fullname) {
+            None if moved_from.contains(fullname) => {}
+            None => changes.push(SchemaChange::Removed {
+                fullname: fullname.clone(),
+                id: old.id,
+                node_type: old.node_type,
+            }),
+            Some(new) => {
+                let fields = diff_def(old, new);
+                if !fields.is_empty() {
+                    changes.push(SchemaChange::Modified {
+                        fullname: fullname.clone(),
+                        id: new.id,
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+
+    for (fullname, new) in b.iter().filter(|(n, _)| a.get(n).is_none()) {
+        match moved.get(fullname) {
+            Some(from) => changes.push(SchemaChange::Moved {
+                old_fullname: (*from).clone(),
+                fullname: fullname.clone(),
+                id: new.id,
+                fields: diff_def(a.get(from).unwrap(), new),
+            }),
+            None => changes.push(SchemaChange::Added {
+                fullname: fullname.clone(),
+                id: new.id,
+                node_type: new.node_type,
+            }),
+        }
+    }
+
+    changes.sort_by(|x, y| x.fullname().cmp(y.fullname()));
+    SchemaDiff { changes }
+}
+
+fn diff_def(old: &WingNodeDef, new: &WingNodeDef) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+
+    fn field<T: PartialEq + ToString>(fields: &mut Vec<FieldChange>, field: &'static str, old: Option<T>, new: Option<T>) {
+        if old != new {
+            fields.push(FieldChange {
+                field,
+                old: old.map(|v| v.to_string()),
+                new: new.map(|v| v.to_string()),
+            });
+        }
+    }
+
+    field(&mut fields, "id",           Some(old.id),                   Some(new.id));
+    field(&mut fields, "type",         Some(old.node_type.as_str()),   Some(new.node_type.as_str()));
+    field(&mut fields, "unit",         Some(old.unit.as_str()),        Some(new.unit.as_str()));
+    field(&mut fields, "read_only",    Some(old.read_only),            Some(new.read_only));
+    field(&mut fields, "minfloat",     old.min_float,                  new.min_float);
+    field(&mut fields, "maxfloat",     old.max_float,                  new.max_float);
+    field(&mut fields, "steps",        old.steps,                      new.steps);
+    field(&mut fields, "minint",       old.min_int,                    new.min_int);
+    field(&mut fields, "maxint",       old.max_int,                    new.max_int);
+    field(&mut fields, "maxstringlen", old.max_string_len,             new.max_string_len);
+
+    let old_items = enum_items(old);
+    let new_items = enum_items(new);
+    for (item, long_item) in &old_items {
+        match new_items.iter().find(|(i, _)| i == item) {
+            None => fields.push(FieldChange { field: "items", old: Some(format_item(item, long_item)), new: None }),
+            Some((_, new_long_item)) if new_long_item != long_item => fields.push(FieldChange {
+                field: "items",
+                old: Some(format_item(item, long_item)),
+                new: Some(format_item(item, new_long_item)),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (item, long_item) in &new_items {
+        if !old_items.iter().any(|(i, _)| i == item) {
+            fields.push(FieldChange { field: "items", old: None, new: Some(format_item(item, long_item)) });
+        }
+    }
+
+    fields
+}
+
+/// The enum items of a node as (item, long item) strings, whichever kind of enum it is
+fn enum_items(def: &WingNodeDef) -> Vec<(String, String)> {
+    if let Some(items) = &def.string_enum {
+        items.iter().map(|i| (i.item.clone(), i.long_item.clone())).collect()
+    } else if let Some(items) = &def.float_enum {
+        items.iter().map(|i| (i.item.to_string(), i.long_item.clone())).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn format_item(item: &str, long_item: &str) -> String {
+    if long_item.is_empty() {
+        item.to_string()
+    } else {
+        format!("{} ({})", item, long_item)
+    }
+}
+
+/// A pattern over full property names, for example `/ch/*/fdr`, `/bus/{1..8}/mute` or
+/// `**/eq/*/f`.
+///
+/// - `*` matches a whole path segment, or any run of characters within one (`/ch/1/eq/*g`)
+/// - `**` as a whole segment matches any number of segments, including none
+/// - `{1..8}` matches a number in the range, inclusive
+/// - `{a,b,c}` matches any one of the alternatives
+/// - `?` matches any one character, and `[a-c]` or `[!a-c]` one character in or not in the set,
+///   as in OSC address patterns
+///
+/// A leading `/` is optional and ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathPattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    AnyDepth,
+    Pieces(Vec<Piece>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Literal(String),
+    Star,
+    Range(i64, i64),
+    Alternatives(Vec<String>),
+    AnyChar,
+    /// characters or inclusive ranges of them, and whether the set is negated
+    CharSet(Vec<(char, char)>, bool),
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        for seg in pattern.trim_start_matches('/').split('/') {
+            if seg == "**" {
+                segments.push(Segment::AnyDepth);
+                continue;
+            }
+
+            let mut pieces = Vec::new();
+            let mut literal = String::new();
+            let mut chars = seg.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '*' | '{' | '?' | '[' if !literal.is_empty() => {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    _ => {}
+                }
+                match c {
+                    '*' => pieces.push(Piece::Star),
+                    '{' => {
+                        let mut inner = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('}') => break,
+                                Some(c) => inner.push(c),
+                                None => return Err(Error::PatternError(format!("missing '}}' in {}", pattern))),
+                            }
+                        }
+                        pieces.push(Self::parse_braces(&inner)
+                            .ok_or_else(|| Error::PatternError(format!("invalid {{{}}} in {}", inner, pattern)))?);
+                    }
+                    '?' => pieces.push(Piece::AnyChar),
+                    '[' => {
+                        let mut inner = Vec::new();
+                        loop {
+                            match chars.next() {
+                                Some(']') => break,
+                                Some(c) => inner.push(c),
+                                None => return Err(Error::PatternError(format!("missing ']' in {}", pattern))),
+                            }
+                        }
+                        pieces.push(Self::parse_char_set(&inner)
+                            .ok_or_else(|| Error::PatternError(format!("invalid [{}] in {}", inner.iter().collect::<String>(), pattern)))?);
+                    }
+                    _ => literal.push(c),
+                }
+            }
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(literal));
+            }
+            segments.push(Segment::Pieces(pieces));
+        }
+        Ok(Self { segments })
+    }
+
+    fn parse_braces(inner: &str) -> Option<Piece> {
+        if let Some((from, to)) = inner.split_once("..") {
+            let from = from.trim().parse::<i64>().ok()?;
+            let to = to.trim().parse::<i64>().ok()?;
+            Some(Piece::Range(from.min(to), from.max(to)))
+        } else if inner.is_empty() {
+            None
+        } else {
+            Some(Piece::Alternatives(inner.split(',').map(|s| s.trim().to_string()).collect()))
+        }
+    }
+
+    fn parse_char_set(inner: &[char]) -> Option<Piece> {
+        let (negated, inner) = match inner.first() {
+            Some('!') => (true, &inner[1..]),
+            _ => (false, inner),
+        };
+        let mut ranges = Vec::new();
+        let mut i = 0;
+        while i < inner.len() {
+            if i + 2 < inner.len() && inner[i + 1] == '-' {
+                ranges.push((inner[i].min(inner[i + 2]), inner[i].max(inner[i + 2])));
+                i += 3;
+            } else {
+                ranges.push((inner[i], inner[i]));
+                i += 1;
+            }
+        }
+        (!ranges.is_empty()).then_some(Piece::CharSet(ranges, negated))
+    }
+
+    /// Returns true if the string contains any pattern syntax, so it can not be a plain property
+    /// name
+    pub fn is_pattern(s: &str) -> bool {
+        s.contains(['*', '{', '?', '['])
+    }
+
+    pub fn matches(&self, fullname: &str) -> bool {
+        let path: Vec<&str> = fullname.trim_start_matches('/').split('/').collect();
+        Self::match_segments(&self.segments, &path)
+    }
+
+    fn match_segments(segments: &[Segment], path: &[&str]) -> bool {
+        match segments.first() {
+            None => path.is_empty(),
+            Some(Segment::AnyDepth) => {
+                (0..=path.len()).any(|skip| Self::match_segments(&segments[1..], &path[skip..]))
+            }
+            Some(Segment::Pieces(pieces)) => {
+                !path.is_empty()
+                    && Self::match_pieces(pieces, path[0])
+                    && Self::match_segments(&segments[1..], &path[1..])
+            }
+        }
+    }
+
+    fn match_pieces(pieces: &[Piece], s: &str) -> bool {
+        match pieces.first() {
+            None => s.is_empty(),
+            Some(Piece::Literal(l)) => {
+                s.strip_prefix(l.as_str()).is_some_and(|rest| Self::match_pieces(&pieces[1..], rest))
+            }
+            Some(Piece::Star) => {
+                s.char_indices().map(|(i, _)| i).chain(std::iter::once(s.len()))
+                    .any(|i| Self::match_pieces(&pieces[1..], &s[i..]))
+            }
+            Some(Piece::Range(from, to)) => {
+                let digits = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+                (1..=digits).any(|n| {
+                    s[..n].parse::<i64>().is_ok_and(|v| v >= *from && v <= *to)
+                        && Self::match_pieces(&pieces[1..], &s[n..])
+                })
+            }
+            Some(Piece::Alternatives(alts)) => {
+                alts.iter().any(|a| {
+                    s.strip_prefix(a.as_str()).is_some_and(|rest| Self::match_pieces(&pieces[1..], rest))
+                })
+            }
+            Some(Piece::AnyChar) => {
+                s.chars().next().is_some_and(|c| Self::match_pieces(&pieces[1..], &s[c.len_utf8()..]))
+            }
+            Some(Piece::CharSet(ranges, negated)) => {
+                s.chars().next().is_some_and(|c| {
+                    ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) != *negated
+                        && Self::match_pieces(&pieces[1..], &s[c.len_utf8()..])
+                })
+            }
+        }
+    }
+}
+
+impl Schema {
+    /// All nodes whose full name matches the pattern, sorted by full name
+    pub fn find(&self, pattern: &PathPattern) -> Vec<(&String, &WingNodeDef)> {
+        self.nodes.iter().filter(|(n, _)| pattern.matches(n)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, fullname: &str) -> bool {
+        PathPattern::parse(pattern).unwrap().matches(fullname)
+    }
+
+    fn schema(lines: &[&str]) -> Schema {
+        Schema::from_jsonl(&lines.join("\n")).unwrap()
+    }
+
+    #[test]
+    fn star_matches_one_segment_or_part_of_one() {
+        assert!(matches("/ch/*/fdr", "/ch/1/fdr"));
+        assert!(matches("ch/*/fdr", "/ch/40/fdr"));
+        assert!(!matches("/ch/*/fdr", "/ch/1/eq/fdr"));
+        assert!(!matches("/ch/*/fdr", "/ch/fdr"));
+        assert!(matches("/ch/1/eq/*g", "/ch/1/eq/hg"));
+        assert!(matches("/ch/1/eq/*g", "/ch/1/eq/g"));
+        assert!(!matches("/ch/1/eq/*g", "/ch/1/eq/hf"));
+    }
+
+    #[test]
+    fn double_star_matches_any_depth() {
+        assert!(matches("**/f", "/ch/1/eq/1/f"));
+        assert!(matches("**/eq/*/f", "/ch/1/eq/1/f"));
+        assert!(matches("/ch/**/f", "/ch/f"));
+        assert!(matches("/**", "/"));
+        assert!(!matches("**/eq/*/f", "/ch/1/eq/1/g"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(matches("/bus/{1..8}/mute", "/bus/1/mute"));
+        assert!(matches("/bus/{1..8}/mute", "/bus/8/mute"));
+        assert!(!matches("/bus/{1..8}/mute", "/bus/9/mute"));
+        assert!(!matches("/bus/{1..8}/mute", "/bus/10/mute"));
+        assert!(!matches("/bus/{1..8}/mute", "/bus/x/mute"));
+        // reversed and single value ranges
+        assert!(matches("/bus/{8..1}/mute", "/bus/3/mute"));
+        assert!(matches("/bus/{5..5}/mute", "/bus/5/mute"));
+        assert!(!matches("/bus/{5..5}/mute", "/bus/4/mute"));
+        // a range followed by digits takes only as many as it needs
+        assert!(matches("/ch/{1..4}0", "/ch/30"));
+        assert!(matches("/ch/{1..40}/fdr", "/ch/40/fdr"));
+    }
+
+    #[test]
+    fn alternatives_and_characters() {
+        assert!(matches("/{ch,aux}/1/fdr", "/aux/1/fdr"));
+        assert!(!matches("/{ch,aux}/1/fdr", "/bus/1/fdr"));
+        // overlapping alternatives are each tried
+        assert!(matches("/{m,mt}x/1", "/mtx/1"));
+        assert!(matches("/ch/?/fdr", "/ch/7/fdr"));
+        assert!(!matches("/ch/?/fdr", "/ch/17/fdr"));
+        assert!(matches("/ch/[1-3]/fdr", "/ch/2/fdr"));
+        assert!(!matches("/ch/[1-3]/fdr", "/ch/4/fdr"));
+        assert!(matches("/ch/[!1-3]/fdr", "/ch/4/fdr"));
+        assert!(matches("/ch/1/eq/[hl]g", "/ch/1/eq/lg"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in ["/ch/{1..8/fdr", "/ch/{}/fdr", "/ch/{1..x}/fdr", "/ch/[1-3/fdr", "/ch/[]/fdr"] {
+            assert!(matches!(PathPattern::parse(pattern), Err(Error::PatternError(_))), "{}", pattern);
+        }
+        assert!(PathPattern::is_pattern("/ch/*/fdr"));
+        assert!(!PathPattern::is_pattern("/ch/1/fdr"));
+    }
+
+    #[test]
+    fn from_jsonl_rejects_duplicates() {
+        let text = [
+            r#"{"fullname":"/ch/1/fdr","id":10,"type":"fader level","unit":"dB"}"#,
+            "",
+            r#"{"fullname":"/ch/1/fdr","id":11,"type":"fader level","unit":"dB"}"#,
+        ].join("\n");
+        match Schema::from_jsonl(&text) {
+            Err(Error::SchemaError(e)) => assert_eq!(e, "line 3: /ch/1/fdr is already on line 1"),
+            _ => panic!("duplicate accepted"),
+        }
+        assert!(matches!(Schema::from_jsonl(r#"{"id":10,"type":"node"}"#), Err(Error::SchemaError(_))));
+    }
+
+    #[test]
+    fn diff_finds_moves() {
+        let a = schema(&[
+            r#"{"fullname":"/ch/1/dly","id":10,"type":"linear float","unit":"ms","minfloat":0,"maxfloat":500}"#,
+            r#"{"fullname":"/ch/1/mute","id":11,"type":"integer","minint":0,"maxint":1}"#,
+        ]);
+        let b = schema(&[
+            r#"{"fullname":"/ch/1/delay","id":10,"type":"linear float","unit":"ms","minfloat":0,"maxfloat":250}"#,
+            r#"{"fullname":"/ch/1/mute","id":11,"type":"integer","minint":0,"maxint":1}"#,
+        ]);
+        let diff = diff(&a, &b);
+        assert_eq!(diff.changes.len(), 1);
+        match &diff.changes[0] {
+            SchemaChange::Moved { old_fullname, fullname, id, fields } => {
+                assert_eq!((old_fullname.as_str(), fullname.as_str(), *id), ("/ch/1/dly", "/ch/1/delay", 10));
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].field, "maxfloat");
+            }
+            change => panic!("{:?}", change),
+        }
+        assert!(diff.to_text().ends_with("0 added, 0 removed, 0 modified, 1 moved\n"));
+        assert!(super::diff(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn diff_does_not_pair_shared_ids() {
+        // the parameters of different FX models share ids, so these are not moves
+        let a = schema(&[
+            r#"{"fullname":"/fx/1/HALL/1","id":20,"type":"linear float","minfloat":0,"maxfloat":1}"#,
+            r#"{"fullname":"/fx/1/ROOM/1","id":20,"type":"linear float","minfloat":0,"maxfloat":1}"#,
+        ]);
+        let b = schema(&[
+            r#"{"fullname":"/fx/1/PLATE/1","id":20,"type":"linear float","minfloat":0,"maxfloat":1}"#,
+        ]);
+        let diff = diff(&a, &b);
+        assert_eq!(diff.removed().count(), 2);
+        assert_eq!(diff.added().count(), 1);
+        assert_eq!(diff.moved().count(), 0);
+    }
+}
@@ -0,0 +1,125 @@
+//! Runtime support for the typed parameter API.
+//!
+//! The API itself is generated from a schema by `codegen::generate()` (run `wingschema -g
+//! propmap.jsonl` to write it to params.rs) and added to your crate as a module. It lets you write
+//!
+//! ```ignore
+//! use params::Params;
+//! wing.ch(1).fdr().set(-6.0)?;
+//! let muted = wing.bus(3).mute().get()?;
+//! ```
+//!
+//! where every step is checked by the compiler, and indexes are checked against the ranges found
+//! in the schema.
+
+use std::marker::PhantomData;
+
+use crate::{Result, Error, WingConsole};
+use crate::node::WingNodeData;
+
+/// A Rust type that a property value can be converted to and from. Implemented for `f32`, `i32`
+/// and `String`, and by the enums in the generated code.
+pub trait ParamValue: Sized {
+    fn from_data(data: &WingNodeData) -> Option<Self>;
+    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<()>;
+}
+
+impl ParamValue for f32 {
+    fn from_data(data: &WingNodeData) -> Option<Self> {
+        data.to_float()
+    }
+    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<()> {
+        wing.set_float(id, *self)
+    }
+}
+
+impl ParamValue for i32 {
+    fn from_data(data: &WingNodeData) -> Option<Self> {
+        data.to_int()
+    }
+    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<()> {
+        wing.set_int(id, *self)
+    }
+}
+
+impl ParamValue for String {
+    fn from_data(data: &WingNodeData) -> Option<Self> {
+        Some(data.get_string())
+    }
+    fn write(&self, wing: &mut WingConsole, id: i32) -> Result<()> {
+        wing.set_string(id, self)
+    }
+}
+
+/// A read/write property. `id` is `None` if an index along its path was out of range, in which
+/// case `get()` and `set()` return `Error::IndexError`.
+pub struct Param<'a, T> {
+    wing: &'a mut WingConsole,
+    id: Option<i32>,
+    name: String,
+    _type: PhantomData<T>,
+}
+
+impl<'a, T: ParamValue> Param<'a, T> {
+    #[doc(hidden)]
+    pub fn new(wing: &'a mut WingConsole, id: Option<i32>, name: String) -> Self {
+        Self { wing, id, name, _type: PhantomData }
+    }
+
+    pub fn id(&self) -> Result<i32> {
+        self.id.ok_or_else(|| Error::IndexError(self.name.clone()))
+    }
+
+    /// The full property name, for example `/ch/1/fdr`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Requests the current value from the Wing and waits for it
+    pub fn get(&mut self) -> Result<T> {
+        let id = self.id()?;
+        T::from_data(&self.wing.get_data(id)?).ok_or(Error::InvalidData)
+    }
+
+    pub fn set(&mut self, value: T) -> Result<()> {
+        let id = self.id()?;
+        value.write(self.wing, id)
+    }
+}
+
+/// A read-only property, such as a meter or status value
+pub struct ReadOnlyParam<'a, T>(Param<'a, T>);
+
+impl<'a, T: ParamValue> ReadOnlyParam<'a, T> {
+    #[doc(hidden)]
+    pub fn new(wing: &'a mut WingConsole, id: Option<i32>, name: String) -> Self {
+        Self(Param::new(wing, id, name))
+    }
+
+    pub fn id(&self) -> Result<i32> {
+        self.0.id()
+    }
+
+    pub fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    pub fn get(&mut self) -> Result<T> {
+        self.0.get()
+    }
+}
+
+/// Looks up a property id in a generated table. `dims` has the inclusive (min, max) range of each
+/// index along the property's path, and `ids` the ids for every combination of indexes, with the
+/// last index varying fastest and 0 for combinations that do not exist.
+#[doc(hidden)]
+pub fn lookup(ids: &[i32], dims: &[(u16, u16)], idx: &[u16]) -> Option<i32> {
+    let mut offset = 0usize;
+    for (&(min, max), &i) in dims.iter().zip(idx) {
+        if i < min || i > max {
+            return None;
+        }
+        offset = offset * (max - min + 1) as usize + (i - min) as usize;
+    }
+    ids.get(offset).copied().filter(|&id| id != 0)
+}
@@ -0,0 +1,189 @@
+// Runs the C API against the stand-in console of tests/c_api.rs: discover, connect, set and read a
+// value, read meters, and poll and get callbacks. abi_asserts.h is written by the test, with the
+// enum values and struct layouts of the Rust side.
+
+#define _POSIX_C_SOURCE 200809L
+#include <stdatomic.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+#include "libwing.h"
+#include "abi_asserts.h"
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        const char* error = wing_last_error_message(); \
+        fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n", __FILE__, __LINE__, #cond, error); \
+        return 1; \
+    } \
+} while (0)
+
+// What the callbacks received, written on the threads of the library
+struct Received {
+    WingConsole* console;
+    uint16_t meter_id;
+    atomic_int meter_packets;
+    atomic_int node_data;
+    float value;
+    // set by the response callback for 1004, which waits for meter frames meanwhile
+    atomic_int waited;
+    atomic_int meters_while_waiting;
+};
+
+static int wait_for(atomic_int* flag, int value);
+
+static void on_response(void* user_data, const Response* response) {
+    struct Received* received = user_data;
+    if (wing_response_get_type(response) == WING_RESPONSE_NODE_DATA && wing_node_data_get_id(response) == 1003) {
+        received->value = wing_node_data_get_float(response);
+        atomic_store(&received->node_data, 1);
+    }
+    if (wing_response_get_type(response) == WING_RESPONSE_NODE_DATA && wing_node_data_get_id(response) == 1004) {
+        // the meter callback has to keep running while this one does
+        atomic_store(&received->meters_while_waiting, wait_for(&received->meter_packets, atomic_load(&received->meter_packets) + 2));
+        atomic_store(&received->waited, 1);
+    }
+}
+
+static void on_meter(void* user_data, uint16_t id, const WingMeterSource* sources, size_t source_count,
+                     const float* values, size_t value_count) {
+    struct Received* received = user_data;
+    size_t size_sources, size_values;
+    if (id == received->meter_id && source_count == 1 && sources[0].meter == METER_ID(CHANNEL, 0)
+            && sources[0].count == 8 && value_count == 8 && values[0] == -20.0f && values[7] == -13.0f
+            // calls back into the console, which has to stay valid while wing_console_destroy() waits
+            && wing_console_meter_size(received->console, id, &size_sources, &size_values) == WING_OK) {
+        atomic_fetch_add(&received->meter_packets, 1);
+    }
+}
+
+// Waits up to 5 seconds for a flag set by a callback
+static int wait_for(atomic_int* flag, int value) {
+    struct timespec tick = { 0, 10 * 1000 * 1000 };
+    for (int i = 0; i < 500 && atomic_load(flag) < value; i++) {
+        nanosleep(&tick, NULL);
+    }
+    return atomic_load(flag) >= value;
+}
+
+static WingConsole* scan_and_connect(uint16_t discovery_port, uint16_t native_port) {
+    WingDiscoveryInfo* found = wing_discover_scan_target("127.0.0.1", discovery_port, 2000);
+    if (!found) {
+        return NULL;
+    }
+    WingConsole* console = NULL;
+    for (int i = 0; i < wing_discover_count(found) && !console; i++) {
+        const char* name = wing_discover_get_name(found, i);
+        if (strcmp(name, "StandIn") == 0) {
+            const char* ip = wing_discover_get_ip(found, i);
+            console = wing_console_connect_port(ip, native_port);
+            wing_string_destroy(ip);
+        }
+        wing_string_destroy(name);
+    }
+    wing_discover_destroy(found);
+    return console;
+}
+
+// Takes the discovery and Native ports of the stand-in
+int main(int argc, char** argv) {
+    CHECK(argc == 3);
+    CHECK(wing_api_version() == WING_API_VERSION);
+
+    WingConsole* console = scan_and_connect((uint16_t)atoi(argv[1]), (uint16_t)atoi(argv[2]));
+    CHECK(console != NULL);
+
+    CHECK(wing_console_set_float(console, 1001, -3.5f) == WING_OK);
+    CHECK(wing_console_request_node_data(console, 1001) == WING_OK);
+    Response* response;
+    while ((response = wing_console_read(console)) && wing_response_get_type(response) != WING_RESPONSE_NODE_DATA) {
+        wing_response_destroy(response);
+    }
+    CHECK(response != NULL);
+    CHECK(wing_node_data_get_id(response) == 1001);
+    CHECK(wing_node_data_has_float(response) == 1);
+    CHECK(wing_node_data_get_float(response) == -3.5f);
+    CHECK(wing_node_definition_get_id(response) == 0);
+    CHECK(wing_last_error() == WING_ERROR_WRONG_RESPONSE_TYPE);
+    wing_response_destroy(response);
+
+    uint16_t meters[] = { METER_ID(CHANNEL, 0), METER_ID(DCA, 1) };
+    uint16_t id = wing_console_request_meter(console, meters, 2);
+    CHECK(id != 0);
+    size_t source_count = 0, value_count = 0;
+    CHECK(wing_console_meter_size(console, id, &source_count, &value_count) == WING_OK);
+    CHECK(source_count == 2);
+    CHECK(value_count == (size_t)(wing_meter_value_count(meters[0]) + wing_meter_value_count(meters[1])));
+
+    WingMeterSource* sources = calloc(source_count, sizeof(WingMeterSource));
+    float* values = calloc(value_count, sizeof(float));
+    uint16_t frame_id = 0;
+    size_t got_sources = 0, got_values = 0;
+    CHECK(wing_console_read_meter_frame(console, 5000, &frame_id, sources, source_count - 1, &got_sources,
+                                        values, value_count, &got_values) == WING_ERROR_BUFFER_TOO_SMALL);
+    CHECK(got_sources == source_count && got_values == value_count);
+    CHECK(wing_console_read_meter_frame(console, 5000, &frame_id, sources, source_count, &got_sources,
+                                        values, value_count, &got_values) == WING_OK);
+    CHECK(frame_id == id);
+    CHECK(got_sources == source_count && got_values == value_count);
+    CHECK(sources[0].meter == METER_ID(CHANNEL, 0) && sources[0].offset == 0 && sources[0].count == 8);
+    CHECK(METER_TYPE(sources[1].meter) == DCA && METER_INDEX(sources[1].meter) == 1);
+    CHECK(sources[1].offset == 8 && sources[1].count == 4);
+    // the stand-in sends value i as i - 20 dB
+    CHECK(values[0] == -20.0f && values[8] == -12.0f && values[11] == -9.0f);
+    free(sources);
+    free(values);
+    CHECK(wing_console_cancel_meter(console, id) == WING_OK);
+
+    // meter callbacks don't read responses, so wing_console_read() still gets them
+    static struct Received received;
+    received.console = console;
+    uint16_t channel = METER_ID(CHANNEL, 0);
+    received.meter_id = wing_console_request_meter(console, &channel, 1);
+    CHECK(received.meter_id != 0);
+    CHECK(wing_console_set_meter_callback(console, on_meter, &received) == WING_OK);
+    CHECK(wait_for(&received.meter_packets, 3));
+    CHECK(wing_console_set_float(console, 1002, 3.0f) == WING_OK);
+    CHECK(wing_console_request_node_data(console, 1002) == WING_OK);
+    while ((response = wing_console_read(console)) && wing_response_get_type(response) != WING_RESPONSE_NODE_DATA) {
+        wing_response_destroy(response);
+    }
+    CHECK(response != NULL);
+    CHECK(wing_node_data_get_float(response) == 3.0f);
+    wing_response_destroy(response);
+
+    // from here on a thread of the library reads the console
+    // the end of the request is still queued
+    while ((response = wing_console_poll(console, 50))) {
+        wing_response_destroy(response);
+    }
+    CHECK(wing_last_error() == WING_ERROR_TIMEOUT);
+    CHECK(wing_console_set_float(console, 1002, 4.0f) == WING_OK);
+    CHECK(wing_console_request_node_data(console, 1002) == WING_OK);
+    while ((response = wing_console_poll(console, 5000)) && wing_response_get_type(response) != WING_RESPONSE_NODE_DATA) {
+        wing_response_destroy(response);
+    }
+    CHECK(response != NULL);
+    CHECK(wing_node_data_get_id(response) == 1002);
+    CHECK(wing_node_data_get_float(response) == 4.0f);
+    wing_response_destroy(response);
+    CHECK(wing_console_dropped_responses(console) == 0);
+
+    CHECK(wing_console_set_response_callback(console, on_response, &received) == WING_OK);
+    CHECK(wing_console_set_float(console, 1003, 12.5f) == WING_OK);
+    CHECK(wing_console_request_node_data(console, 1003) == WING_OK);
+    CHECK(wait_for(&received.node_data, 1));
+    CHECK(received.value == 12.5f);
+
+    // the two callbacks run at the same time
+    CHECK(wing_console_set_float(console, 1004, 1.0f) == WING_OK);
+    CHECK(wing_console_request_node_data(console, 1004) == WING_OK);
+    CHECK(wait_for(&received.waited, 1));
+    CHECK(atomic_load(&received.meters_while_waiting));
+
+    // with both callbacks still set
+    wing_console_destroy(console);
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,428 @@
+//! Checks that libwing.h matches the C API in src/ffi.rs: the declarations against the Rust
+//! signatures, and with a C compiler the enum values and struct layouts, and a program linked
+//! against the cdylib that runs against a stand-in console.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use libwing::{ErrorCode, Meter, NodeType, NodeUnit, ResponseType, WingMeterSource, WING_API_VERSION};
+
+/// The C type of a Rust type in src/ffi.rs, as it is written in libwing.h
+fn c_type(rust: &str) -> String {
+    let rust = rust.trim();
+    if let Some(inner) = rust.strip_prefix("*const ") {
+        return format!("const {}*", c_type(inner));
+    }
+    if let Some(inner) = rust.strip_prefix("*mut ") {
+        return format!("{}*", c_type(inner));
+    }
+    // a callback that may be NULL
+    if let Some(inner) = rust.strip_prefix("Option<").and_then(|r| r.strip_suffix('>')) {
+        return c_type(inner);
+    }
+    match rust {
+        "()" | "c_void" => "void",
+        "c_int" => "int",
+        "c_float" => "float",
+        "c_char" => "char",
+        "i16" => "int16_t",
+        "u16" => "uint16_t",
+        "i32" => "int32_t",
+        "u32" => "uint32_t",
+        "usize" => "size_t",
+        "WingDiscoveryInfoHandle" => "WingDiscoveryInfo",
+        "WingConsoleHandle" => "WingConsole",
+        "ResponseHandle" => "Response",
+        "WingDefinitionsHandle" => "WingDefinitions",
+        "ResponseType" => "WingResponseType",
+        "NodeType" => "WingNodeType",
+        "NodeUnit" => "WingNodeUnit",
+        "ErrorCode" => "WingErrorCode",
+        other => other,
+    }.to_string()
+}
+
+/// A declaration as `return type (parameter types)`, without names and spaces
+fn signature(ret: &str, params: &[String]) -> String {
+    format!("{}({})", ret, params.join(",")).replace(' ', "")
+}
+
+/// The exported functions and callback types of src/ffi.rs, by name
+fn rust_declarations() -> BTreeMap<String, String> {
+    let source = include_str!("../src/ffi.rs");
+    let mut declarations = BTreeMap::new();
+    for marker in ["pub extern \"C\" fn ", "pub type "] {
+        for (start, _) in source.match_indices(marker) {
+            let rest = &source[start + marker.len()..];
+            let open = rest.find('(').unwrap();
+            let close = open + rest[open..].find(')').unwrap();
+            let name = rest[..open].split('=').next().unwrap().trim();
+            let params = rest[open + 1..close].split(',')
+                .filter(|p| !p.trim().is_empty())
+                .map(|p| c_type(p.split_once(':').unwrap().1))
+                .collect::<Vec<_>>();
+            let after = rest[close + 1..].trim_start();
+            let ret = match after.strip_prefix("->") {
+                Some(ret) => c_type(ret.split(['{', ';']).next().unwrap()),
+                None => "void".to_string(),
+            };
+            declarations.insert(name.to_string(), signature(&ret, &params));
+        }
+    }
+    declarations
+}
+
+/// The functions and callback types declared in libwing.h, by name
+fn header_declarations() -> BTreeMap<String, String> {
+    let header = include_str!("../libwing.h");
+    let code = header.lines()
+        .map(|line| line.split("//").next().unwrap())
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut declarations = BTreeMap::new();
+    for statement in code.split(';') {
+        let Some(open) = statement.find('(') else { continue };
+        let close = statement.rfind(')').unwrap();
+        let (ret, name) = match statement[..open].trim().strip_prefix("typedef ") {
+            // typedef void (*Name)(params)
+            Some(ret) => {
+                let name = &statement[open + 1..statement[open..].find(')').unwrap() + open];
+                (ret.to_string(), name.trim_start_matches('*').trim().to_string())
+            }
+            None => {
+                let before = statement[..open].trim();
+                let split = before.rfind(|c: char| c.is_whitespace() || c == '*').unwrap();
+                (before[..=split].to_string(), before[split + 1..].to_string())
+            }
+        };
+        if !name.starts_with("wing_") && !name.starts_with("Wing") {
+            continue;
+        }
+        let params_start = statement[..close].rfind('(').unwrap();
+        let params = statement[params_start + 1..close].split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty() && *p != "void")
+            // drop the parameter name
+            .map(|p| p.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').to_string())
+            .collect::<Vec<_>>();
+        declarations.insert(name, signature(ret.trim(), &params));
+    }
+    declarations
+}
+
+#[test]
+fn header_declarations_match_ffi() {
+    let rust = rust_declarations();
+    let header = header_declarations();
+    let mut problems = Vec::new();
+    for (name, signature) in &rust {
+        match header.get(name) {
+            None => problems.push(format!("{} is missing in libwing.h", name)),
+            Some(declared) if declared != signature => {
+                problems.push(format!("{}: libwing.h declares {}, ffi.rs has {}", name, declared, signature))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in header.keys().filter(|name| !rust.contains_key(*name)) {
+        problems.push(format!("{} is declared in libwing.h but not exported by ffi.rs", name));
+    }
+    assert!(problems.is_empty(), "\n{}", problems.join("\n"));
+}
+
+/// Static asserts that the enums and structs in libwing.h have the values and layout of the Rust
+/// ones
+fn abi_asserts() -> String {
+    let mut values: Vec<(&str, i64)> = vec![
+        ("WING_API_VERSION", WING_API_VERSION as i64),
+        ("WING_RESPONSE_END", ResponseType::End as i64),
+        ("WING_RESPONSE_NODE_DEFINITION", ResponseType::NodeDefinition as i64),
+        ("WING_RESPONSE_NODE_DATA", ResponseType::NodeData as i64),
+        ("WING_OK", ErrorCode::Ok as i64),
+        ("WING_ERROR_INVALID_ARGUMENT", ErrorCode::InvalidArgument as i64),
+        ("WING_ERROR_IO", ErrorCode::Io as i64),
+        ("WING_ERROR_CONNECTION", ErrorCode::Connection as i64),
+        ("WING_ERROR_DISCOVERY", ErrorCode::Discovery as i64),
+        ("WING_ERROR_INVALID_DATA", ErrorCode::InvalidData as i64),
+        ("WING_ERROR_WRONG_RESPONSE_TYPE", ErrorCode::WrongResponseType as i64),
+        ("WING_ERROR_METER", ErrorCode::Meter as i64),
+        ("WING_ERROR_TIMEOUT", ErrorCode::Timeout as i64),
+        ("WING_ERROR_INTERNAL", ErrorCode::Internal as i64),
+        ("WING_ERROR_BUFFER_TOO_SMALL", ErrorCode::BufferTooSmall as i64),
+        ("WING_NODE_TYPE_NODE", NodeType::Node as i64),
+        ("WING_NODE_TYPE_LINEAR_FLOAT", NodeType::LinearFloat as i64),
+        ("WING_NODE_TYPE_LOGARITHMIC_FLOAT", NodeType::LogarithmicFloat as i64),
+        ("WING_NODE_TYPE_FADER_LEVEL", NodeType::FaderLevel as i64),
+        ("WING_NODE_TYPE_INTEGER", NodeType::Integer as i64),
+        ("WING_NODE_TYPE_STRING_ENUM", NodeType::StringEnum as i64),
+        ("WING_NODE_TYPE_FLOAT_ENUM", NodeType::FloatEnum as i64),
+        ("WING_NODE_TYPE_STRING", NodeType::String as i64),
+        ("WING_NODE_UNIT_NONE", NodeUnit::None as i64),
+        ("WING_NODE_UNIT_DB", NodeUnit::Db as i64),
+        ("WING_NODE_UNIT_PERCENT", NodeUnit::Percent as i64),
+        ("WING_NODE_UNIT_MILLISECONDS", NodeUnit::Milliseconds as i64),
+        ("WING_NODE_UNIT_HERTZ", NodeUnit::Hertz as i64),
+        ("WING_NODE_UNIT_METERS", NodeUnit::Meters as i64),
+        ("WING_NODE_UNIT_SECONDS", NodeUnit::Seconds as i64),
+        ("WING_NODE_UNIT_OCTAVES", NodeUnit::Octaves as i64),
+        ("sizeof(WingResponseType)", std::mem::size_of::<ResponseType>() as i64),
+        ("sizeof(WingErrorCode)", std::mem::size_of::<ErrorCode>() as i64),
+        ("sizeof(WingNodeType)", std::mem::size_of::<NodeType>() as i64),
+        ("sizeof(WingNodeUnit)", std::mem::size_of::<NodeUnit>() as i64),
+        ("sizeof(WingMeterSource)", std::mem::size_of::<WingMeterSource>() as i64),
+        ("offsetof(WingMeterSource, meter)", std::mem::offset_of!(WingMeterSource, meter) as i64),
+        ("offsetof(WingMeterSource, count)", std::mem::offset_of!(WingMeterSource, count) as i64),
+        ("offsetof(WingMeterSource, offset)", std::mem::offset_of!(WingMeterSource, offset) as i64),
+    ];
+    let meter_types = [
+        ("CHANNEL", Meter::Channel(0)), ("AUX", Meter::Aux(0)), ("BUS", Meter::Bus(0)), ("MAIN", Meter::Main(0)),
+        ("MATRIX", Meter::Matrix(0)), ("DCA", Meter::Dca(0)), ("FX", Meter::Fx(0)), ("SOURCE", Meter::Source(0)),
+        ("OUTPUT", Meter::Output(0)), ("MONITOR", Meter::Monitor), ("RTA", Meter::Rta), ("CHANNEL2", Meter::Channel2(0)),
+        ("AUX2", Meter::Aux2(0)), ("BUS2", Meter::Bus2(0)), ("MAIN2", Meter::Main2(0)), ("MATRIX2", Meter::Matrix2(0)),
+    ];
+    values.extend(meter_types.iter().map(|(name, meter)| (*name, (meter.code() >> 8) as i64)));
+
+    let mut asserts = String::from("#include <stddef.h>\n");
+    for (c, value) in values {
+        asserts += &format!("_Static_assert({} == {}, \"{} is {} in Rust\");\n", c, value, c.replace('"', ""), value);
+    }
+    asserts
+}
+
+/// Compiles a program in tests/c against libwing.h and the cdylib, or returns `None` if there is
+/// no C compiler
+fn compile(source: &str) -> Option<PathBuf> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(tmp.join("abi_asserts.h"), abi_asserts()).unwrap();
+    // the test runs from target/<profile>/deps, the cdylib is in target/<profile>
+    let lib_dir = std::env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+    let lib = lib_dir.join(format!("{}libwing{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX));
+    // `cargo test` builds the rlib only, so build the cdylib as well (this is a no-op when it is
+    // up to date)
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--lib", "--manifest-path"]).arg(root.join("Cargo.toml"));
+    if lib_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    assert!(build.status().unwrap().success(), "building the cdylib failed");
+    assert!(lib.exists(), "{} was not built", lib.display());
+
+    let program = tmp.join(source.trim_end_matches(".c"));
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let output = Command::new(&compiler)
+        .args(["-std=c11", "-Wall", "-Werror=implicit-function-declaration"])
+        .arg("-I").arg(&root)
+        .arg("-I").arg(&tmp)
+        .arg(root.join("tests/c").join(source))
+        .arg("-L").arg(&lib_dir)
+        .arg("-llibwing")
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-o").arg(&program)
+        .output();
+    match output {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("skipping, no C compiler: {} not found", compiler);
+            None
+        }
+        Err(e) => panic!("running {}: {}", compiler, e),
+        Ok(output) => {
+            assert!(output.status.success(), "compiling {} failed:\n{}", source, String::from_utf8_lossy(&output.stderr));
+            Some(program)
+        }
+    }
+}
+
+/// A stand-in for a Wing on ports of its own on the loopback interface: answers discovery, keeps the values set and sends
+/// them back when requested, and sends meter packets for meter requests. Ids with a 0xdf byte,
+/// which need escaping, are not supported.
+struct StandIn {
+    discovery_port: u16,
+    native_port: u16,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+enum Request {
+    Skip,
+    Get(i32),
+    SetFloat(i32, f32),
+    Meters { port: u16, id: u16, meters: Vec<Meter> },
+}
+
+impl StandIn {
+    fn start() -> Self {
+        // not the ports of the Wing, so a console on the network doesn't answer and parallel runs
+        // don't collide
+        let discovery = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let discovery_port = discovery.local_addr().unwrap().port();
+        let native_port = listener.local_addr().unwrap().port();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut threads = Vec::new();
+
+        let discovery_stop = stop.clone();
+        threads.push(std::thread::spawn(move || {
+            discovery.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+            let mut buf = [0u8; 64];
+            while !discovery_stop.load(Ordering::Relaxed) {
+                if let Ok((len, from)) = discovery.recv_from(&mut buf) {
+                    if &buf[..len] == b"WING?" {
+                        let _ = discovery.send_to(b"WING,127.0.0.1,StandIn,ngc-full,S0000001,3.0.5", from);
+                    }
+                }
+            }
+        }));
+
+        let accept_stop = stop.clone();
+        threads.push(std::thread::spawn(move || {
+            listener.set_nonblocking(true).unwrap();
+            let mut clients = Vec::new();
+            while !accept_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let stop = accept_stop.clone();
+                        clients.push(std::thread::spawn(move || serve(stream, stop)));
+                    }
+                    Err(_) => std::thread::sleep(Duration::from_millis(10)),
+                }
+            }
+            for client in clients {
+                let _ = client.join();
+            }
+        }));
+
+        StandIn { discovery_port, native_port, stop, threads }
+    }
+}
+
+impl Drop for StandIn {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The next request in the bytes from the client, and its length, or `None` if it isn't complete
+fn parse(buf: &[u8]) -> Option<(usize, Request)> {
+    let b = |i: usize| buf.get(i).copied();
+    match (b(0)?, b(1)?) {
+        // channel select
+        (0xdf, 0xd1) => Some((2, Request::Skip)),
+        // meter keepalive
+        (0xdf, 0xd3) if b(2)? == 0xd4 => b(6).map(|_| (7, Request::Skip)),
+        // df d3 d3 port d4 id port dc, then the meters up to de
+        (0xdf, 0xd3) => {
+            let port = u16::from_be_bytes([b(3)?, b(4)?]);
+            let id = u16::from_be_bytes([b(6)?, b(7)?]);
+            let mut meters = Vec::new();
+            let mut i = 11;
+            while b(i)? != 0xde {
+                let kind = b(i)?;
+                // the monitor and RTA meters have no index
+                let (code, len) = match kind {
+                    0xa9 | 0xaa => ((kind as u16) << 8, 1),
+                    _ => (((kind as u16) << 8) | b(i + 1)? as u16, 2),
+                };
+                meters.push(Meter::from_code(code).expect("unknown meter in request"));
+                i += len;
+            }
+            Some((i + 1, Request::Meters { port, id, meters }))
+        }
+        (0xd7, _) => {
+            let id = i32::from_be_bytes([b(1)?, b(2)?, b(3)?, b(4)?]);
+            match b(5)? {
+                0xdc => Some((6, Request::Get(id))),
+                0xd5 => Some((10, Request::SetFloat(id, f32::from_be_bytes([b(6)?, b(7)?, b(8)?, b(9)?])))),
+                _ => Some((6, Request::Skip)),
+            }
+        }
+        _ => Some((1, Request::Skip)),
+    }
+}
+
+fn serve(mut stream: TcpStream, stop: Arc<AtomicBool>) {
+    stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    let mut values = HashMap::new();
+    // stops the meters of this client when it disconnects
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let mut meter_threads = Vec::new();
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !stop.load(Ordering::Relaxed) {
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(_) => break,
+        }
+        while let Some((len, request)) = parse(&buf) {
+            buf.drain(..len);
+            match request {
+                Request::Skip => {}
+                Request::SetFloat(id, value) => {
+                    values.insert(id, value);
+                }
+                Request::Get(id) => {
+                    let mut reply = vec![0xd7];
+                    reply.extend(id.to_be_bytes());
+                    reply.push(0xd5);
+                    reply.extend(values.get(&id).copied().unwrap_or(0.0).to_be_bytes());
+                    reply.push(0xde);
+                    if stream.write_all(&reply).is_err() {
+                        return;
+                    }
+                }
+                Request::Meters { port, id, meters } => {
+                    let (stop, disconnected) = (stop.clone(), disconnected.clone());
+                    meter_threads.push(std::thread::spawn(move || send_meters(port, id, &meters, &[&stop, &disconnected])));
+                }
+            }
+        }
+    }
+    disconnected.store(true, Ordering::Relaxed);
+    for thread in meter_threads {
+        let _ = thread.join();
+    }
+}
+
+/// Sends a meter packet every 20 ms, with value i as i - 20 dB, until one of `stop` is set
+fn send_meters(port: u16, id: u16, meters: &[Meter], stop: &[&AtomicBool]) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let count: usize = meters.iter().map(|m| m.value_count()).sum();
+    let mut packet = id.to_be_bytes().to_vec();
+    packet.extend([0, 0]);
+    for i in 0..count {
+        packet.extend(((i as i16 - 20) * 256).to_be_bytes());
+    }
+    while !stop.iter().any(|s| s.load(Ordering::Relaxed)) {
+        let _ = socket.send_to(&packet, ("127.0.0.1", port));
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn c_program_against_stand_in_console() {
+    let Some(program) = compile("capi.c") else { return };
+    let stand_in = StandIn::start();
+    let output = Command::new(&program)
+        .arg(stand_in.discovery_port.to_string())
+        .arg(stand_in.native_port.to_string())
+        .output().unwrap();
+    drop(stand_in);
+    assert!(output.status.success(), "capi failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,89 @@
+//! Checks that the typed API from `codegen::generate()` compiles against the library, for an
+//! empty schema like the one built from empty-propmap.rs and for a small schema with every node
+//! type.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use libwing::codegen;
+use libwing::schema::Schema;
+
+const SCHEMA: &str = r#"
+{"fullname":"/ch","id":1,"type":"node"}
+{"fullname":"/ch/1","id":2,"type":"node"}
+{"fullname":"/ch/2","id":3,"type":"node"}
+{"fullname":"/ch/1/fdr","id":10,"type":"fader level","unit":"dB"}
+{"fullname":"/ch/2/fdr","id":11,"type":"fader level","unit":"dB"}
+{"fullname":"/ch/1/mute","id":12,"type":"integer","minint":0,"maxint":1}
+{"fullname":"/ch/2/mute","id":13,"type":"integer","minint":0,"maxint":1}
+{"fullname":"/ch/1/name","id":14,"type":"string","maxstringlen":16}
+{"fullname":"/ch/1/eq","id":15,"type":"node"}
+{"fullname":"/ch/1/eq/mdl","id":16,"type":"string enum","items":[{"item":"STD"},{"item":"SOUL","longitem":"Soul"}]}
+{"fullname":"/ch/1/eq/1/f","id":17,"type":"log float","unit":"Hz","minfloat":20,"maxfloat":20000}
+{"fullname":"/ch/1/eq/2/f","id":18,"type":"log float","unit":"Hz","minfloat":20,"maxfloat":20000}
+{"fullname":"/ch/1/dly","id":19,"type":"linear float","unit":"ms","minfloat":0,"maxfloat":500}
+{"fullname":"/ch/1/dly/on","id":20,"type":"integer","minint":0,"maxint":1}
+{"fullname":"/cfg/rate","id":30,"type":"float enum","items":[{"item":44100},{"item":48000}]}
+{"fullname":"/cfg/serial","id":31,"type":"string","read_only":true}
+"#;
+
+/// Calls into the generated API, so the test also checks the types and method chains
+const USAGE: &str = r#"
+pub fn usage(wing: &mut libwing::WingConsole) -> Result<(), libwing::Error> {
+    wing.ch(1).fdr().set(-6.0)?;
+    let _: i32 = wing.ch(2).mute().get()?;
+    let _: String = wing.ch(1).name().get()?;
+    wing.ch(1).eq().mdl().set(ChEqMdlValue::Soul)?;
+    let _: f32 = wing.ch(1).eq().at(2).f().get()?;
+    wing.ch(1).dly().set(10.0)?;
+    wing.ch(1).dly_node().on().set(1)?;
+    let _ = wing.cfg().rate().get()?;
+    let _: String = wing.cfg().serial().get()?;
+    Ok(())
+}
+"#;
+
+/// Compiles generated source as a library crate against libwing, and fails with the compiler's
+/// errors if it doesn't build
+fn check(name: &str, source: &str) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    // the test runs from target/<profile>/deps, the rlib is in target/<profile>
+    let lib_dir = std::env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--lib", "--manifest-path"]).arg(root.join("Cargo.toml"));
+    if lib_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    assert!(build.status().unwrap().success(), "building the library failed");
+
+    let file = tmp.join(format!("{}.rs", name));
+    std::fs::write(&file, source).unwrap();
+    // rustc is next to cargo in a toolchain
+    let rustc = Path::new(env!("CARGO")).with_file_name("rustc");
+    let rustc = if rustc.exists() { rustc } else { PathBuf::from("rustc") };
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "--crate-type", "lib", "--emit", "metadata", "--crate-name", name])
+        .arg("-L").arg(format!("dependency={}", lib_dir.join("deps").display()))
+        .arg("--extern").arg(format!("libwing={}", lib_dir.join("liblibwing.rlib").display()))
+        .arg("--out-dir").arg(&tmp)
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{} does not compile:\n{}", file.display(), String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn empty_schema_compiles() {
+    check("params_empty", &codegen::generate(&Schema::new()));
+}
+
+#[test]
+fn generated_params_compile() {
+    let schema = Schema::from_jsonl(SCHEMA).unwrap();
+    let source = codegen::generate(&schema);
+    // /ch/1/dly is a property with children of its own
+    assert!(source.contains("fn dly(self) -> Param<'a, f32>"));
+    assert!(source.contains("fn dly_node(self) -> ChDly<'a>"));
+    check("params_fixture", &(source + USAGE));
+}
@@ -0,0 +1,39 @@
+mod utils;
+use utils::Args;
+
+use std::net::SocketAddr;
+use std::result::Result;
+
+use libwing::WingConsole;
+use libwing::oscbridge::OscBridge;
+use libwing::osc::OSC_PORT;
+
+fn main() -> Result<(),libwing::Error> {
+    let mut args = Args::new(r#"
+Usage: oscbridge [-h host] [-p port]
+
+   -h host : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
+   -p port : UDP port to listen for OSC on. Default is 2223, the Wing's own OSC port.
+
+   OSC addresses are property names like /ch/1/fdr, or patterns like /ch/*/mute. A message
+   without arguments gets the value, a message with an argument sets it, and /*s subscribes
+   to changes for 10 seconds.
+"#);
+    let mut host = None;
+    let mut port = OSC_PORT;
+    while args.has_next() {
+        match args.next().as_str() {
+            "-h" => host = Some(args.next()),
+            "-p" => match args.next().parse() {
+                Ok(p) => port = p,
+                Err(_) => { args.print_help(Some("invalid port")); std::process::exit(1); }
+            },
+            _ => { args.print_help(None); std::process::exit(1); }
+        }
+    }
+
+    let wing = WingConsole::connect(host.as_deref())?;
+    let bridge = OscBridge::new(wing, SocketAddr::from(([0, 0, 0, 0], port)))?;
+    println!("Connected! Listening for OSC on {}", bridge.local_addr()?);
+    bridge.run()
+}
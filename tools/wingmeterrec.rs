@@ -0,0 +1,114 @@
+mod utils;
+use utils::Args;
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::result::Result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use libwing::{WingConsole, Meter};
+use libwing::recording::{MeterRecorder, MeterPlayer};
+
+fn main() -> Result<(),libwing::Error> {
+    let mut args = Args::new(r#"
+Usage: wingmeterrec [-h host] record file [meter...]
+       wingmeterrec csv file [output.csv]
+
+   -h host : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
+   record  : Records the meters to a file until stopped with Ctrl-C. Meters are named like ch1,
+             aux2, bus3, main1, mtx1, dca1, fx1, src1, out1, mon and rta. Default is ch1 to ch16.
+   csv     : Writes the levels of a recording as CSV, one row per frame and one column per
+             level, to a file or to stdout.
+"#);
+    let mut host = None;
+    let mut command = args.next();
+    if command == "-h" { host = Some(args.next()); command = args.next(); }
+
+    match command.as_str() {
+        "record" => {
+            let file = args.next();
+            let mut meters = Vec::new();
+            while args.has_next() {
+                let name = args.next();
+                match Meter::from_name(&name) {
+                    Some(m) => meters.push(m),
+                    None => { args.print_help(Some(&format!("unknown meter: {}", name))); std::process::exit(1); }
+                }
+            }
+            if meters.is_empty() {
+                meters = (0..16).map(Meter::Channel).collect();
+            }
+            record(host.as_deref(), &file, &meters)
+        }
+        "csv" => {
+            let file = args.next();
+            let output = if args.has_next() { Some(args.next()) } else { None };
+            match output {
+                Some(path) => csv(&file, std::io::BufWriter::new(std::fs::File::create(path)?)),
+                None => csv(&file, std::io::stdout().lock()),
+            }
+        }
+        _ => { args.print_help(None); std::process::exit(1); }
+    }
+}
+
+fn record(host: Option<&str>, file: &str, meters: &[Meter]) -> Result<(),libwing::Error> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, interrupted.clone())?;
+
+    let mut wing = WingConsole::connect(host)?;
+    wing.request_meter(meters)?;
+    let mut recorder = MeterRecorder::create(file)?;
+    eprintln!("Recording to {}, press Ctrl-C to stop", file);
+
+    let mut frames = 0;
+    while !interrupted.load(Ordering::Relaxed) {
+        match recorder.record_from(&mut wing) {
+            Ok(true) => {
+                frames += 1;
+                eprint!("\r{} frames, {} skipped", frames, recorder.skipped());
+            }
+            Ok(false) => {}
+            Err(_) if interrupted.load(Ordering::Relaxed) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    let skipped = recorder.skipped();
+    recorder.finish()?;
+    eprintln!("\rRecorded {} frames to {}, skipped {} bad packets", frames, file, skipped);
+    Ok(())
+}
+
+fn csv<W: Write>(file: &str, mut out: W) -> Result<(),libwing::Error> {
+    // the columns are every level that appears in the recording, in the order they first appear
+    let mut columns = Vec::new();
+    let mut column_index = HashMap::new();
+    let mut player = MeterPlayer::open(file)?;
+    while let Some((_, frame)) = player.next_frame()? {
+        for (meter, values) in &frame.meters {
+            for name in values.level_names() {
+                let column = format!("{}.{}", meter.name(), name);
+                if !column_index.contains_key(&column) {
+                    column_index.insert(column.clone(), columns.len());
+                    columns.push(column);
+                }
+            }
+        }
+    }
+
+    writeln!(out, "time,id,{}", columns.join(","))?;
+    let mut player = MeterPlayer::open(file)?;
+    let mut row = vec![String::new(); columns.len()];
+    while let Some((time, frame)) = player.next_frame()? {
+        row.iter_mut().for_each(|c| c.clear());
+        for (meter, values) in &frame.meters {
+            for (name, db) in values.level_names().into_iter().zip(values.levels()) {
+                row[column_index[&format!("{}.{}", meter.name(), name)]] = format!("{:.2}", db);
+            }
+        }
+        writeln!(out, "{:.3},{},{}", time.as_secs_f64(), frame.id, row.join(","))?;
+    }
+    out.flush()?;
+    Ok(())
+}
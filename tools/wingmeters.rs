@@ -3,7 +3,7 @@ use utils::Args;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use eframe::egui::{self, vec2, Rect, Color32, RichText, FontId, Pos2};
-use libwing::{WingConsole, Meter};
+use libwing::{WingConsole, Meter, MeterProcessor, StripMeter, RtaSpectrum, FeedbackDetector, FeedbackCandidate};
 
 // Number of channels to request meters for max 40
 const CHANNEL_COUNT: u8 = 16;
@@ -29,6 +29,7 @@ Usage: wingmeters [-h host]
 
     let mut wing = WingConsole::connect(host.as_deref())?;
     wing.request_meter(&meters)?;
+    wing.request_meter(&[Meter::Rta])?;
 
     eframe::run_native(
         "Wing Meters",
@@ -40,21 +41,39 @@ Usage: wingmeters [-h host]
 }
 
 struct WingMetersApp {
-    meters: Arc<RwLock<Vec<f32>>>,
+    meters: Arc<RwLock<MeterProcessor>>,
+    spectrum: Arc<RwLock<Option<Spectrum>>>,
+}
+
+/// The latest RTA spectrum and the peaks in it that may be feedback
+struct Spectrum {
+    rta: RtaSpectrum,
+    feedback: Vec<FeedbackCandidate>,
+}
+
+/// Level, peak and clip of one side of a channel, with the levels scaled from -60..0 dB to 0..1
+struct MeterDisplay {
+    level: f32,
+    peak: f32,
+    clipped: bool,
 }
 
 impl WingMetersApp {
     fn new(mut wing: WingConsole) -> Self {
-        let meters = Arc::new(RwLock::new(vec![0.0; (CHANNEL_COUNT * 2).into()]));
+        let meters = Arc::new(RwLock::new(MeterProcessor::default()));
+        let spectrum = Arc::new(RwLock::new(None));
         let m = meters.clone();
+        let s = spectrum.clone();
 
         let _ = thread::spawn(move || {
+            let mut detector = FeedbackDetector::new(Default::default());
             loop {
-                if let Ok((_, values)) = wing.read_meters() {
-                    let mut vals = m.write().unwrap();
-                    for i in 0..CHANNEL_COUNT.into() {
-                        vals[2*i]   = ((values[i*8 + 2] as f32 / 256.0 + 60.0) / 60.0).clamp(0.0, 1.0);
-                        vals[2*i+1] = ((values[i*8 + 3] as f32 / 256.0 + 60.0) / 60.0).clamp(0.0, 1.0);
+                if let Ok(frame) = wing.read_meters() {
+                    if let Some(rta) = frame.rta() {
+                        let feedback = detector.process(rta);
+                        *s.write().unwrap() = Some(Spectrum { rta: rta.clone(), feedback });
+                    } else {
+                        m.write().unwrap().process(&frame);
                     }
                 }
             }
@@ -62,22 +81,101 @@ impl WingMetersApp {
 
         Self {
             meters,
+            spectrum,
+        }
+    }
+
+    fn display(&self, channel: u8, index: usize) -> MeterDisplay {
+        let scale = |db: f32| ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+        match self.meters.read().unwrap().get(&Meter::Channel(channel), index) {
+            Some(b) => MeterDisplay { level: scale(b.level), peak: scale(b.peak), clipped: b.clipped },
+            None => MeterDisplay { level: 0.0, peak: 0.0, clipped: false },
         }
     }
 }
 
+fn meter_color(level: f32) -> Color32 {
+    if level > 0.9 {
+        Color32::RED
+    } else if level > 0.7 {
+        Color32::YELLOW
+    } else {
+        Color32::GREEN
+    }
+}
+
+/// Draws the spectrum in 1/3 octave bands, with the bins that may be feedback in red
+fn draw_spectrum(ui: &mut egui::Ui, spectrum: &Spectrum) {
+    let (_id, rect) = ui.allocate_space(ui.available_size());
+    ui.painter().rect_filled(rect, 0.0, Color32::from_gray(32));
+    let scale = |db: f32| ((db + 90.0) / 90.0).clamp(0.0, 1.0);
+
+    let bands = spectrum.rta.average(4);
+    let width = rect.width() / bands.len() as f32;
+    for (i, band) in bands.iter().enumerate() {
+        let left = rect.left() + i as f32 * width;
+        let top = rect.bottom() - rect.height() * scale(band.level);
+        ui.painter().rect_filled(
+            Rect::from_min_max(Pos2::new(left + 1.0, top), Pos2::new(left + width - 1.0, rect.bottom())),
+            0.0,
+            Color32::from_rgb(64, 160, 255),
+        );
+    }
+
+    // the bins are placed by frequency, 12 to an octave like the bands above
+    let bin_width = rect.width() / spectrum.rta.bins.len() as f32;
+    for c in &spectrum.feedback {
+        let x = rect.left() + (c.bin as f32 + 0.5) * bin_width;
+        let top = rect.bottom() - rect.height() * scale(c.level);
+        ui.painter().rect_filled(Rect::from_min_max(Pos2::new(x - 1.5, top), Pos2::new(x + 1.5, rect.bottom())), 0.0, Color32::RED);
+        ui.painter().text(Pos2::new(x, top - 2.0), egui::Align2::CENTER_BOTTOM,
+            format!("{:.0} Hz", c.frequency), FontId::proportional(10.0), Color32::RED);
+    }
+}
+
+/// Draws one meter bar with its peak line, and a red top when it has clipped
+fn draw_meter(ui: &egui::Ui, rect: Rect, m: &MeterDisplay) {
+    let height = rect.height();
+    ui.painter().rect_filled(rect, 0.0, if m.clipped { Color32::from_rgb(96, 32, 32) } else { Color32::from_gray(64) });
+    ui.painter().rect_filled(
+        Rect::from_min_max(Pos2::new(rect.left(), rect.bottom() - height * m.level), rect.max),
+        0.0,
+        meter_color(m.level),
+    );
+    let peak_y = rect.bottom() - height * m.peak;
+    ui.painter().rect_filled(
+        Rect::from_min_max(Pos2::new(rect.left(), peak_y - 1.0), Pos2::new(rect.right(), peak_y + 1.0)),
+        0.0,
+        meter_color(m.peak),
+    );
+    if m.clipped {
+        ui.painter().rect_filled(Rect::from_min_size(rect.min, vec2(rect.width(), 4.0)), 0.0, Color32::RED);
+    }
+}
+
 impl eframe::App for WingMetersApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // clicking anywhere clears the clip indicators
+        if ctx.input(|i| i.pointer.any_click()) {
+            self.meters.write().unwrap().reset_latches();
+        }
+
+        egui::TopBottomPanel::bottom("spectrum").exact_height(150.0).show(ctx, |ui| {
+            match self.spectrum.read().unwrap().as_ref() {
+                Some(spectrum) => draw_spectrum(ui, spectrum),
+                None => { ui.label("Waiting for RTA data"); }
+            }
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            let vals = self.meters.read().unwrap();
-            let num_cols = vals.len()/2;
+            let num_cols = CHANNEL_COUNT as usize;
 
-            ui.columns(num_cols, |col| {
-                for i in 0..num_cols {
-                    let left = vals[2*i];
-                    let right = vals[2*i+1];
+            ui.columns(num_cols, |cols| {
+                for (i, col) in cols.iter_mut().enumerate() {
+                    let left = self.display(i as u8, StripMeter::OUTPUT_LEFT);
+                    let right = self.display(i as u8, StripMeter::OUTPUT_RIGHT);
 
-                    col[i].vertical(|ui| {
+                    col.vertical(|ui| {
                         ui.vertical_centered(|ui| {
                             ui.label(RichText::new(format!("CH{}", i + 1)).font(FontId::proportional(10.0)));
                         });
@@ -96,52 +194,8 @@ impl eframe::App for WingMetersApp {
                         let meter_height = ui.available_height();
                         let (_id, rect) = ui.allocate_space(vec2(ui.available_width(), meter_height));
 
-                        // Draw meter value
-                        let color = if left > 0.9 {
-                            Color32::RED
-                        } else if left > 0.7 {
-                            Color32::YELLOW
-                        } else {
-                            Color32::GREEN
-                        };
-
-                        // bg left
-                        ui.painter().rect_filled(
-                            Rect::from_min_size(
-                                Pos2::new(rect.left(), rect.bottom() - meter_height),
-                                vec2(rect.width()/2.0-1.0, meter_height),
-                            ),
-                            0.0,
-                            Color32::from_gray(64),
-                        );
-                        // fg left
-                        ui.painter().rect_filled(
-                            Rect::from_min_size(
-                                Pos2::new(rect.left(), rect.bottom() - meter_height * left),
-                                vec2(rect.width()/2.0-1.0, meter_height * left),
-                            ),
-                            0.0,
-                            color,
-                        );
-
-                        // bg right
-                        ui.painter().rect_filled(
-                            Rect::from_min_max(
-                                Pos2::new(rect.left() + rect.width()/2.0+1.0, rect.bottom() - meter_height),
-                                rect.max,
-                            ),
-                            0.0,
-                            Color32::from_gray(64),
-                        );
-                        // fg right
-                        ui.painter().rect_filled(
-                            Rect::from_min_max(
-                                Pos2::new(rect.left() + rect.width()/2.0+1.0, rect.bottom() - meter_height * right),
-                                rect.max,
-                            ),
-                            0.0,
-                            color,
-                        );
+                        draw_meter(ui, Rect::from_min_max(rect.min, Pos2::new(rect.center().x - 1.0, rect.bottom())), &left);
+                        draw_meter(ui, Rect::from_min_max(Pos2::new(rect.center().x + 1.0, rect.top()), rect.max), &right);
                     });
                 }
             });
@@ -15,15 +15,20 @@ Usage: wingmon [-h host]
     if args.has_next() && args.next() == "-h" { host = Some(args.next()); }
 
     let mut wing = WingConsole::connect(host.as_deref())?;
+    wing.sync_models()?;
     println!("Connected!");
 
     loop {
         if let WingResponse::NodeData(id, data) =  wing.read()? {
+            if let Some((name, def)) = wing.resolve_id(id) {
+                println!("{} = {}", name, def.format_value(&data));
+                continue;
+            }
             match WingConsole::id_to_defs(id) {
                 None => println!("<Unknown:{}> = {}", id, data.get_string()),
                 Some(defs) if defs.is_empty() => println!("<Unknown:{}> = {}", id, data.get_string()),
                 Some(defs) if defs.len() == 1 => {
-                    println!("{} = {}", defs[0].0, data.get_string());
+                    println!("{} = {}", defs[0].0, defs[0].1.format_value(&data));
                 }
                 Some(defs) if (defs.len() > 1) => {
                     let u = std::collections::HashSet::<u16>::from_iter(defs.iter().map(|(_, def)| def.index));
@@ -3,19 +3,25 @@ use utils::Args;
 
 use std::result::Result;
 
-use libwing::{WingConsole, WingResponse, WingNodeDef, NodeType};
+use libwing::{WingConsole, WingResponse, WingNodeDef, WingNodeData, NodeType};
+use libwing::schema::PathPattern;
 
 fn main() -> Result<(),libwing::Error> {
     let mut args = Args::new(r#"
 Usage: wingprop [-h host] [-j] property[=value|?]
 
-   -h host : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
-   -j      : Prints JSON of the value or definition.
+   -h host  : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
+   -j       : Prints JSON of the value or definition.
+   property : A property name or id, or a pattern matching many property names.
+              In a pattern, * matches any name, ** matches any number of levels,
+              {1..8} matches a range of numbers and {a,b} matches alternatives.
 
    examples:
-       wingprop /main/1/mute=1 # set a property
-       wingprop /main/1/mute   # get a property's value
-       wingprop /main/1/mute?  # get a property's definition
+       wingprop /main/1/mute=1      # set a property
+       wingprop /main/1/mute        # get a property's value
+       wingprop /main/1/mute?       # get a property's definition
+       wingprop '/ch/*/fdr'         # get the faders of all channels
+       wingprop '/bus/{1..8}/mute=0' # unmute buses 1 to 8
 
 "#);
     let mut host = None;
@@ -25,6 +31,11 @@ Usage: wingprop [-h host] [-j] property[=value|?]
     if arg == "-h" { host = Some(args.next()); arg = args.next(); }
     if arg == "-j" { jsonoutput = true; arg = args.next(); }
 
+    let name = arg.trim_end_matches('?').split('=').next().unwrap_or_default();
+    if PathPattern::is_pattern(name) {
+        return pattern_action(host.as_deref(), &arg, jsonoutput);
+    }
+
     #[derive(Debug)]
     enum Action {
         Lookup,
@@ -33,23 +44,29 @@ Usage: wingprop [-h host] [-j] property[=value|?]
     }
 
     let propname;
-    let propid;
-    let proptype;
-    let propparentid;
+    let propdef: WingNodeDef;
 
-    fn parse_id(name: &str) -> (i32, i32, String, NodeType) {
-        let propid;
-        let propparentid;
+    // an id that maps to several names is resolved against the models loaded on the mixer, so
+    // this may connect early; the connection is kept in `wing` for later
+    fn parse_id(name: &str, host: Option<&str>, wing: &mut Option<WingConsole>) -> Result<(String, WingNodeDef),libwing::Error> {
         let propname;
-        let proptype;
+        let propdef;
 
         if let Ok(id) = name.parse::<i32>() {
-            propid = id;
             if let Some(defs) = WingConsole::id_to_defs(id) {
-                if defs.len() == 1 {
-                    proptype = defs[0].1.node_type;
-                    propparentid = defs[0].1.parent_id;
-                    propname = defs[0].0.clone();
+                let resolved = if defs.len() == 1 {
+                    Some(defs[0].clone())
+                } else {
+                    if wing.is_none() {
+                        let mut w = WingConsole::connect(host)?;
+                        w.sync_models()?;
+                        *wing = Some(w);
+                    }
+                    wing.as_ref().unwrap().resolve_id(id)
+                };
+                if let Some((name, def)) = resolved {
+                    propdef = def;
+                    propname = name;
                 } else {
                     eprintln!("property id {} maps to multiple names, which may have different types. Use a full name please:", id);
                     eprintln!();
@@ -66,30 +83,30 @@ Usage: wingprop [-h host] [-j] property[=value|?]
         } else {
             propname = name.to_string();
             if let Some(def) = WingConsole::name_to_def(name) {
-                propid = def.id;
-                proptype = def.node_type;
-                propparentid = def.parent_id;
+                propdef = def.clone();
             } else {
                 eprintln!("invalid property name: {}", name);
                 std::process::exit(1);
             }
         }
-        (propid, propparentid, propname, proptype)
+        Ok((propname, propdef))
     }
 
+    let mut wing = None;
+
     let action = 
         if arg.ends_with("?") {
             let name = arg.trim_end_matches("?");
-            (propid, propparentid, propname, proptype) = parse_id(name);
+            (propname, propdef) = parse_id(name, host.as_deref(), &mut wing)?;
             Action::Definition
 
         } else {
             let parts:Vec<&str> = arg.split("=").collect();
             if parts.len() == 2 {
-                (propid, propparentid, propname, proptype) = parse_id(parts[0]);
+                (propname, propdef) = parse_id(parts[0], host.as_deref(), &mut wing)?;
                 Action::Set(parts[1].to_string())
             } else if parts.len() == 1 {
-                (propid, propparentid, propname, proptype) = parse_id(parts[0]);
+                (propname, propdef) = parse_id(parts[0], host.as_deref(), &mut wing)?;
                 Action::Lookup
             } else {
                 eprintln!("invalid argument. only 1 equals allowed.");
@@ -97,8 +114,15 @@ Usage: wingprop [-h host] [-j] property[=value|?]
             }
         };
 
-    let mut wing = WingConsole::connect(host.as_deref())?;
-    
+    let propid = propdef.id;
+    let propparentid = propdef.parent_id;
+    let proptype = propdef.node_type;
+
+    let mut wing = match wing {
+        Some(wing) => wing,
+        None => WingConsole::connect(host.as_deref())?,
+    };
+
     match action {
         Action::Lookup => {
             if proptype == NodeType::Node {
@@ -108,39 +132,19 @@ Usage: wingprop [-h host] [-j] property[=value|?]
             }
         },
         Action::Set(val) => {
-            match proptype {
-                NodeType::Node => {
-                    eprintln!("Can not set node {} because it's a node, and not a property.", propname);
+            if proptype == NodeType::Node {
+                eprintln!("Can not set node {} because it's a node, and not a property.", propname);
+                std::process::exit(1);
+            }
+            match propdef.parse_value(&val) {
+                Some(data) => wing.set_data(&propdef, &data)?,
+                None => {
+                    eprintln!("Invalid value for property {}: {}. Run `wingprop {}?` to see its definition.", propname, val, propname);
                     std::process::exit(1);
-                },
-                NodeType::StringEnum |
-                NodeType::String => {
-                    wing.set_string(propid, &val)?;
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    std::process::exit(0);
-                },
-                NodeType::Integer => {
-                    if let Ok(v) = val.parse::<i32>() {
-                        wing.set_int(propid, v)?;
-                    } else {
-                        eprintln!("Property {} is an integer, but that was not passed: {}", propname, val);
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    std::process::exit(0);
-                },
-                NodeType::FloatEnum |
-                NodeType::FaderLevel |
-                NodeType::LogarithmicFloat |
-                NodeType::LinearFloat => {
-                    if let Ok(v) = val.parse::<f32>() {
-                        wing.set_float(propid, v)?;
-                    } else {
-                        eprintln!("Property {} is a floating point number, but that was not passed: {}", propname, val);
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    std::process::exit(0);
                 }
             }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::process::exit(0);
         },
         Action::Definition => {
             if proptype == NodeType::Node {
@@ -190,7 +194,7 @@ Usage: wingprop [-h host] [-j] property[=value|?]
                             if jsonoutput {
                                 println!("{}", data.get_string());
                             } else {
-                                println!("{} = {}", propname, data.get_string());
+                                println!("{} = {}", propname, propdef.format_value(&data));
                             }
                         },
                     }
@@ -215,3 +219,56 @@ Usage: wingprop [-h host] [-j] property[=value|?]
         }
     }
 }
+
+fn pattern_action(host: Option<&str>, arg: &str, jsonoutput: bool) -> Result<(),libwing::Error> {
+    if let Some(pattern) = arg.strip_suffix('?') {
+        let defs = WingConsole::find_names(pattern)?;
+        if jsonoutput {
+            let mut ret = jzon::array![ ];
+            for (name, def) in defs {
+                let mut json = def.to_json();
+                json.insert("fullname", name).unwrap();
+                ret.push(json).unwrap();
+            }
+            println!("{}", ret);
+        } else {
+            for (name, def) in defs {
+                println!("Property:  {}", name);
+                println!("{}", def.to_description());
+                println!();
+            }
+        }
+        return Ok(());
+    }
+
+    let mut wing = WingConsole::connect(host)?;
+
+    if let Some((pattern, val)) = arg.split_once('=') {
+        let names = wing.set_many(pattern, &WingNodeData::with_string(val.to_string()))?;
+        if names.is_empty() {
+            eprintln!("no settable properties match {}", pattern);
+            std::process::exit(1);
+        }
+        for name in names {
+            println!("{} = {}", name, val);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    } else {
+        let values = wing.get_many(arg, std::time::Duration::from_secs(5))?;
+        if jsonoutput {
+            let mut ret = jzon::object!{ };
+            for (name, data) in values {
+                ret.insert(&name, data.get_string()).unwrap();
+            }
+            println!("{}", ret);
+        } else {
+            for (name, data) in values {
+                match WingConsole::name_to_def(&name) {
+                    Some(def) => println!("{} = {}", name, def.format_value(&data)),
+                    None => println!("{} = {}", name, data.get_string()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,140 +1,140 @@
-mod utils; 
+mod utils;
 use utils::Args;
 
-use std::fs::File;
 use std::io::Write;
 use std::result::Result;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-use libwing::{WingConsole, WingResponse, WingNodeDef};
+use libwing::WingConsole;
+use libwing::crawler::Crawler;
+use libwing::schema::Schema;
 
-fn get_node_def(wing: &mut WingConsole, parents: Vec<i32>) -> Vec<Vec<WingNodeDef>> {
-    for parent in &parents {
-        wing.request_node_definition(*parent).unwrap();
-    }
+const CHECKPOINT: &str = "wingschema.checkpoint";
 
-    let mut ret = Vec::new();
-    for parent in &parents {
-        let mut ret2 = Vec::new();
-        loop {
-            match wing.read().unwrap() {
-                WingResponse::NodeData(_, _) => { }
-                WingResponse::NodeDef(def) => {
-                    if def.parent_id == *parent {
-                        ret2.push(def);
-                    }
-                }
-                WingResponse::RequestEnd => {
-                    break;
-                }
+fn main() -> Result<(),libwing::Error> {
+    let mut args = Args::new(r#"
+Usage: wingschema [-h host] [-c count] [-n]
+       wingschema -g propmap.jsonl
+
+   -h host  : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
+   -c count : Number of connections to send requests on. Default is 1.
+   -n       : Dry run. Only lists the model properties that would be changed, including the ones inside
+              models as far as the built-in property map knows them, and does not write any files.
+   -g file  : Do not connect to a mixer, only generate params.rs, propmap.schema.json and propmap.d.ts
+              from an existing propmap.jsonl.
+
+Progress is saved to wingschema.checkpoint while running. If the tool is stopped or
+the connection drops, run it again to continue where it left off.
+"#);
+    let mut host = None;
+    let mut connections = 1;
+    let mut dry_run = false;
+    while args.has_next() {
+        match args.next().as_str() {
+            "-h" => host = Some(args.next()),
+            "-c" => connections = args.next().parse().unwrap_or_else(|_| { args.print_help(Some("invalid connection count")); std::process::exit(1) }),
+            "-n" | "--dry-run" => dry_run = true,
+            "-g" => {
+                let schema = Schema::load_jsonl(args.next())?;
+                write_generated(&schema);
+                return Ok(());
             }
+            _ => { args.print_help(None); std::process::exit(1); }
         }
-        ret.push(ret2);
     }
-    ret
-}
 
-fn add(cnt: usize, wing: &mut WingConsole, json_file: &mut File, raw: &mut Vec<u8>, parent_fullname: &str, nodes: &[WingNodeDef], ignore: bool) -> usize {
-    let mut cnt = cnt;
-    if !ignore {
-        if let Some(mdl_def) = nodes.iter().find(|x| &x.name == "mdl" && x.node_type == libwing::NodeType::StringEnum) {
-            let children = get_node_def(wing, nodes.iter().map(|x| x.id).collect());
-            cnt += children.len();
-
-            for (i, def) in nodes.iter().enumerate() {
-                if def.index != 0 { continue; }
-                let fullname =
-                    if def.name.is_empty() {
-                        String::new() + parent_fullname + "/" + &def.index.to_string()[..]
-                    } else {
-                        String::new() + parent_fullname + "/" + &def.name[..]
-                    };
-
-                let mut json = def.to_json();
-                json.insert("fullname", fullname.clone()).unwrap();
-                // println!("{}", jzon::stringify(json.clone()));
-                writeln!(json_file, "{}", jzon::stringify(json)).unwrap();
-                raw.push(0);
-                for b in (fullname.len() as u16).to_be_bytes() { raw.push(b); }
-                for b in fullname.clone().into_bytes() { raw.push(b); }
-                for b in (def.raw.len() as u16).to_be_bytes() { raw.push(b); }
-                raw.append(&mut def.raw.clone());
-
-                cnt = add(cnt, wing, json_file, raw, &fullname, &children[i], false);
-            }
-
-            for item in mdl_def.string_enum.as_ref().unwrap().iter() {
-                let parent_fullname = String::new() + parent_fullname + "/" + &item.item;
-                wing.set_string(mdl_def.id, &item.item).unwrap();
-                let children = get_node_def(wing, Vec::from([mdl_def.parent_id]));
-                cnt = add(cnt, wing, json_file, raw, &parent_fullname, &children[0], true);
-            }
+    if !dry_run {
+        // print out a message asking the user if it is ok to connect and get the schema, which
+        // will temporarily change the properties of the device. ask them on the commandline and
+        // let them type "yes" to continue.
+        println!(r#"
+This tool will connect to a Behringer Wing Mixer on your network and get the
+schema of all properties. To do so it has to load every FX, EQ and dynamics
+model in turn. The current models and their settings are saved first and put
+back when the tool finishes, fails, or is interrupted with Ctrl-C, but you
+should still save a snapshot you can restore after this process is complete.
+Run with -n to see which properties would be changed.
 
-            return cnt;
+Do you have a backup snapshot you can restore after, and want to continue?
+"#);
+        print!("Enter 'yes' to continue: ");
+        std::io::stdout().flush().unwrap();
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+        if input.trim().to_lowercase() != "yes" {
+            println!("Aborting");
+            return Ok(());
         }
-    }
-
-    let children = get_node_def(wing, nodes.iter().map(|x| x.id).collect());
-    cnt += children.len();
-
-    for (i, def) in nodes.iter().enumerate() {
-        // skip mdl and other nodes since we handled them above
-        if !ignore || def.index != 0 {
-            let fullname =
-                if def.name.is_empty() {
-                    String::new() + parent_fullname + "/" + &def.index.to_string()[..]
-                } else {
-                    String::new() + parent_fullname + "/" + &def.name[..]
-                };
-
-            let mut json = def.to_json();
-            json.insert("fullname", fullname.clone()).unwrap();
-            // println!("{}", jzon::stringify(json.clone()));
-            writeln!(json_file, "{}", jzon::stringify(json)).unwrap();
-            raw.push(if ignore { def.index as u8 } else { 0 });
-            for b in (fullname.len() as u16).to_be_bytes() { raw.push(b); }
-            for b in fullname.clone().into_bytes() { raw.push(b); }
-            for b in (def.raw.len() as u16).to_be_bytes() { raw.push(b); }
-            raw.append(&mut def.raw.clone());
-
-            cnt = add(cnt, wing, json_file, raw, &fullname, &children[i], false);
+        if std::path::Path::new(CHECKPOINT).exists() {
+            println!("Resuming from {}", CHECKPOINT);
         }
     }
-    print!("\rReceived {} nodes", cnt);
-    cnt
-}
-fn main() -> Result<(),libwing::Error> {
-    let mut args = Args::new(r#"
-Usage: wingschema [-h host]
-
-   -h host : IP address or hostname of Wing mixer. Default is to discover and connect to the first mixer found.
-"#);
-    let mut host = None;
-    if args.has_next() && args.next() == "-h" { host = Some(args.next()); }
 
-    // print out a message asking the user if it is ok to connect and get the schema, which WILL
-    // change the properties of the device, so you should have had a saved snapshot. ask them on
-    // the commandline and let them type "yes" to continue.
-    println!(r#"
-This tool will connect to a Behringer Wing Mixer on your network and get the
-schema of all properties. It will change the properties of the device in a
-destructive manner, so you should have had a saved snapshot you can restore
-after this process is complete.
-
-THIS IS A DESTRUCTIVE OPERATION AND CAN NOT BE UNDONE WITHOUT
-REINITIALIZING YOUR MIXER FROM SCRATCH.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, interrupted.clone())?;
+
+    // extra connections have to go to the same mixer as the first one
+    let host = match host {
+        Some(host) => host,
+        None => WingConsole::scan(true)?.into_iter().next().ok_or(libwing::Error::DiscoveryError)?.ip,
+    };
+    let mut wing = WingConsole::connect(Some(&host))?;
+
+    let mut crawler = Crawler::new(&mut wing)
+        .stop_flag(interrupted.clone())
+        .cycle_models(!dry_run)
+        .on_progress(|p| {
+            print!("\rReceived {} nodes", p.nodes);
+            if p.models_done > 0 {
+                print!(", {} of {} models", p.models_done, p.models_total);
+            }
+            std::io::stdout().flush().unwrap();
+        });
+    if !dry_run {
+        crawler = crawler.checkpoint(CHECKPOINT);
+    }
+    for _ in 1..connections {
+        crawler = crawler.add_connection(WingConsole::connect(Some(&host))?);
+    }
 
-Do you have a backup snapshot you can restore after, and want to continue?
-"#);
-    print!("Enter 'yes' to continue: ");
-    std::io::stdout().flush().unwrap();
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    if input.trim().to_lowercase() != "yes" {
-        println!("Aborting");
+    let result = match crawler.run() {
+        Ok(result) => result,
+        Err(e) => {
+            println!();
+            eprintln!("Stopped: {}", e);
+            if crawler.needs_restore() {
+                eprintln!("Restoring the original models and settings failed. Load your snapshot to get them back.");
+            } else if !dry_run {
+                eprintln!("Run wingschema again to continue.");
+            }
+            std::process::exit(1);
+        }
+    };
+    drop(crawler);
+
+    if dry_run {
+        println!();
+        for slot in &result.models {
+            let items: Vec<&str> = slot.mdl.string_enum.iter().flatten().map(|x| &x.item[..]).collect();
+            let current = wing.get_data(slot.mdl.id)?.get_string();
+            println!("{}/mdl: would cycle through {} and restore {}", slot.fullname, items.join(", "), current);
+            // the slots inside a model only show up once it is loaded, so they come from the
+            // built-in property map
+            let own = format!("{}/mdl", slot.fullname);
+            for (name, def) in WingConsole::find_names(&format!("{}/**/mdl", slot.fullname))? {
+                if name == own {
+                    continue;
+                }
+                let items: Vec<&str> = def.string_enum.iter().flatten().map(|x| &x.item[..]).collect();
+                println!("  {}: would cycle through {} and restore it", name, items.join(", "));
+            }
+        }
         return Ok(());
     }
 
-    let mut wing = WingConsole::connect(host.as_deref())?;
+    print!("\nFinishing up... ");
+    std::io::stdout().flush().unwrap();
 
     let mut json_file = std::fs::OpenOptions::new()
         .write(true)
@@ -142,6 +142,18 @@ Do you have a backup snapshot you can restore after, and want to continue?
         .truncate(true)
         .open("propmap.jsonl")
         .unwrap();
+    let mut raw = Vec::new();
+    for entry in &result.entries {
+        let mut json = entry.def.to_json();
+        json.insert("fullname", entry.fullname.clone()).unwrap();
+        writeln!(json_file, "{}", jzon::stringify(json)).unwrap();
+        raw.push(entry.fake);
+        raw.extend_from_slice(&(entry.fullname.len() as u16).to_be_bytes());
+        raw.extend_from_slice(entry.fullname.as_bytes());
+        raw.extend_from_slice(&(entry.def.raw.len() as u16).to_be_bytes());
+        raw.extend_from_slice(&entry.def.raw);
+    }
+    drop(json_file);
 
     let mut rust_file = std::fs::OpenOptions::new()
         .write(true)
@@ -153,12 +165,6 @@ Do you have a backup snapshot you can restore after, and want to continue?
     writeln!(rust_file, "use std::collections::HashMap;").unwrap();
     writeln!(rust_file, "use crate::node::WingNodeDef;").unwrap();
     writeln!(rust_file, "lazy_static::lazy_static! {{").unwrap();
-    let mut raw = Vec::<u8>::new();
-    let children = get_node_def(&mut wing, Vec::from([0]));
-    add(1, &mut wing, &mut json_file, &mut raw, "", &children[0], false);
-    print!("\nFinishing up... ");
-    std::io::stdout().flush().unwrap();
-
     writeln!(rust_file, "    pub static ref NAME_TO_DEF: HashMap<String, WingNodeDef> = {{").unwrap();
     writeln!(rust_file, "        let mut m = HashMap::new();").unwrap();
     write!(  rust_file, "        let d = b\"").unwrap();
@@ -182,7 +188,15 @@ Do you have a backup snapshot you can restore after, and want to continue?
     writeln!(rust_file, "    }};").unwrap();
     writeln!(rust_file, "}}").unwrap();
 
+    write_generated(&Schema::load_jsonl("propmap.jsonl")?);
 
     println!("done");
     Ok(())
 }
+
+/// Writes the typed Rust API, the JSON Schema and the TypeScript declarations
+fn write_generated(schema: &Schema) {
+    std::fs::write("params.rs", libwing::codegen::generate(schema)).unwrap();
+    std::fs::write("propmap.schema.json", jzon::stringify_pretty(libwing::export::json_schema(schema), 2)).unwrap();
+    std::fs::write("propmap.d.ts", libwing::export::typescript(schema)).unwrap();
+}
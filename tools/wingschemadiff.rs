@@ -0,0 +1,44 @@
+mod utils;
+use utils::Args;
+
+use std::result::Result;
+
+use libwing::schema::{self, Schema};
+
+fn main() -> Result<(),libwing::Error> {
+    let mut args = Args::new(r#"
+Usage: wingschemadiff [-j] old new
+
+   -j      : Prints the differences as JSON.
+   old new : propmap.jsonl files to compare, as written by wingschema. Use "builtin" for
+             the property map built into libwing.
+
+   examples:
+       wingschemadiff propmap-3.0.5.jsonl propmap-3.1.0.jsonl
+       wingschemadiff builtin propmap.jsonl
+
+"#);
+    let mut jsonoutput = false;
+
+    let mut arg = args.next();
+    if arg == "-j" { jsonoutput = true; arg = args.next(); }
+    let old = load(&arg)?;
+    let new = load(&args.next())?;
+
+    let d = schema::diff(&old, &new);
+    if jsonoutput {
+        println!("{}", d.to_json());
+    } else {
+        print!("{}", d.to_text());
+    }
+
+    std::process::exit(if d.is_empty() { 0 } else { 1 });
+}
+
+fn load(name: &str) -> Result<Schema, libwing::Error> {
+    if name == "builtin" {
+        Ok(Schema::builtin())
+    } else {
+        Schema::load_jsonl(name)
+    }
+}